@@ -82,7 +82,7 @@ impl PoolPushHandler {
 				.and_then(move |tx: Transaction| {
 					let source = pool::TxSource {
 						debug_name: "push-api".to_string(),
-						identifier: "?.?.?.?".to_string(),
+						identifier: pool::LOCAL_SOURCE_IDENTIFIER.to_string(),
 					};
 					info!(
 						"Pushing transaction {} to pool (inputs: {}, outputs: {}, kernels: {})",
@@ -98,10 +98,10 @@ impl PoolPushHandler {
 						.blockchain
 						.chain_head()
 						.context(ErrorKind::Internal("Failed to get chain head".to_owned()))?;
-					let res = tx_pool
+					tx_pool
 						.add_to_pool(source, tx, !fluff, &header)
 						.context(ErrorKind::Internal("Failed to update pool".to_owned()))?;
-					Ok(res)
+					Ok(())
 				}),
 		)
 	}
@@ -15,38 +15,45 @@
 //! Facade and handler for the rest of the blockchain implementation
 //! and mostly the chain pipeline.
 
+use crate::core::consensus;
+use crate::core::core::committed;
 use crate::core::core::hash::{Hash, Hashed, ZERO_HASH};
 use crate::core::core::merkle_proof::MerkleProof;
 use crate::core::core::verifier_cache::VerifierCache;
 use crate::core::core::{
-	Block, BlockHeader, BlockSums, Committed, Output, OutputIdentifier, Transaction, TxKernelEntry,
+	Block, BlockHeader, BlockSums, Committed, Output, OutputIdentifier, Transaction, TxKernel,
+	TxKernelEntry,
 };
 use crate::core::global;
 use crate::core::pow;
-use crate::core::ser::{Readable, StreamingReader};
+use crate::core::ser::{self, Readable, StreamingReader};
 use crate::error::{Error, ErrorKind};
+use crate::keychain::BlindingFactor;
 use crate::pipe;
 use crate::store;
 use crate::txhashset;
 use crate::txhashset::TxHashSet;
 use crate::types::{
-	BlockStatus, ChainAdapter, NoStatus, Options, Tip, TxHashSetRoots, TxHashsetWriteStatus,
+	BlockStatus, ChainAdapter, ChainEvent, DiskUsage, NoStatus, Options, Tip, TxHashSetRoots,
+	TxHashsetWriteStatus, VerificationStats, VerificationTiming,
 };
 use crate::util::secp::pedersen::{Commitment, RangeProof};
-use crate::util::RwLock;
+use crate::util::{static_secp_instance, Mutex, RwLock};
 use grin_store::Error::NotFoundErr;
-use std::collections::HashMap;
+use std::cmp::max;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{self, File};
 use std::io::Read;
 use std::path::PathBuf;
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender};
 use std::sync::Arc;
 use std::time::{Duration, Instant};
 
-/// Orphan pool size is limited by MAX_ORPHAN_SIZE
+/// Default orphan pool size limit, see Chain::set_orphan_limits
 pub const MAX_ORPHAN_SIZE: usize = 200;
 
-/// When evicting, very old orphans are evicted first
+/// Default max age of an orphan, older ones are dropped
 const MAX_ORPHAN_AGE_SECS: u64 = 300;
 
 #[derive(Debug, Clone)]
@@ -62,8 +69,12 @@ pub struct OrphanBlockPool {
 	// additional index of height -> hash
 	// so we can efficiently identify a child block (ex-orphan) after processing a block
 	height_idx: RwLock<HashMap<u64, Vec<Hash>>>,
-	// accumulated number of evicted block because of MAX_ORPHAN_SIZE limitation
+	// accumulated number of evicted (or expired) blocks
 	evicted: AtomicUsize,
+	// max number of orphans, the oldest ones are evicted beyond
+	max_orphans: AtomicUsize,
+	// orphans older than this are dropped
+	max_age_secs: AtomicU64,
 }
 
 impl OrphanBlockPool {
@@ -72,6 +83,8 @@ impl OrphanBlockPool {
 			orphans: RwLock::new(HashMap::new()),
 			height_idx: RwLock::new(HashMap::new()),
 			evicted: AtomicUsize::new(0),
+			max_orphans: AtomicUsize::new(MAX_ORPHAN_SIZE),
+			max_age_secs: AtomicU64::new(MAX_ORPHAN_AGE_SECS),
 		}
 	}
 
@@ -84,37 +97,55 @@ impl OrphanBlockPool {
 		self.evicted.load(Ordering::Relaxed)
 	}
 
+	fn max_orphans(&self) -> usize {
+		self.max_orphans.load(Ordering::Relaxed)
+	}
+
+	fn set_limits(&self, max_orphans: usize, max_age_secs: u64) {
+		self.max_orphans.store(max_orphans, Ordering::Relaxed);
+		self.max_age_secs.store(max_age_secs, Ordering::Relaxed);
+		self.evict();
+	}
+
 	fn add(&self, orphan: Orphan) {
-		let mut orphans = self.orphans.write();
-		let mut height_idx = self.height_idx.write();
 		{
+			let mut orphans = self.orphans.write();
+			let mut height_idx = self.height_idx.write();
 			let height_hashes = height_idx
 				.entry(orphan.block.header.height)
 				.or_insert(vec![]);
 			height_hashes.push(orphan.block.hash());
 			orphans.insert(orphan.block.hash(), orphan);
 		}
+		self.evict();
+	}
 
-		if orphans.len() > MAX_ORPHAN_SIZE {
-			let old_len = orphans.len();
-
-			// evict too old
-			orphans.retain(|_, ref mut x| {
-				x.added.elapsed() < Duration::from_secs(MAX_ORPHAN_AGE_SECS)
-			});
-			// evict too far ahead
-			let mut heights = height_idx.keys().cloned().collect::<Vec<u64>>();
-			heights.sort_unstable();
-			for h in heights.iter().rev() {
-				if let Some(hs) = height_idx.remove(h) {
-					for h in hs {
-						let _ = orphans.remove(&h);
-					}
-				}
-				if orphans.len() < MAX_ORPHAN_SIZE {
-					break;
-				}
+	/// Drops the expired orphans, then the oldest ones if the pool is still
+	/// above its limit.
+	fn evict(&self) {
+		let mut orphans = self.orphans.write();
+		let mut height_idx = self.height_idx.write();
+		let old_len = orphans.len();
+
+		// evict too old
+		let max_age = Duration::from_secs(self.max_age_secs.load(Ordering::Relaxed));
+		orphans.retain(|_, ref mut x| x.added.elapsed() < max_age);
+
+		// evict the oldest when full
+		let max_orphans = self.max_orphans();
+		if orphans.len() > max_orphans {
+			let mut by_age = orphans
+				.iter()
+				.map(|(h, x)| (x.added, h.clone()))
+				.collect::<Vec<_>>();
+			by_age.sort_by_key(|(added, _)| *added);
+			let excess = orphans.len() - max_orphans;
+			for (_, h) in by_age.into_iter().take(excess) {
+				let _ = orphans.remove(&h);
 			}
+		}
+
+		if orphans.len() < old_len {
 			// cleanup index
 			height_idx.retain(|_, ref mut xs| xs.iter().any(|x| orphans.contains_key(&x)));
 
@@ -146,9 +177,16 @@ pub struct Chain {
 	db_root: String,
 	store: Arc<store::ChainStore>,
 	adapter: Arc<dyn ChainAdapter + Send + Sync>,
+	// Channels notified of every accepted block, see subscribe
+	subscribers: Mutex<Vec<Sender<ChainEvent>>>,
 	orphans: Arc<OrphanBlockPool>,
 	txhashset: Arc<RwLock<txhashset::TxHashSet>>,
 	verifier_cache: Arc<RwLock<dyn VerifierCache>>,
+	verification_stats: Arc<RwLock<VerificationStats>>,
+	// Per block hash locks of the blocks being processed, see process_block
+	processing: Mutex<HashMap<Hash, Arc<Mutex<()>>>>,
+	// Trusted checkpoints, block hash by height
+	checkpoints: Arc<RwLock<BTreeMap<u64, Hash>>>,
 	// POW verification function
 	pow_verifier: fn(&BlockHeader) -> Result<(), pow::Error>,
 	archive_mode: bool,
@@ -167,6 +205,8 @@ impl Chain {
 		verifier_cache: Arc<RwLock<dyn VerifierCache>>,
 		archive_mode: bool,
 	) -> Result<Chain, Error> {
+		validate_genesis_mmr_sizes(&genesis)?;
+
 		let store = Arc::new(store::ChainStore::new(&db_root)?);
 
 		// open the txhashset, creating a new one if necessary
@@ -179,15 +219,29 @@ impl Chain {
 			db_root,
 			store,
 			adapter,
+			subscribers: Mutex::new(vec![]),
 			orphans: Arc::new(OrphanBlockPool::new()),
 			txhashset: Arc::new(RwLock::new(txhashset)),
 			pow_verifier,
 			verifier_cache,
+			verification_stats: Arc::new(RwLock::new(VerificationStats::default())),
+			processing: Mutex::new(HashMap::new()),
+			checkpoints: Arc::new(RwLock::new(global::checkpoints().into_iter().collect())),
 			archive_mode,
 			genesis: genesis.header.clone(),
 		})
 	}
 
+	/// Subscribe to the accepted blocks. The returned channel receives an
+	/// event for every block accepted from now on, in the order they are
+	/// accepted. Any number of subscribers can coexist with the adapter.
+	/// Dropping the receiver ends the subscription.
+	pub fn subscribe(&self) -> Receiver<ChainEvent> {
+		let (tx, rx) = mpsc::channel();
+		self.subscribers.lock().push(tx);
+		rx
+	}
+
 	/// Return our shared txhashset instance.
 	pub fn txhashset(&self) -> Arc<RwLock<TxHashSet>> {
 		self.txhashset.clone()
@@ -239,10 +293,46 @@ impl Chain {
 	}
 
 	/// Processes a single block, then checks for orphans, processing
-	/// those as well if they're found
+	/// those as well if they're found.
+	/// Concurrent calls for the same block are serialized, only the first one
+	/// does the work. The others see the block as already known and return
+	/// without updating the head.
 	pub fn process_block(&self, b: Block, opts: Options) -> Result<Option<Tip>, Error> {
 		let height = b.header.height;
-		let res = self.process_block_single(b, opts);
+		let hash = b.hash();
+
+		let (lock, in_flight) = {
+			let mut processing = self.processing.lock();
+			let in_flight = processing.contains_key(&hash);
+			let lock = processing
+				.entry(hash)
+				.or_insert_with(|| Arc::new(Mutex::new(())))
+				.clone();
+			(lock, in_flight)
+		};
+
+		let res = {
+			let _guard = lock.lock();
+			// errors are left to the regular processing to report
+			if in_flight && self.block_exists(hash).unwrap_or(false) {
+				debug!(
+					"process_block: {} at {} already processed concurrently",
+					hash, height
+				);
+				Ok(None)
+			} else {
+				self.process_block_single(b, opts)
+			}
+		};
+
+		{
+			// last one out cleans up, the map holds the other reference
+			let mut processing = self.processing.lock();
+			if Arc::strong_count(&lock) == 2 {
+				processing.remove(&hash);
+			}
+		}
+
 		if res.is_ok() {
 			self.check_orphans(height + 1);
 		}
@@ -251,23 +341,20 @@ impl Chain {
 
 	fn determine_status(&self, head: Option<Tip>, prev_head: Tip) -> BlockStatus {
 		// We have more work if the chain head is updated.
-		let is_more_work = head.is_some();
-
-		let mut is_next_block = false;
-		let mut reorg_depth = None;
-		if let Some(head) = head {
-			if head.prev_block_h == prev_head.last_block_h {
-				is_next_block = true;
-			} else {
-				reorg_depth = Some(prev_head.height.saturating_sub(head.height) + 1);
+		match head {
+			None => BlockStatus::Fork,
+			Some(head) => {
+				if head.prev_block_h == prev_head.last_block_h {
+					BlockStatus::Next
+				} else {
+					BlockStatus::Reorg {
+						depth: prev_head.height.saturating_sub(head.height) + 1,
+						prev_head: prev_head.last_block_h,
+						new_head: head.last_block_h,
+					}
+				}
 			}
 		}
-
-		match (is_more_work, is_next_block) {
-			(true, true) => BlockStatus::Next,
-			(true, false) => BlockStatus::Reorg(reorg_depth.unwrap_or(0)),
-			(false, _) => BlockStatus::Fork,
-		}
 	}
 
 	/// Attempt to add a new block to the chain.
@@ -300,6 +387,14 @@ impl Chain {
 				let status = self.determine_status(head.clone(), prev_head);
 
 				// notifying other parts of the system of the update
+				let event = ChainEvent::Accepted {
+					hash: b.hash(),
+					height: b.header.height,
+					status: status.clone(),
+				};
+				self.subscribers
+					.lock()
+					.retain(|tx| tx.send(event.clone()).is_ok());
 				self.adapter.block_accepted(&b, status, opts);
 
 				Ok(head)
@@ -307,6 +402,10 @@ impl Chain {
 			Err(e) => match e.kind() {
 				ErrorKind::Orphan => {
 					let block_hash = b.hash();
+
+					// notifying other parts of the system the parent is missing
+					self.adapter.block_orphaned(&b);
+
 					let orphan = Orphan {
 						block: b,
 						opts: opts,
@@ -336,6 +435,15 @@ impl Chain {
 					);
 					Err(ErrorKind::Unfit(msg.clone()).into())
 				}
+				ErrorKind::CheckpointMismatch { height } => {
+					info!(
+						"Rejected block {} at {}: conflicts with the checkpoint at {}",
+						b.hash(),
+						b.header.height,
+						height
+					);
+					Err(ErrorKind::CheckpointMismatch { height }.into())
+				}
 				_ => {
 					info!(
 						"Rejected block {} at {}: {:?}",
@@ -387,9 +495,25 @@ impl Chain {
 			txhashset,
 			batch,
 			orphans: self.orphans.clone(),
+			verification_stats: self.verification_stats.clone(),
+			checkpoints: self.checkpoints.clone(),
 		})
 	}
 
+	/// Adds a trusted checkpoint on top of the hardcoded ones (see
+	/// global::checkpoints), any block at this height with another hash is
+	/// rejected from now on.
+	pub fn add_checkpoint(&self, height: u64, hash: Hash) {
+		self.checkpoints.write().insert(height, hash);
+	}
+
+	/// Average time spent verifying a rangeproof and a kernel signature over
+	/// the recently processed blocks (the ones found in the verifier cache
+	/// don't count).
+	pub fn verification_timing(&self) -> VerificationTiming {
+		self.verification_stats.read().timing()
+	}
+
 	/// Check if hash is for a known orphan.
 	pub fn is_orphan(&self, hash: &Hash) -> bool {
 		self.orphans.contains(hash)
@@ -484,6 +608,131 @@ impl Chain {
 		Ok(bh.height + 1)
 	}
 
+	/// Coinbase value (block reward plus the provided fees) for the next block
+	/// built on top of the current chain head.
+	pub fn next_coinbase_value(&self, fees: u64) -> Result<u64, Error> {
+		let height = self.next_block_height()?;
+		Ok(consensus::reward(fees, height))
+	}
+
+	/// Total number of coins in existence at the current chain head, block
+	/// rewards plus the genesis reward if the genesis block had one.
+	pub fn total_supply(&self) -> Result<u64, Error> {
+		let head = self.head()?;
+		let genesis_had_reward = self.genesis.kernel_mmr_size > 0;
+		Ok(consensus::calc_mwc_block_overage(head.height, genesis_had_reward))
+	}
+
+	/// Net change in emitted subsidy when the disconnected blocks are replaced
+	/// by the connected ones in a reorg. Only the block subsidy is accounted
+	/// for, the fees just move from one coinbase to another.
+	pub fn reorg_subsidy_delta(&self, disconnected: &[Block], connected: &[Block]) -> i64 {
+		let subsidy = |blocks: &[Block]| -> i64 {
+			blocks
+				.iter()
+				.map(|b| consensus::reward_components(b.header.height, 0).subsidy as i64)
+				.sum()
+		};
+		subsidy(connected) - subsidy(disconnected)
+	}
+
+	/// Total fees collected by the blocks in the (inclusive) height range on the
+	/// main chain. The fees of a block are what its coinbase gets on top of the
+	/// block subsidy, summed here from the block kernels as the coinbase value
+	/// itself is hidden. Requires the full blocks, pruned ones are an error.
+	pub fn cumulative_fees(&self, from: u64, to: u64) -> Result<u64, Error> {
+		if from > to {
+			return Err(ErrorKind::Other(format!("invalid height range {}..{}", from, to)).into());
+		}
+		let mut fees = 0;
+		for height in from..=to {
+			let hash = self.get_header_hash_by_height(height)?;
+			fees += self.get_block(&hash)?.total_fees();
+		}
+		Ok(fees)
+	}
+
+	/// Scan the coinbase outputs of the blocks in the (inclusive) height range
+	/// on the main chain for the provided commitments, typically the ones a
+	/// wallet could have mined. Returns the matching outputs along with the
+	/// height of the block they were found in.
+	/// Requires the full blocks, pruned ones are an error.
+	pub fn scan_coinbase_outputs(
+		&self,
+		from: u64,
+		to: u64,
+		commits: &HashSet<Commitment>,
+	) -> Result<Vec<(u64, OutputIdentifier)>, Error> {
+		if from > to {
+			return Err(ErrorKind::Other(format!("invalid height range {}..{}", from, to)).into());
+		}
+		let mut found = vec![];
+		for height in from..=to {
+			let hash = self.get_header_hash_by_height(height)?;
+			let block = self.get_block(&hash)?;
+			for out in block.outputs() {
+				if out.is_coinbase() && commits.contains(&out.commitment()) {
+					found.push((height, OutputIdentifier::from_output(out)));
+				}
+			}
+		}
+		Ok(found)
+	}
+
+	/// Validate the transactions selected for a block built on top of the
+	/// current chain head, in order, against the UTXO set and each other.
+	/// Points back to the offending transaction (by kernel) with an
+	/// InvalidBlockTx error, so it can be dropped and the block rebuilt.
+	pub fn validate_block_txs(&self, txs: &[Transaction]) -> Result<(), Error> {
+		let height = self.next_block_height()?;
+		let txhashset = self.txhashset.read();
+		txhashset::utxo_view(&txhashset, |utxo| {
+			let mut created = HashSet::new();
+			let mut spent = HashSet::new();
+			for tx in txs {
+				let res = (|| -> Result<(), Error> {
+					if tx.lock_height() > height {
+						return Err(ErrorKind::TxLockHeight.into());
+					}
+					for output in tx.outputs() {
+						if created.contains(&output.commitment()) {
+							return Err(ErrorKind::DuplicateUtxo {
+								commit: output.commitment(),
+							}
+							.into());
+						}
+						utxo.validate_output(output)?;
+					}
+					for input in tx.inputs() {
+						let commit = input.commitment();
+						if spent.contains(&commit) {
+							return Err(ErrorKind::AlreadySpent(commit).into());
+						}
+						// spending an output of a previous tx of the block is fine
+						if !created.contains(&commit) {
+							utxo.validate_input(input)?;
+						}
+					}
+					utxo.verify_coinbase_maturity(&tx.inputs(), height)?;
+					Ok(())
+				})();
+
+				if let Err(e) = res {
+					let kernel = tx.kernels().first().map(|k| k.excess());
+					return Err(ErrorKind::InvalidBlockTx {
+						kernel,
+						reason: format!("{}", e),
+					}
+					.into());
+				}
+
+				spent.extend(tx.inputs().iter().map(|x| x.commitment()));
+				created.extend(tx.outputs().iter().map(|x| x.commitment()));
+			}
+			Ok(())
+		})
+	}
+
 	/// Verify we are not attempting to spend a coinbase output
 	/// that has not yet sufficiently matured.
 	pub fn verify_coinbase_maturity(&self, tx: &Transaction) -> Result<(), Error> {
@@ -495,6 +744,17 @@ impl Chain {
 		})
 	}
 
+	/// Height of the first block the tx can be included in as far as the
+	/// maturity of the coinbase outputs it spends is concerned.
+	/// 0 if it spends no coinbase output.
+	pub fn coinbase_mature_height(&self, tx: &Transaction) -> Result<u64, Error> {
+		let head_height = self.head_header()?.height;
+		let txhashset = self.txhashset.read();
+		txhashset::utxo_view(&txhashset, |utxo| {
+			utxo.coinbase_mature_height(&tx.inputs(), head_height)
+		})
+	}
+
 	/// Verify that the tx has a lock_height that is less than or equal to
 	/// the height of the next block.
 	pub fn verify_tx_lock_height(&self, tx: &Transaction) -> Result<(), Error> {
@@ -508,6 +768,43 @@ impl Chain {
 
 	/// Validate the current chain state.
 	pub fn validate(&self, fast_validation: bool) -> Result<(), Error> {
+		self.validate_with(fast_validation, false)
+	}
+
+	/// Validate the current chain state, verifying the rangeproofs and kernel
+	/// signatures in parallel. Gives the same result as validate.
+	pub fn validate_parallel(&self, fast_validation: bool) -> Result<(), Error> {
+		self.validate_with(fast_validation, true)
+	}
+
+	/// Validate the current chain state like validate, but going through all
+	/// the validation steps and returning every failure instead of stopping at
+	/// the first one. Helps diagnosing a store corrupted in several ways.
+	/// Returns an empty vec if the chain state is valid.
+	pub fn validate_collect_errors(&self, fast_validation: bool) -> Vec<Error> {
+		let header = match self.store.head_header() {
+			Ok(header) => header,
+			Err(e) => return vec![e.into()],
+		};
+
+		// Lets just treat an "empty" node that just got started up as valid.
+		if header.height == 0 {
+			return vec![];
+		}
+
+		let mut txhashset = self.txhashset.write();
+
+		let res = txhashset::extending_readonly(&mut txhashset, |extension| {
+			extension.rewind(&header)?;
+			Ok(extension.validate_collect_errors(fast_validation, &NoStatus))
+		});
+		match res {
+			Ok(errors) => errors,
+			Err(e) => vec![e],
+		}
+	}
+
+	fn validate_with(&self, fast_validation: bool, parallel: bool) -> Result<(), Error> {
 		let header = self.store.head_header()?;
 
 		// Lets just treat an "empty" node that just got started up as valid.
@@ -522,11 +819,121 @@ impl Chain {
 		// ensure the view is consistent.
 		txhashset::extending_readonly(&mut txhashset, |extension| {
 			extension.rewind(&header)?;
-			extension.validate(fast_validation, &NoStatus)?;
+			if parallel {
+				extension.validate_parallel(fast_validation, &NoStatus)?;
+			} else {
+				extension.validate(fast_validation, &NoStatus)?;
+			}
 			Ok(())
 		})
 	}
 
+	/// Rewind the chain to the block at the provided height, for operators
+	/// recovering from a bad local state. Rewinds the txhashset MMRs, resets
+	/// the head, header_head and sync_head to the block at that height and
+	/// removes the rewound blocks so they can be processed again.
+	/// Refuses to rewind beyond the cut-through horizon or the body tail.
+	pub fn rewind_to_height(&self, height: u64) -> Result<Tip, Error> {
+		let mut txhashset = self.txhashset.write();
+		let mut batch = self.store.batch()?;
+
+		let head = batch.head()?;
+		if height > head.height {
+			return Err(ErrorKind::Other(format!(
+				"cannot rewind to {}, head is at {}",
+				height, head.height
+			))
+			.into());
+		}
+
+		let tail = match batch.tail() {
+			Ok(tail) => tail,
+			Err(_) => Tip::from_header(&self.genesis),
+		};
+		let horizon = global::cut_through_horizon() as u64;
+		let min_height = max(tail.height, head.height.saturating_sub(horizon));
+		if height < min_height {
+			return Err(ErrorKind::Other(format!(
+				"cannot rewind to {}, beyond horizon or tail at {}",
+				height, min_height
+			))
+			.into());
+		}
+
+		let header = txhashset.get_header_by_height(height)?;
+
+		// Collect the blocks we are about to rewind, walking back from the head.
+		let mut rewound = vec![];
+		let mut current = batch.head_header()?;
+		while current.height > height {
+			rewound.push(current.hash());
+			current = batch.get_previous_header(&current)?;
+		}
+
+		txhashset::extending(&mut txhashset, &mut batch, |extension| {
+			extension.rewind(&header)?;
+			extension.validate_roots()?;
+			Ok(())
+		})?;
+
+		for hash in rewound {
+			batch.delete_block(&hash)?;
+		}
+
+		let tip = Tip::from_header(&header);
+		batch.save_head(&tip)?;
+		batch.reset_sync_head()?;
+		batch.commit()?;
+
+		info!(
+			"rewind_to_height: rewound chain from {} to {} at {}",
+			head.height,
+			tip.last_block_h,
+			tip.height
+		);
+
+		Ok(tip)
+	}
+
+	/// Validate the total kernel offset of every header from the body tail
+	/// (genesis on an archive node) up to the head against the kernel sums
+	/// committed in the corresponding block_sums.
+	/// The utxo_sum must equal the kernel_sum plus the commitment to the offset.
+	pub fn validate_kernel_offsets(&self) -> Result<(), Error> {
+		let head = self.head()?;
+		let tail_height = self.tail().map(|x| x.height).unwrap_or(0);
+
+		let txhashset = self.txhashset.read();
+		let secp = static_secp_instance();
+
+		// Genesis block_sums are verified when the chain is initialized.
+		for height in max(tail_height, 1)..=head.height {
+			let header = txhashset.get_header_by_height(height)?;
+			let sums = self.get_block_sums(&header.hash())?;
+
+			let offset = header.total_kernel_offset();
+			let kernel_sum_plus_offset = {
+				let secp = secp.lock();
+				let mut commits = vec![sums.kernel_sum];
+				if offset != BlindingFactor::zero() {
+					let key = offset.secret_key(&secp)?;
+					commits.push(secp.commit(0, key)?);
+				}
+				secp.commit_sum(commits, vec![])?
+			};
+
+			if kernel_sum_plus_offset != sums.utxo_sum {
+				error!(
+					"validate_kernel_offsets: kernel offset mismatch at {} @ {}",
+					header.hash(),
+					header.height,
+				);
+				return Err(ErrorKind::Committed(committed::Error::KernelSumMismatch).into());
+			}
+		}
+		Ok(())
+	}
+
 	/// *** Only used in tests. ***
 	/// Convenience for setting roots on a block header when
 	/// creating a chain fork during tests.
@@ -630,6 +1037,33 @@ impl Chain {
 		txhashset.merkle_proof(commit)
 	}
 
+	/// Return a merkle proof of the inclusion of the kernel with the provided
+	/// excess, valid for the current kernel MMR root (the head kernel_root),
+	/// along with the position of the kernel in the kernel MMR.
+	pub fn kernel_merkle_proof(&self, excess: &Commitment) -> Result<(MerkleProof, u64), Error> {
+		let mut txhashset = self.txhashset.write();
+		txhashset.kernel_merkle_proof(excess)
+	}
+
+	/// Verify a merkle proof of the inclusion of the provided kernel at the
+	/// provided kernel MMR position, as returned by kernel_merkle_proof,
+	/// against the kernel root of the current head. Doesn't rely on the local
+	/// kernel index. A proof built before the head moved doesn't verify anymore.
+	pub fn verify_kernel_merkle_proof(
+		&self,
+		kernel: &TxKernel,
+		proof: &MerkleProof,
+		pos: u64,
+	) -> Result<(), Error> {
+		let head = self.head_header()?;
+		if proof.mmr_size != head.kernel_mmr_size {
+			return Err(ErrorKind::MerkleProof.into());
+		}
+		proof
+			.verify(head.kernel_root, kernel, pos)
+			.map_err(|_| ErrorKind::MerkleProof.into())
+	}
+
 	/// Returns current txhashset roots.
 	pub fn get_txhashset_roots(&self) -> TxHashSetRoots {
 		self.txhashset.read().roots()
@@ -937,6 +1371,7 @@ impl Chain {
 			)?;
 
 			extension.rebuild_index()?;
+			extension.rebuild_kernel_index()?;
 			Ok(())
 		})?;
 
@@ -998,13 +1433,13 @@ impl Chain {
 	fn remove_historical_blocks(
 		&self,
 		txhashset: &txhashset::TxHashSet,
+		horizon: u64,
 		batch: &mut store::Batch<'_>,
 	) -> Result<(), Error> {
 		if self.archive_mode {
 			return Ok(());
 		}
 
-		let horizon = global::cut_through_horizon() as u64;
 		let head = batch.head()?;
 
 		let tail = match batch.tail() {
@@ -1052,12 +1487,28 @@ impl Chain {
 	/// * removes historical blocks and associated data from the db (unless archive mode)
 	///
 	pub fn compact(&self) -> Result<(), Error> {
+		self.compact_with_horizon(global::cut_through_horizon())
+	}
+
+	/// Triggers chain compaction with a custom cut-through horizon (in blocks),
+	/// larger to keep more history, smaller to save disk space. The horizon
+	/// cannot be lower than the state sync threshold to stay reorg-safe.
+	pub fn compact_with_horizon(&self, horizon: u32) -> Result<(), Error> {
+		let min_horizon = global::state_sync_threshold();
+		if horizon < min_horizon {
+			return Err(ErrorKind::Other(format!(
+				"compaction horizon {} below state sync threshold {}",
+				horizon, min_horizon
+			))
+			.into());
+		}
+		let horizon = horizon as u64;
+
 		// A node may be restarted multiple times in a short period of time.
 		// We compact at most once per 60 blocks in this situation by comparing
 		// current "head" and "tail" height to our cut-through horizon and
 		// allowing an additional 60 blocks in height before allowing a further compaction.
 		if let (Ok(tail), Ok(head)) = (self.tail(), self.head()) {
-			let horizon = global::cut_through_horizon() as u64;
 			let threshold = horizon.saturating_add(60);
 			debug!(
 				"compact: head: {}, tail: {}, diff: {}, horizon: {}",
@@ -1077,7 +1528,7 @@ impl Chain {
 		let mut batch = self.store.batch()?;
 
 		// Compact the txhashset itself (rewriting the pruned backend files).
-		txhashset.compact(&mut batch)?;
+		txhashset.compact(horizon, &mut batch)?;
 
 		// Rebuild our output_pos index in the db based on current UTXO set.
 		txhashset::extending(&mut txhashset, &mut batch, |extension| {
@@ -1087,7 +1538,7 @@ impl Chain {
 
 		// If we are not in archival mode remove historical blocks from the db.
 		if !self.archive_mode {
-			self.remove_historical_blocks(&txhashset, &mut batch)?;
+			self.remove_historical_blocks(&txhashset, horizon, &mut batch)?;
 		}
 
 		// Commit all the above db changes.
@@ -1096,6 +1547,36 @@ impl Chain {
 		Ok(())
 	}
 
+	/// Rebuild the secondary indexes from the chain data on disk, recovering
+	/// from a corrupted db short of a full resync. The header MMR backing the
+	/// height to hash lookups is rebuilt walking the stored headers from
+	/// genesis, the output positions index from the output MMR and the kernel
+	/// index from the kernel MMR.
+	pub fn reindex(&self) -> Result<(), Error> {
+		let header_head = self.header_head()?;
+		let mut txhashset = self.txhashset.write();
+
+		self.rebuild_header_mmr(&header_head, &mut txhashset)?;
+
+		let mut batch = self.store.batch()?;
+		txhashset::extending(&mut txhashset, &mut batch, |extension| {
+			extension.rebuild_index()?;
+			extension.rebuild_kernel_index()?;
+			Ok(())
+		})?;
+		batch.commit()?;
+
+		Ok(())
+	}
+
+	/// Size on disk of the header, output, rangeproof and kernel MMRs and of
+	/// the whole chain data dir, for capacity planning.
+	pub fn disk_usage(&self) -> Result<DiskUsage, Error> {
+		// Hold the txhashset lock so we do not measure files being rewritten.
+		let _txhashset = self.txhashset.read();
+		txhashset::disk_usage(&self.db_root)
+	}
+
 	/// returns the last n nodes inserted into the output sum tree
 	pub fn get_last_n_output(&self, distance: u64) -> Vec<(Hash, OutputIdentifier)> {
 		self.txhashset.read().last_n_output(distance)
@@ -1148,6 +1629,24 @@ impl Chain {
 		self.orphans.len()
 	}
 
+	/// Number of orphans in the pool, after dropping the expired ones.
+	pub fn orphan_count(&self) -> usize {
+		self.orphans.evict();
+		self.orphans.len()
+	}
+
+	/// Max number of orphans kept in the pool.
+	pub fn max_orphans(&self) -> usize {
+		self.orphans.max_orphans()
+	}
+
+	/// Limits the orphan pool to max_orphans blocks, the oldest orphans are
+	/// evicted first when full, and drops the orphans older than max_age_secs.
+	/// Defaults to MAX_ORPHAN_SIZE orphans and 5 minutes.
+	pub fn set_orphan_limits(&self, max_orphans: usize, max_age_secs: u64) {
+		self.orphans.set_limits(max_orphans, max_age_secs);
+	}
+
 	/// Tip (head) of the block chain.
 	pub fn head(&self) -> Result<Tip, Error> {
 		self.store
@@ -1155,6 +1654,31 @@ impl Chain {
 			.map_err(|e| ErrorKind::StoreErr(e, "chain head".to_owned()).into())
 	}
 
+	/// All the stored chain tips: the head of the main chain and the tip of
+	/// every side branch we still have the blocks of, by decreasing total
+	/// difficulty. A tip is a stored block without any stored child. Reads
+	/// every stored header, meant for monitoring rather than the hot path.
+	pub fn all_tips(&self) -> Result<Vec<Tip>, Error> {
+		let batch = self.store.batch()?;
+		let mut headers = HashMap::new();
+		let mut parents = HashSet::new();
+		for (_, header) in batch.headers_iter()? {
+			let hash = header.hash();
+			// only the headers of the blocks we have, not the header chain
+			if batch.block_exists(&hash)? {
+				parents.insert(header.prev_hash);
+				headers.insert(hash, header);
+			}
+		}
+		let mut tips = headers
+			.iter()
+			.filter(|(hash, _)| !parents.contains(*hash))
+			.map(|(_, header)| Tip::from_header(header))
+			.collect::<Vec<_>>();
+		tips.sort_by(|a, b| b.total_difficulty.cmp(&a.total_difficulty));
+		Ok(tips)
+	}
+
 	/// Tail of the block chain in this node after compact (cross-block cut-through)
 	pub fn tail(&self) -> Result<Tip, Error> {
 		self.store
@@ -1169,6 +1693,19 @@ impl Chain {
 			.map_err(|e| ErrorKind::StoreErr(e, "chain header head".to_owned()).into())
 	}
 
+	/// Tip of the most work header chain we know of, whether the corresponding
+	/// blocks have been processed or not. Can be ahead of the chain head while
+	/// syncing, the block bodies still having to be downloaded.
+	pub fn best_header_tip(&self) -> Result<Tip, Error> {
+		let head = self.head()?;
+		let header_head = self.header_head()?;
+		if head.total_difficulty > header_head.total_difficulty {
+			Ok(head)
+		} else {
+			Ok(header_head)
+		}
+	}
+
 	/// Block header for the chain head
 	pub fn head_header(&self) -> Result<BlockHeader, Error> {
 		self.store
@@ -1197,6 +1734,24 @@ impl Chain {
 			.map_err(|e| ErrorKind::StoreErr(e, "chain get previous header".to_owned()).into())
 	}
 
+	/// Up to the last n block headers ending at the chain head, in height
+	/// order. Walks back the headers only, stopping at genesis.
+	pub fn last_n_headers(&self, n: usize) -> Result<Vec<BlockHeader>, Error> {
+		let mut headers = Vec::with_capacity(n);
+		if n == 0 {
+			return Ok(headers);
+		}
+		let mut header = self.head_header()?;
+		while headers.len() + 1 < n && header.height > 0 {
+			let prev = self.get_previous_header(&header)?;
+			headers.push(header);
+			header = prev;
+		}
+		headers.push(header);
+		headers.reverse();
+		Ok(headers)
+	}
+
 	/// Get block_sums by header hash.
 	pub fn get_block_sums(&self, h: &Hash) -> Result<BlockSums, Error> {
 		self.store
@@ -1204,14 +1759,149 @@ impl Chain {
 			.map_err(|e| ErrorKind::StoreErr(e, "chain get block_sums".to_owned()).into())
 	}
 
+	/// Total transaction fees of the block, indexed when the block was
+	/// accepted. Blocks stored before the index existed are read in full.
+	pub fn block_fees(&self, h: &Hash) -> Result<u64, Error> {
+		match self.store.get_block_fees(h) {
+			Ok(fees) => Ok(fees),
+			Err(NotFoundErr(_)) => Ok(self.get_block(h)?.total_fees()),
+			Err(e) => Err(ErrorKind::StoreErr(e, "chain get block fees".to_owned()).into()),
+		}
+	}
+
 	/// Gets the block header at the provided height.
 	/// Note: Takes a read lock on the txhashset.
 	/// Take care not to call this repeatedly in a tight loop.
+	/// Errors with HeightAboveHead above the header head.
 	pub fn get_header_by_height(&self, height: u64) -> Result<BlockHeader, Error> {
-		let hash = self.get_header_hash_by_height(height)?;
+		let hash = match self.get_header_hash_by_height(height) {
+			Ok(hash) => hash,
+			Err(e) => {
+				let header_head = self.header_head()?;
+				if height > header_head.height {
+					return Err(ErrorKind::HeightAboveHead {
+						height,
+						head: header_head.height,
+					}
+					.into());
+				}
+				return Err(e);
+			}
+		};
 		self.get_block_header(&hash)
 	}
 
+	/// Gets the full block at the provided height on the current chain.
+	/// Errors with HeightAboveHead above the chain head and with HeightPruned
+	/// for a block removed by compaction, below the chain tail.
+	pub fn get_block_by_height(&self, height: u64) -> Result<Block, Error> {
+		let head = self.head()?;
+		if height > head.height {
+			return Err(ErrorKind::HeightAboveHead {
+				height,
+				head: head.height,
+			}
+			.into());
+		}
+		let hash = self.get_header_hash_by_height(height)?;
+		match self.store.get_block(&hash) {
+			Ok(block) => Ok(block),
+			Err(NotFoundErr(e)) => {
+				let tail = self.tail()?;
+				if height < tail.height {
+					Err(ErrorKind::HeightPruned {
+						horizon: tail.height,
+					}
+					.into())
+				} else {
+					Err(ErrorKind::StoreErr(NotFoundErr(e), "chain get block".to_owned()).into())
+				}
+			}
+			Err(e) => Err(ErrorKind::StoreErr(e, "chain get block".to_owned()).into()),
+		}
+	}
+
+	/// Gets the coinbase output of the block at the provided height, on the
+	/// current chain. Errors if the block doesn't have exactly one.
+	pub fn coinbase_output(&self, height: u64) -> Result<Output, Error> {
+		let block = self.get_block_by_height(height)?;
+		let hash = block.hash();
+		let mut coinbase = block.outputs().iter().filter(|out| out.is_coinbase());
+		match (coinbase.next(), coinbase.next()) {
+			(Some(out), None) => Ok(out.clone()),
+			_ => Err(ErrorKind::Other(format!(
+				"block {} at {} doesn't have a single coinbase output",
+				hash, height
+			))
+			.into()),
+		}
+	}
+
+	/// Snapshot of the unspent outputs as of the block at the provided height,
+	/// on the current chain. The output MMR is rewound in a readonly extension
+	/// so the live chain state is left untouched. Refuses heights above the
+	/// head or beyond the cut-through horizon (or body tail).
+	pub fn utxo_snapshot(&self, height: u64) -> Result<Vec<OutputIdentifier>, Error> {
+		let head = self.head()?;
+		if height > head.height {
+			return Err(ErrorKind::HeightAboveHead {
+				height,
+				head: head.height,
+			}
+			.into());
+		}
+		let tail = match self.store.tail() {
+			Ok(tail) => tail,
+			Err(_) => Tip::from_header(&self.genesis),
+		};
+		let horizon = global::cut_through_horizon() as u64;
+		let min_height = max(tail.height, head.height.saturating_sub(horizon));
+		if height < min_height {
+			return Err(ErrorKind::HeightPruned {
+				horizon: min_height,
+			}
+			.into());
+		}
+
+		let header = self.get_header_by_height(height)?;
+		let mut txhashset = self.txhashset.write();
+		txhashset::extending_readonly(&mut txhashset, |extension| {
+			extension.rewind(&header)?;
+			Ok(extension.unspent_outputs())
+		})
+	}
+
+	/// Total difficulty (cumulative work) of the chain up to the block at the
+	/// provided height, read from its header. Errors above the chain head.
+	pub fn total_difficulty_at_height(&self, height: u64) -> Result<pow::Difficulty, Error> {
+		let head = self.head()?;
+		if height > head.height {
+			return Err(ErrorKind::HeightAboveHead {
+				height,
+				head: head.height,
+			}
+			.into());
+		}
+		Ok(self.get_header_by_height(height)?.total_difficulty())
+	}
+
+	/// Median time past of the provided header, the median timestamp (in
+	/// seconds) of the header and its predecessors over the last
+	/// consensus::MEDIAN_TIME_WINDOW blocks. Only a query, block validation
+	/// doesn't enforce it (see consensus::validate_block_time).
+	pub fn median_time_past(&self, header: &BlockHeader) -> Result<u64, Error> {
+		let batch = self.store.batch()?;
+		pipe::median_time_past(header, &batch)
+	}
+
+	/// Gets the timestamp (in seconds) of the block header at the provided
+	/// height, without reading the full header from the db.
+	pub fn block_timestamp(&self, height: u64) -> Result<u64, Error> {
+		let txhashset = self.txhashset.read();
+		let timestamp = txhashset.get_header_timestamp_by_height(height)?;
+		Ok(timestamp)
+	}
+
 	/// Gets the header hash at the provided height.
 	/// Note: Takes a read lock on the txhashset.
 	/// Take care not to call this repeatedly in a tight loop.
@@ -1229,31 +1919,68 @@ impl Chain {
 		let txhashset = self.txhashset.read();
 
 		let (_, pos) = txhashset.is_unspent(output_ref)?;
+		let head = self.head()?;
 
-		let mut min = 0;
-		let mut max = {
-			let head = self.head()?;
-			head.height
-		};
+		header_for_output_pos(&txhashset, pos, 0, head.height)
+	}
 
-		loop {
-			let search_height = max - (max - min) / 2;
-			let h = txhashset.get_header_by_height(search_height)?;
-			if search_height == 0 {
-				return Ok(h);
-			}
-			let h_prev = txhashset.get_header_by_height(search_height - 1)?;
-			if pos > h.output_mmr_size {
-				min = search_height;
-			} else if pos < h_prev.output_mmr_size {
-				max = search_height;
-			} else {
-				if pos == h_prev.output_mmr_size {
-					return Ok(h_prev);
-				}
-				return Ok(h);
+	/// Gets a kernel by its excess commitment, along with the height of the
+	/// block that included it on the current chain. None if the kernel isn't
+	/// on the chain (kernels of blocks processed before the kernel index
+	/// existed can't be found either, until a reindex).
+	pub fn get_kernel_by_excess(
+		&self,
+		excess: &Commitment,
+	) -> Result<Option<(TxKernel, u64)>, Error> {
+		let txhashset = self.txhashset.read();
+		let head = self.head_header()?;
+		match txhashset.get_kernel(excess)? {
+			Some((kernel, pos)) if pos <= head.kernel_mmr_size => {
+				let header = header_for_kernel_pos(&txhashset, pos, head.height)?;
+				Ok(Some((kernel, header.height)))
 			}
+			_ => Ok(None),
+		}
+	}
+
+	/// Gets the block headers in which the given outputs appear in the
+	/// txhashset, preserving the order of the provided outputs.
+	/// Output positions are sorted so each binary search starts from the
+	/// header found for the previous output, outputs in the same block sharing
+	/// the same header lookup.
+	/// Errors if any of the outputs cannot be found.
+	pub fn get_headers_for_outputs(
+		&self,
+		outputs: &[OutputIdentifier],
+	) -> Result<Vec<(OutputIdentifier, BlockHeader)>, Error> {
+		let txhashset = self.txhashset.read();
+
+		let mut positions = Vec::with_capacity(outputs.len());
+		for (i, output_ref) in outputs.iter().enumerate() {
+			let (_, pos) = txhashset.is_unspent(output_ref)?;
+			positions.push((pos, i));
+		}
+		positions.sort_unstable();
+
+		let head = self.head()?;
+		let mut headers: Vec<Option<BlockHeader>> = vec![None; outputs.len()];
+		let mut current: Option<BlockHeader> = None;
+		for (pos, i) in positions {
+			let header = match current {
+				Some(header) if pos <= header.output_mmr_size => header,
+				// The output is in a later block, search after the previous header.
+				Some(header) => header_for_output_pos(&txhashset, pos, header.height, head.height)?,
+				None => header_for_output_pos(&txhashset, pos, 0, head.height)?,
+			};
+			headers[i] = Some(header.clone());
+			current = Some(header);
 		}
+
+		Ok(outputs
+			.iter()
+			.cloned()
+			.zip(headers.into_iter().flatten())
+			.collect())
 	}
 
 	/// Verifies the given block header is actually on the current chain.
@@ -1268,6 +1995,43 @@ impl Chain {
 		}
 	}
 
+	/// Builds a block locator from the current chain head, the hashes of the
+	/// headers at heights head, head - 1, head - 2, head - 4 and so on, down
+	/// to and including the genesis block.
+	pub fn block_locator(&self) -> Result<Vec<Hash>, Error> {
+		let head = self.head()?;
+		let txhashset = self.txhashset.read();
+
+		let mut locator = vec![];
+		let mut offset = 0;
+		loop {
+			let height = head.height.saturating_sub(offset);
+			locator.push(txhashset.get_header_hash_by_height(height)?);
+			if height == 0 {
+				break;
+			}
+			offset = if offset == 0 { 1 } else { offset * 2 };
+		}
+		Ok(locator)
+	}
+
+	/// Finds the most recent header we share with the provided block locator
+	/// (most recent hash first), i.e. the first locator hash that refers to a
+	/// header on our current chain.
+	pub fn locate_fork_point(&self, locator: &[Hash]) -> Result<BlockHeader, Error> {
+		let txhashset = self.txhashset.read();
+		for hash in locator {
+			if let Ok(header) = self.get_block_header(hash) {
+				if let Ok(hash_at_height) = txhashset.get_header_hash_by_height(header.height) {
+					if hash_at_height == *hash {
+						return Ok(header);
+					}
+				}
+			}
+		}
+		Err(ErrorKind::Other(format!("no common header in locator")).into())
+	}
+
 	/// Get the tip of the current "sync" header chain.
 	/// This may be significantly different to current header chain.
 	pub fn get_sync_head(&self) -> Result<Tip, Error> {
@@ -1285,6 +2049,34 @@ impl Chain {
 		Ok(store::DifficultyIter::from(head.last_block_h, store))
 	}
 
+	/// Same as difficulty_iter but stops after at most max headers, bounding
+	/// the memory and I/O spent on very long chains.
+	pub fn difficulty_iter_bounded(&self, max: usize) -> Result<store::DifficultyIter<'_>, Error> {
+		Ok(self.difficulty_iter()?.bounded(max))
+	}
+
+	/// Builds a difficulty iterator starting from the provided header and
+	/// running backward. Useful to compute the next difficulty as of any
+	/// point in the chain history.
+	pub fn difficulty_iter_from(
+		&self,
+		header: &BlockHeader,
+	) -> Result<store::DifficultyIter<'_>, Error> {
+		let store = self.store.clone();
+		Ok(store::DifficultyIter::from(header.hash(), store))
+	}
+
+	/// Exports the difficulty window ending at the header at the provided
+	/// height (on the header chain), serialized to a compact binary blob.
+	/// The difficulty of the next block can be recomputed offline from it
+	/// with `consensus::next_difficulty_from_bytes`.
+	pub fn export_difficulty_window(&self, ending_height: u64) -> Result<Vec<u8>, Error> {
+		let header = self.get_header_by_height(ending_height)?;
+		let window = consensus::dump_difficulty_window(self.difficulty_iter_from(&header)?);
+		let data = ser::ser_vec(&window).map_err(ErrorKind::SerErr)?;
+		Ok(data)
+	}
+
 	/// Check whether we have a block without reading it
 	pub fn block_exists(&self, h: Hash) -> Result<bool, Error> {
 		self.store
@@ -1293,6 +2085,72 @@ impl Chain {
 	}
 }
 
+/// Binary search for the header of the block (after min_height, up to
+/// max_height) that added the output at the provided MMR position.
+fn header_for_output_pos(
+	txhashset: &txhashset::TxHashSet,
+	pos: u64,
+	min_height: u64,
+	max_height: u64,
+) -> Result<BlockHeader, Error> {
+	header_for_mmr_pos(txhashset, pos, min_height, max_height, |h| {
+		h.output_mmr_size
+	})
+}
+
+/// Binary search for the header of the block (up to max_height) that added
+/// the kernel at the provided MMR position.
+fn header_for_kernel_pos(
+	txhashset: &txhashset::TxHashSet,
+	pos: u64,
+	max_height: u64,
+) -> Result<BlockHeader, Error> {
+	header_for_mmr_pos(txhashset, pos, 0, max_height, |h| h.kernel_mmr_size)
+}
+
+/// Binary search for the header of the block (after min_height, up to
+/// max_height) that added the element at the provided MMR position, given the
+/// MMR size of a header.
+fn header_for_mmr_pos(
+	txhashset: &txhashset::TxHashSet,
+	pos: u64,
+	min_height: u64,
+	max_height: u64,
+	mmr_size: fn(&BlockHeader) -> u64,
+) -> Result<BlockHeader, Error> {
+	let mut min = min_height;
+	let mut max = max_height;
+
+	loop {
+		let search_height = max - (max - min) / 2;
+		let h = txhashset.get_header_by_height(search_height)?;
+		if search_height == 0 {
+			return Ok(h);
+		}
+		let h_prev = txhashset.get_header_by_height(search_height - 1)?;
+		if pos > mmr_size(&h) {
+			min = search_height;
+		} else if pos < mmr_size(&h_prev) {
+			max = search_height;
+		} else {
+			if pos == mmr_size(&h_prev) {
+				return Ok(h_prev);
+			}
+			return Ok(h);
+		}
+	}
+}
+
+/// A rewarded genesis block holds a single output and kernel, its header must
+/// commit to MMRs of exactly that size.
+fn validate_genesis_mmr_sizes(genesis: &Block) -> Result<(), Error> {
+	let rewarded = !genesis.outputs().is_empty() || !genesis.kernels().is_empty();
+	if rewarded && (genesis.header.output_mmr_size != 1 || genesis.header.kernel_mmr_size != 1) {
+		return Err(ErrorKind::InvalidGenesisMmrSize.into());
+	}
+	Ok(())
+}
+
 fn setup_head(
 	genesis: &Block,
 	store: &store::ChainStore,
@@ -50,6 +50,15 @@ pub enum ErrorKind {
 	/// Scaling factor between primary and secondary PoW is invalid
 	#[fail(display = "Wrong scaling factor")]
 	InvalidScaling,
+	/// Secondary PoW scaling factor is below the minimum
+	#[fail(display = "Secondary scaling factor below minimum")]
+	InvalidSecondaryScaling,
+	/// Block conflicts with a trusted checkpoint at its height
+	#[fail(display = "Block conflicts with the checkpoint at {}", height)]
+	CheckpointMismatch {
+		/// Height of the checkpoint
+		height: u64,
+	},
 	/// The proof of work is invalid
 	#[fail(display = "Invalid PoW")]
 	InvalidPow,
@@ -71,6 +80,9 @@ pub enum ErrorKind {
 	/// One of the MMR sizes in the block header is invalid
 	#[fail(display = "Invalid MMR Size")]
 	InvalidMMRSize,
+	/// A rewarded genesis block must have an output and kernel MMR size of 1
+	#[fail(display = "Invalid Genesis MMR Size")]
+	InvalidGenesisMmrSize,
 	/// Error from underlying keychain impl
 	#[fail(display = "Keychain Error")]
 	Keychain(keychain::Error),
@@ -83,6 +95,21 @@ pub enum ErrorKind {
 	/// An output with that commitment already exists (should be unique)
 	#[fail(display = "Duplicate Commitment: {:?}", _0)]
 	DuplicateCommitment(Commitment),
+	/// A block output would re-create an output that is already in the UTXO set
+	#[fail(display = "Duplicate UTXO: {:?}", commit)]
+	DuplicateUtxo {
+		/// The duplicated output commitment
+		commit: Commitment,
+	},
+	/// A transaction assembled into a block is invalid against the chain state
+	#[fail(display = "Invalid Block Tx {:?}: {}", kernel, reason)]
+	InvalidBlockTx {
+		/// Excess commitment of the first kernel of the offending transaction,
+		/// None if it has no kernel
+		kernel: Option<Commitment>,
+		/// Why the transaction is invalid
+		reason: String,
+	},
 	/// Attempt to spend a coinbase output before it sufficiently matures.
 	#[fail(display = "Attempt to spend immature coinbase")]
 	ImmatureCoinbase,
@@ -101,6 +128,20 @@ pub enum ErrorKind {
 	/// output spent
 	#[fail(display = "Output is spent")]
 	OutputSpent,
+	/// Requested height is above the chain head
+	#[fail(display = "Height {} above head at {}", height, head)]
+	HeightAboveHead {
+		/// Requested height
+		height: u64,
+		/// Height of the chain head
+		head: u64,
+	},
+	/// Requested block is below the horizon, pruned by compaction
+	#[fail(display = "Height pruned below horizon at {}", horizon)]
+	HeightPruned {
+		/// Height of the oldest full block we still have
+		horizon: u64,
+	},
 	/// Invalid block version, either a mistake or outdated software
 	#[fail(display = "Invalid Block Version: {:?}", _0)]
 	InvalidBlockVersion(block::HeaderVersion),
@@ -184,6 +225,8 @@ impl Error {
 			| ErrorKind::SerErr(_)
 			| ErrorKind::TxHashSetErr(_)
 			| ErrorKind::GenesisBlockRequired
+			| ErrorKind::HeightAboveHead { .. }
+			| ErrorKind::HeightPruned { .. }
 			| ErrorKind::Other(_) => false,
 			_ => true,
 		}
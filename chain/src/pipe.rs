@@ -16,7 +16,7 @@
 
 use crate::chain::OrphanBlockPool;
 use crate::core::consensus;
-use crate::core::core::hash::Hashed;
+use crate::core::core::hash::{Hash, Hashed};
 use crate::core::core::verifier_cache::VerifierCache;
 use crate::core::core::Committed;
 use crate::core::core::{Block, BlockHeader, BlockSums};
@@ -25,12 +25,12 @@ use crate::core::pow;
 use crate::error::{Error, ErrorKind};
 use crate::store;
 use crate::txhashset;
-use crate::types::{Options, Tip};
+use crate::types::{Options, Tip, VerificationStats};
 use crate::util::RwLock;
-use chrono::prelude::Utc;
-use chrono::Duration;
 use grin_store;
+use std::collections::BTreeMap;
 use std::sync::Arc;
+use std::time::Instant;
 
 /// Contextual information required to process a new block and either reject or
 /// accept it.
@@ -47,6 +47,10 @@ pub struct BlockContext<'a> {
 	pub verifier_cache: Arc<RwLock<dyn VerifierCache>>,
 	/// Recent orphan blocks to avoid double-processing
 	pub orphans: Arc<OrphanBlockPool>,
+	/// Time spent verifying rangeproofs and kernel signatures
+	pub verification_stats: Arc<RwLock<VerificationStats>>,
+	/// Trusted checkpoints, block hash by height
+	pub checkpoints: Arc<RwLock<BTreeMap<u64, Hash>>>,
 }
 
 /// Process a block header as part of processing a full block.
@@ -106,13 +110,14 @@ pub fn process_block(b: &Block, ctx: &mut BlockContext<'_>) -> Result<Option<Tip
 	let head = ctx.batch.head()?;
 	let is_next = b.header.prev_hash == head.last_block_h;
 
-	let prev = prev_header_store(&b.header, &mut ctx.batch)?;
-
 	// Block is an orphan if we do not know about the previous full block.
 	// Skip this check if we have just processed the previous block
 	// or the full txhashset state (fast sync) at the previous block height.
-	if !is_next && !ctx.batch.block_exists(&prev.hash())? {
-		return Err(ErrorKind::Orphan.into());
+	if !is_next {
+		let prev = prev_header_store(&b.header, &mut ctx.batch)?;
+		if !ctx.batch.block_exists(&prev.hash())? {
+			return Err(ErrorKind::Orphan.into());
+		}
 	}
 
 	// This is a fork in the context of both header and block processing
@@ -140,6 +145,8 @@ pub fn process_block(b: &Block, ctx: &mut BlockContext<'_>) -> Result<Option<Tip
 		verify_coinbase_maturity(b, &mut extension)?;
 
 		// Validate the block against the UTXO set.
+		// Every input must be unspent and no output may re-create an existing
+		// unspent output (DuplicateUtxo).
 		validate_utxo(b, &mut extension)?;
 
 		// Using block_sums (utxo_sum, kernel_sum) for the previous block from the db
@@ -334,6 +341,21 @@ fn prev_header_store(
 	Ok(prev)
 }
 
+/// Median timestamp (in seconds) of the provided header and its predecessors,
+/// up to consensus::MEDIAN_TIME_WINDOW headers in total.
+pub fn median_time_past(header: &BlockHeader, batch: &store::Batch<'_>) -> Result<u64, Error> {
+	let mut timestamps = vec![header.timestamp.timestamp()];
+	let mut current = header.clone();
+	while current.height > 0 && (timestamps.len() as u64) < consensus::MEDIAN_TIME_WINDOW {
+		current = batch
+			.get_previous_header(&current)
+			.map_err(|e| ErrorKind::StoreErr(e, "median time past".into()))?;
+		timestamps.push(current.timestamp.timestamp());
+	}
+	timestamps.sort();
+	Ok(timestamps[timestamps.len() / 2] as u64)
+}
+
 /// First level of block validation that only needs to act on the block header
 /// to make it as cheap as possible. The different validations are also
 /// arranged by order of cost to have as little DoS surface as possible.
@@ -347,15 +369,28 @@ fn validate_header(header: &BlockHeader, ctx: &mut BlockContext<'_>) -> Result<(
 		return Err(ErrorKind::InvalidBlockVersion(header.version).into());
 	}
 
-	// TODO: remove CI check from here somehow
-	if header.timestamp > Utc::now() + Duration::seconds(12 * (consensus::BLOCK_TIME_SEC as i64))
-		&& !global::is_automated_testing_mode()
-	{
-		// refuse blocks more than 12 blocks intervals in future (as in bitcoin)
-		// TODO add warning in p2p code if local time is too different from peers
+	// refuse blocks more than 12 blocks intervals in future (as in bitcoin)
+	// TODO add warning in p2p code if local time is too different from peers
+	if consensus::block_time_too_far_in_future(header) {
 		return Err(ErrorKind::InvalidBlockTime.into());
 	}
 
+	// never follow a chain conflicting with a trusted checkpoint
+	if let Some(hash) = ctx.checkpoints.read().get(&header.height) {
+		if header.hash() != *hash {
+			return Err(ErrorKind::CheckpointMismatch {
+				height: header.height,
+			}
+			.into());
+		}
+	}
+
+	// a secondary PoW scaling factor below the minimum (or zero) would make the
+	// header difficulty meaningless
+	if header.pow.is_secondary() && header.pow.secondary_scaling < consensus::MIN_AR_SCALE as u32 {
+		return Err(ErrorKind::InvalidSecondaryScaling.into());
+	}
+
 	if !ctx.opts.contains(Options::SKIP_POW) {
 		if !header.pow.is_primary() && !header.pow.is_secondary() {
 			return Err(ErrorKind::LowEdgebits.into());
@@ -430,10 +465,66 @@ fn validate_header(header: &BlockHeader, ctx: &mut BlockContext<'_>) -> Result<(
 	Ok(())
 }
 
+/// Whether the header is, on the header chain we are syncing (sync MMR), an
+/// ancestor of a trusted checkpoint, with Options::ASSUME_VALID set. A fork
+/// block below a checkpoint is not.
+fn is_assumed_valid(header: &BlockHeader, ctx: &BlockContext<'_>) -> bool {
+	if !ctx.opts.contains(Options::ASSUME_VALID) {
+		return false;
+	}
+	let on_header_chain = |height: u64, hash: &Hash| {
+		ctx.txhashset
+			.get_sync_header_hash_by_height(height)
+			.map_or(false, |h| h == *hash)
+	};
+	on_header_chain(header.height, &header.hash())
+		&& ctx
+			.checkpoints
+			.read()
+			.range(header.height..)
+			.any(|(height, hash)| on_header_chain(*height, hash))
+}
+
 fn validate_block(block: &Block, ctx: &mut BlockContext<'_>) -> Result<(), Error> {
 	let prev = ctx.batch.get_previous_header(&block.header)?;
+
+	// Verify (and time) the rangeproofs and kernel signatures first, the full
+	// validation below finds them in the verifier cache.
+	// The rangeproofs can be skipped (see Options::SKIP_RANGEPROOFS), deferred
+	// to a full chain validation.
+	let check_rangeproofs = !ctx.opts.contains(Options::SKIP_RANGEPROOFS);
+	// The kernel signatures of the ancestors of a checkpoint can be assumed valid
+	// (see Options::ASSUME_VALID).
+	let check_kernels = !is_assumed_valid(&block.header, ctx);
+	let now = Instant::now();
+	let rangeproofs = if check_rangeproofs {
+		block
+			.verify_rangeproofs(ctx.verifier_cache.clone())
+			.map_err(|e| ErrorKind::InvalidBlockProof(e))?
+	} else {
+		0
+	};
+	let rangeproof_time = now.elapsed();
+	let now = Instant::now();
+	let kernels = if check_kernels {
+		block
+			.verify_kernel_signatures(ctx.verifier_cache.clone())
+			.map_err(|e| ErrorKind::InvalidBlockProof(e))?
+	} else {
+		0
+	};
+	let kernel_time = now.elapsed();
+	ctx.verification_stats
+		.write()
+		.add(rangeproof_time, rangeproofs, kernel_time, kernels);
+
 	block
-		.validate(&prev.total_kernel_offset, ctx.verifier_cache.clone())
+		.validate_partial(
+			&prev.total_kernel_offset,
+			ctx.verifier_cache.clone(),
+			check_rangeproofs,
+			check_kernels,
+		)
 		.map_err(|e| ErrorKind::InvalidBlockProof(e))?;
 	Ok(())
 }
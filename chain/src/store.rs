@@ -36,6 +36,8 @@ const SYNC_HEAD_PREFIX: u8 = 's' as u8;
 const COMMIT_POS_PREFIX: u8 = 'c' as u8;
 const BLOCK_INPUT_BITMAP_PREFIX: u8 = 'B' as u8;
 const BLOCK_SUMS_PREFIX: u8 = 'M' as u8;
+const BLOCK_FEES_PREFIX: u8 = 'F' as u8;
+const KERNEL_POS_PREFIX: u8 = 'k' as u8;
 
 /// All chain-related database operations
 pub struct ChainStore {
@@ -97,6 +99,23 @@ impl ChainStore {
 		)
 	}
 
+	/// Get the total fees of the block, indexed when the block was saved.
+	pub fn get_block_fees(&self, h: &Hash) -> Result<u64, Error> {
+		option_to_not_found(
+			self.db.get_ser(&to_key(BLOCK_FEES_PREFIX, &mut h.to_vec())),
+			&format!("Block fees for block: {}", h),
+		)
+	}
+
+	/// Get the kernel MMR position of the kernel with the provided excess.
+	pub fn get_kernel_pos(&self, excess: &Commitment) -> Result<u64, Error> {
+		option_to_not_found(
+			self.db
+				.get_ser(&to_key(KERNEL_POS_PREFIX, &mut excess.as_ref().to_vec())),
+			&format!("Kernel position for excess: {:?}", excess),
+		)
+	}
+
 	/// Get previous header.
 	pub fn get_previous_header(&self, header: &BlockHeader) -> Result<BlockHeader, Error> {
 		self.get_block_header(&header.prev_hash)
@@ -221,6 +240,12 @@ impl<'a> Batch<'a> {
 		self.db
 			.put_ser(&to_key(BLOCK_PREFIX, &mut b.hash().to_vec())[..], b)?;
 
+		// Index the block total fees, saving a full block read to explorers.
+		self.db.put_ser(
+			&to_key(BLOCK_FEES_PREFIX, &mut b.hash().to_vec())[..],
+			&b.total_fees(),
+		)?;
+
 		Ok(())
 	}
 
@@ -234,6 +259,7 @@ impl<'a> Batch<'a> {
 		// Not an error if these fail.
 		{
 			let _ = self.delete_block_sums(bh);
+			let _ = self.delete_block_fees(bh);
 			let _ = self.delete_block_input_bitmap(bh);
 		}
 
@@ -268,6 +294,23 @@ impl<'a> Batch<'a> {
 		)
 	}
 
+	/// Save kernel_pos to index.
+	pub fn save_kernel_pos(&self, excess: &Commitment, pos: u64) -> Result<(), Error> {
+		self.db.put_ser(
+			&to_key(KERNEL_POS_PREFIX, &mut excess.as_ref().to_vec())[..],
+			&pos,
+		)
+	}
+
+	/// Get the kernel MMR position of the kernel with the provided excess.
+	pub fn get_kernel_pos(&self, excess: &Commitment) -> Result<u64, Error> {
+		option_to_not_found(
+			self.db
+				.get_ser(&to_key(KERNEL_POS_PREFIX, &mut excess.as_ref().to_vec())),
+			&format!("Kernel position for excess: {:?}", excess),
+		)
+	}
+
 	/// Clear all entries from the output_pos index (must be rebuilt after).
 	pub fn clear_output_pos(&self) -> Result<(), Error> {
 		let key = to_key(COMMIT_POS_PREFIX, &mut "".to_string().into_bytes());
@@ -277,6 +320,15 @@ impl<'a> Batch<'a> {
 		Ok(())
 	}
 
+	/// Clear all entries from the kernel_pos index (must be rebuilt after).
+	pub fn clear_kernel_pos(&self) -> Result<(), Error> {
+		let key = to_key(KERNEL_POS_PREFIX, &mut "".to_string().into_bytes());
+		for (k, _) in self.db.iter::<u64>(&key)? {
+			self.db.delete(&k)?;
+		}
+		Ok(())
+	}
+
 	/// Get the previous header.
 	pub fn get_previous_header(&self, header: &BlockHeader) -> Result<BlockHeader, Error> {
 		self.get_block_header(&header.prev_hash)
@@ -324,6 +376,19 @@ impl<'a> Batch<'a> {
 		self.db.delete(&to_key(BLOCK_SUMS_PREFIX, &mut bh.to_vec()))
 	}
 
+	/// Get the total fees of the block, indexed when the block was saved.
+	pub fn get_block_fees(&self, h: &Hash) -> Result<u64, Error> {
+		option_to_not_found(
+			self.db.get_ser(&to_key(BLOCK_FEES_PREFIX, &mut h.to_vec())),
+			&format!("Block fees for block: {}", h),
+		)
+	}
+
+	/// Delete the indexed total fees of the block.
+	fn delete_block_fees(&self, bh: &Hash) -> Result<(), Error> {
+		self.db.delete(&to_key(BLOCK_FEES_PREFIX, &mut bh.to_vec()))
+	}
+
 	/// Build the input bitmap for the given block.
 	fn build_block_input_bitmap(&self, block: &Block) -> Result<Bitmap, Error> {
 		let bitmap = block
@@ -384,6 +449,12 @@ impl<'a> Batch<'a> {
 		let key = to_key(BLOCK_PREFIX, &mut "".to_string().into_bytes());
 		self.db.iter(&key)
 	}
+
+	/// An iterator to all block headers in db
+	pub fn headers_iter(&self) -> Result<SerIterator<BlockHeader>, Error> {
+		let key = to_key(BLOCK_HEADER_PREFIX, &mut "".to_string().into_bytes());
+		self.db.iter(&key)
+	}
 }
 
 /// An iterator on blocks, from latest to earliest, specialized to return
@@ -401,6 +472,9 @@ pub struct DifficultyIter<'a> {
 	// toward the genesis block (while maintaining current state)
 	header: Option<BlockHeader>,
 	prev_header: Option<BlockHeader>,
+
+	// number of headers left to yield, unbounded if none
+	remaining: Option<usize>,
 }
 
 impl<'a> DifficultyIter<'a> {
@@ -413,6 +487,7 @@ impl<'a> DifficultyIter<'a> {
 			batch: None,
 			header: None,
 			prev_header: None,
+			remaining: None,
 		}
 	}
 
@@ -425,14 +500,28 @@ impl<'a> DifficultyIter<'a> {
 			batch: Some(batch),
 			header: None,
 			prev_header: None,
+			remaining: None,
 		}
 	}
+
+	/// Stop the iteration after at most max headers, so consumers never walk
+	/// further back than they need to.
+	pub fn bounded(mut self, max: usize) -> DifficultyIter<'a> {
+		self.remaining = Some(max);
+		self
+	}
 }
 
 impl<'a> Iterator for DifficultyIter<'a> {
 	type Item = HeaderInfo;
 
 	fn next(&mut self) -> Option<Self::Item> {
+		match self.remaining {
+			Some(0) => return None,
+			Some(n) => self.remaining = Some(n - 1),
+			None => {}
+		}
+
 		// Get both header and previous_header if this is the initial iteration.
 		// Otherwise move prev_header to header and get the next prev_header.
 		self.header = if self.header.is_none() {
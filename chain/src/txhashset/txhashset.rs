@@ -22,17 +22,17 @@ use crate::core::core::pmmr::{self, Backend, ReadonlyPMMR, RewindablePMMR, PMMR}
 use crate::core::core::{
 	Block, BlockHeader, Input, Output, OutputIdentifier, TxKernel, TxKernelEntry,
 };
-use crate::core::global;
 use crate::core::ser::{PMMRIndexHashable, PMMRable};
 use crate::error::{Error, ErrorKind};
 use crate::store::{Batch, ChainStore};
 use crate::txhashset::{RewindableKernelView, UTXOView};
-use crate::types::{Tip, TxHashSetRoots, TxHashsetWriteStatus};
+use crate::types::{DiskUsage, Tip, TxHashSetRoots, TxHashsetWriteStatus};
 use crate::util::secp::pedersen::{Commitment, RangeProof};
-use crate::util::{file, secp_static, zip};
+use crate::util::{file, secp, secp_static, zip};
 use croaring::Bitmap;
 use grin_store;
 use grin_store::pmmr::{clean_files_by_prefix, PMMRBackend, PMMR_FILES};
+use rayon::prelude::*;
 use std::collections::HashSet;
 use std::fs::{self, File};
 use std::path::{Path, PathBuf};
@@ -51,6 +51,11 @@ const KERNEL_SUBDIR: &'static str = "kernel";
 
 const TXHASHSET_ZIP: &'static str = "txhashset_snapshot";
 
+/// Number of elements read from the MMRs before verifying them in parallel.
+const PARALLEL_VERIFY_BATCH: usize = 16_000;
+/// Number of elements verified by a single task of the thread pool.
+const PARALLEL_VERIFY_CHUNK: usize = 1_000;
+
 struct PMMRHandle<T: PMMRable> {
 	backend: PMMRBackend<T>,
 	last_pos: u64,
@@ -214,6 +219,23 @@ impl TxHashSet {
 			.get_last_n_insertions(distance)
 	}
 
+	/// Get the kernel with the provided excess, along with its kernel MMR
+	/// position. We look in the index to find the kernel MMR pos, then check
+	/// the entry in the kernel MMR has the same excess.
+	pub fn get_kernel(&self, excess: &Commitment) -> Result<Option<(TxKernel, u64)>, Error> {
+		let pos = match self.commit_index.get_kernel_pos(excess) {
+			Ok(pos) => pos,
+			Err(grin_store::Error::NotFoundErr(_)) => return Ok(None),
+			Err(e) => return Err(ErrorKind::StoreErr(e, format!("txhashset kernel pos")).into()),
+		};
+		let kernel_pmmr: ReadonlyPMMR<'_, TxKernel, _> =
+			ReadonlyPMMR::at(&self.kernel_pmmr_h.backend, self.kernel_pmmr_h.last_pos);
+		match kernel_pmmr.get_data(pos) {
+			Some(entry) if entry.kernel.excess == *excess => Ok(Some((entry.kernel, pos))),
+			_ => Ok(None),
+		}
+	}
+
 	/// Get the header hash at the specified height based on the current state of the txhashset.
 	pub fn get_header_hash_by_height(&self, height: u64) -> Result<Hash, Error> {
 		let pos = pmmr::insertion_to_pmmr_index(height + 1);
@@ -226,6 +248,31 @@ impl TxHashSet {
 		}
 	}
 
+	/// Get the header hash at the specified height on the sync MMR, which follows
+	/// the header chain (sync_head) rather than the body chain.
+	pub fn get_sync_header_hash_by_height(&self, height: u64) -> Result<Hash, Error> {
+		let pos = pmmr::insertion_to_pmmr_index(height + 1);
+		let sync_pmmr = ReadonlyPMMR::at(&self.sync_pmmr_h.backend, self.sync_pmmr_h.last_pos);
+		if let Some(entry) = sync_pmmr.get_data(pos) {
+			Ok(entry.hash())
+		} else {
+			Err(ErrorKind::Other(format!("get sync header hash by height")).into())
+		}
+	}
+
+	/// Get the header timestamp at the specified height based on the current state of the
+	/// txhashset. Read from the header MMR entry, without a db lookup.
+	pub fn get_header_timestamp_by_height(&self, height: u64) -> Result<u64, Error> {
+		let pos = pmmr::insertion_to_pmmr_index(height + 1);
+		let header_pmmr =
+			ReadonlyPMMR::at(&self.header_pmmr_h.backend, self.header_pmmr_h.last_pos);
+		if let Some(entry) = header_pmmr.get_data(pos) {
+			Ok(entry.timestamp())
+		} else {
+			Err(ErrorKind::Other(format!("get header timestamp by height")).into())
+		}
+	}
+
 	/// Get the header at the specified height based on the current state of the txhashset.
 	/// Derives the MMR pos from the height (insertion index) and retrieves the header hash.
 	/// Looks the header up in the db by hash.
@@ -293,15 +340,33 @@ impl TxHashSet {
 			.map_err(|_| ErrorKind::MerkleProof.into())
 	}
 
-	/// Compact the MMR data files and flush the rm logs
-	pub fn compact(&mut self, batch: &mut Batch<'_>) -> Result<(), Error> {
+	/// build a new merkle proof for the kernel with the given excess, against
+	/// the current kernel MMR root, along with the kernel MMR position.
+	/// Errors if the kernel index doesn't point to a kernel with that excess.
+	pub fn kernel_merkle_proof(
+		&mut self,
+		excess: &Commitment,
+	) -> Result<(MerkleProof, u64), Error> {
+		let pos = match self.get_kernel(excess)? {
+			Some((_, pos)) => pos,
+			None => return Err(ErrorKind::MerkleProof.into()),
+		};
+		let proof = PMMR::at(&mut self.kernel_pmmr_h.backend, self.kernel_pmmr_h.last_pos)
+			.merkle_proof(pos)
+			.map_err(|_| ErrorKind::MerkleProof)?;
+		Ok((proof, pos))
+	}
+
+	/// Compact the MMR data files and flush the rm logs, pruning everything
+	/// removed before the horizon (in blocks from the current head).
+	pub fn compact(&mut self, horizon: u64, batch: &mut Batch<'_>) -> Result<(), Error> {
 		debug!("txhashset: starting compaction...");
 
 		let head_header = batch.head_header()?;
 		let current_height = head_header.height;
 
 		// horizon for compacting is based on current_height
-		let horizon_height = current_height.saturating_sub(global::cut_through_horizon().into());
+		let horizon_height = current_height.saturating_sub(horizon);
 		let horizon_hash = self.get_header_hash_by_height(horizon_height)?;
 		let horizon_header = batch.get_block_header(&horizon_hash)?;
 
@@ -960,9 +1025,11 @@ impl<'a> Extension<'a> {
 
 	/// Push kernel onto MMR (hash and data files).
 	fn apply_kernel(&mut self, kernel: &TxKernel) -> Result<(), Error> {
-		self.kernel_pmmr
+		let pos = self
+			.kernel_pmmr
 			.push(kernel)
 			.map_err(&ErrorKind::TxHashSetErr)?;
+		self.batch.save_kernel_pos(&kernel.excess, pos)?;
 		Ok(())
 	}
 
@@ -1019,6 +1086,15 @@ impl<'a> Extension<'a> {
 		Ok(merkle_proof)
 	}
 
+	/// All the unspent outputs in the output MMR, as of the block this
+	/// extension is currently referencing.
+	pub fn unspent_outputs(&self) -> Vec<OutputIdentifier> {
+		self.output_pmmr
+			.leaf_pos_iter()
+			.filter_map(|pos| self.output_pmmr.get_data(pos))
+			.collect()
+	}
+
 	/// Saves a snapshot of the output and rangeproof MMRs to disk.
 	/// Specifically - saves a snapshot of the utxo file, tagged with
 	/// the block hash as filename suffix.
@@ -1231,6 +1307,55 @@ impl<'a> Extension<'a> {
 		&self,
 		fast_validation: bool,
 		status: &dyn TxHashsetWriteStatus,
+	) -> Result<((Commitment, Commitment)), Error> {
+		self.validate_with(fast_validation, false, status)
+	}
+
+	/// Same as validate but verifies the rangeproofs and kernel signatures
+	/// across the rayon thread pool. The MMR and root checks remain sequential.
+	pub fn validate_parallel(
+		&self,
+		fast_validation: bool,
+		status: &dyn TxHashsetWriteStatus,
+	) -> Result<((Commitment, Commitment)), Error> {
+		self.validate_with(fast_validation, true, status)
+	}
+
+	/// Same as validate but goes through all the validation steps, collecting
+	/// every failure instead of stopping at the first one. Meant to diagnose a
+	/// corrupted chain state. No errors means the state is valid.
+	pub fn validate_collect_errors(
+		&self,
+		fast_validation: bool,
+		status: &dyn TxHashsetWriteStatus,
+	) -> Vec<Error> {
+		let mut errors = vec![];
+		let mut check = |res: Result<(), Error>| {
+			if let Err(e) = res {
+				errors.push(e);
+			}
+		};
+
+		check(self.validate_mmrs());
+		check(self.validate_roots());
+		check(self.validate_sizes());
+
+		if self.header.height > 0 {
+			check(self.validate_kernel_sums().map(|_| ()));
+			if !fast_validation {
+				check(self.verify_rangeproofs(status));
+				check(self.verify_kernel_signatures(status));
+			}
+		}
+
+		errors
+	}
+
+	fn validate_with(
+		&self,
+		fast_validation: bool,
+		parallel: bool,
+		status: &dyn TxHashsetWriteStatus,
 	) -> Result<((Commitment, Commitment)), Error> {
 		self.validate_mmrs()?;
 		self.validate_roots()?;
@@ -1247,11 +1372,16 @@ impl<'a> Extension<'a> {
 
 		// These are expensive verification step (skipped for "fast validation").
 		if !fast_validation {
-			// Verify the rangeproof associated with each unspent output.
-			self.verify_rangeproofs(status)?;
+			if parallel {
+				self.verify_rangeproofs_parallel(status)?;
+				self.verify_kernel_signatures_parallel(status)?;
+			} else {
+				// Verify the rangeproof associated with each unspent output.
+				self.verify_rangeproofs(status)?;
 
-			// Verify all the kernel signatures.
-			self.verify_kernel_signatures(status)?;
+				// Verify all the kernel signatures.
+				self.verify_kernel_signatures(status)?;
+			}
 		}
 
 		Ok((output_sum, kernel_sum))
@@ -1281,6 +1411,33 @@ impl<'a> Extension<'a> {
 		Ok(())
 	}
 
+	/// Rebuild the index of kernel excess commitments to their MMR positions.
+	/// Kernels are never pruned so this walks the whole kernel MMR, needed when
+	/// the kernels were not applied block by block (fast sync) or on reindex.
+	pub fn rebuild_kernel_index(&self) -> Result<(), Error> {
+		let now = Instant::now();
+
+		self.batch.clear_kernel_pos()?;
+
+		let mut count = 0;
+		for pos in 1..self.kernel_pmmr.unpruned_size() + 1 {
+			if pmmr::is_leaf(pos) {
+				if let Some(entry) = self.kernel_pmmr.get_data(pos) {
+					self.batch.save_kernel_pos(&entry.kernel.excess, pos)?;
+					count += 1;
+				}
+			}
+		}
+
+		debug!(
+			"txhashset: rebuild_kernel_index: {} kernels, took {}s",
+			count,
+			now.elapsed().as_secs(),
+		);
+
+		Ok(())
+	}
+
 	/// Force the rollback of this extension, no matter the result
 	pub fn force_rollback(&mut self) {
 		self.rollback = true;
@@ -1415,6 +1572,143 @@ impl<'a> Extension<'a> {
 		);
 		Ok(())
 	}
+
+	// Kernels are read sequentially from the MMR in batches, each batch is then
+	// split in chunks verified on the rayon thread pool.
+	fn verify_kernel_signatures_parallel(
+		&self,
+		status: &dyn TxHashsetWriteStatus,
+	) -> Result<(), Error> {
+		let now = Instant::now();
+
+		let mut kern_count = 0;
+		let total_kernels = pmmr::n_leaves(self.kernel_pmmr.unpruned_size());
+		let mut kernels: Vec<TxKernel> = Vec::with_capacity(PARALLEL_VERIFY_BATCH);
+		for n in 1..self.kernel_pmmr.unpruned_size() + 1 {
+			if pmmr::is_leaf(n) {
+				let kernel = self
+					.kernel_pmmr
+					.get_data(n)
+					.ok_or::<Error>(ErrorKind::TxKernelNotFound.into())?;
+				kernels.push(kernel.kernel);
+
+				if kernels.len() >= PARALLEL_VERIFY_BATCH {
+					verify_kernels_parallel(&kernels)?;
+					kern_count += kernels.len() as u64;
+					kernels.clear();
+					status.on_validation(kern_count, total_kernels, 0, 0);
+				}
+			}
+		}
+		if !kernels.is_empty() {
+			verify_kernels_parallel(&kernels)?;
+			kern_count += kernels.len() as u64;
+			status.on_validation(kern_count, total_kernels, 0, 0);
+		}
+
+		debug!(
+			"txhashset: verified {} kernel signatures in parallel, pmmr size {}, took {}s",
+			kern_count,
+			self.kernel_pmmr.unpruned_size(),
+			now.elapsed().as_secs(),
+		);
+
+		Ok(())
+	}
+
+	// Outputs and rangeproofs are read sequentially from the MMRs in batches,
+	// each batch is then split in chunks batch verified on the rayon thread pool.
+	fn verify_rangeproofs_parallel(&self, status: &dyn TxHashsetWriteStatus) -> Result<(), Error> {
+		let now = Instant::now();
+
+		let mut commits: Vec<Commitment> = Vec::with_capacity(PARALLEL_VERIFY_BATCH);
+		let mut proofs: Vec<RangeProof> = Vec::with_capacity(PARALLEL_VERIFY_BATCH);
+
+		let mut proof_count = 0;
+		let total_rproofs = pmmr::n_leaves(self.output_pmmr.unpruned_size());
+		for pos in self.output_pmmr.leaf_pos_iter() {
+			let output = self.output_pmmr.get_data(pos);
+			let proof = self.rproof_pmmr.get_data(pos);
+
+			// Output and corresponding rangeproof *must* exist.
+			// It is invalid for either to be missing and we fail immediately in this case.
+			match (output, proof) {
+				(None, _) => return Err(ErrorKind::OutputNotFound.into()),
+				(_, None) => return Err(ErrorKind::RangeproofNotFound.into()),
+				(Some(output), Some(proof)) => {
+					commits.push(output.commit);
+					proofs.push(proof);
+				}
+			}
+
+			if proofs.len() >= PARALLEL_VERIFY_BATCH {
+				verify_rangeproofs_parallel(&commits, &proofs)?;
+				proof_count += proofs.len() as u64;
+				commits.clear();
+				proofs.clear();
+				status.on_validation(0, 0, proof_count, total_rproofs);
+			}
+		}
+		if !proofs.is_empty() {
+			verify_rangeproofs_parallel(&commits, &proofs)?;
+			proof_count += proofs.len() as u64;
+			status.on_validation(0, 0, proof_count, total_rproofs);
+		}
+
+		debug!(
+			"txhashset: verified {} rangeproofs in parallel, pmmr size {}, took {}s",
+			proof_count,
+			self.rproof_pmmr.unpruned_size(),
+			now.elapsed().as_secs(),
+		);
+		Ok(())
+	}
+}
+
+thread_local! {
+	// Each rayon worker verifies against its own secp context, the static
+	// instance is behind a mutex and would serialize all workers.
+	static VERIFIER_SECP: secp::Secp256k1 =
+		secp::Secp256k1::with_caps(secp::ContextFlag::Commit);
+}
+
+fn verify_kernels_parallel(kernels: &[TxKernel]) -> Result<(), Error> {
+	kernels
+		.par_chunks(PARALLEL_VERIFY_CHUNK)
+		.try_for_each(|chunk| -> Result<(), Error> {
+			VERIFIER_SECP.with(|secp| {
+				for kernel in chunk {
+					kernel.verify_with(secp)?;
+				}
+				Ok(())
+			})
+		})
+}
+
+fn verify_rangeproofs_parallel(commits: &[Commitment], proofs: &[RangeProof]) -> Result<(), Error> {
+	commits
+		.par_chunks(PARALLEL_VERIFY_CHUNK)
+		.zip(proofs.par_chunks(PARALLEL_VERIFY_CHUNK))
+		.try_for_each(|(commits, proofs)| -> Result<(), Error> {
+			VERIFIER_SECP.with(|secp| {
+				Output::batch_verify_proofs_with(secp, &commits.to_vec(), &proofs.to_vec())
+			})?;
+			Ok(())
+		})
+}
+
+/// Size on disk of the MMR files under root_dir, along with the total size
+/// of root_dir itself (including the db).
+pub fn disk_usage(root_dir: &str) -> Result<DiskUsage, Error> {
+	let root_path = Path::new(root_dir);
+	let txhashset_path = root_path.join(TXHASHSET_SUBDIR);
+	Ok(DiskUsage {
+		headers_bytes: file::dir_size(&root_path.join(HEADERHASHSET_SUBDIR))?,
+		outputs_bytes: file::dir_size(&txhashset_path.join(OUTPUT_SUBDIR))?,
+		rangeproofs_bytes: file::dir_size(&txhashset_path.join(RANGE_PROOF_SUBDIR))?,
+		kernels_bytes: file::dir_size(&txhashset_path.join(KERNEL_SUBDIR))?,
+		total: file::dir_size(root_path)?,
+	})
 }
 
 /// Packages the txhashset data files into a zip and returns a Read to the
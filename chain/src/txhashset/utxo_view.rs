@@ -72,10 +72,10 @@ impl<'a> UTXOView<'a> {
 		Ok(())
 	}
 
-	// Input is valid if it is spending an (unspent) output
-	// that currently exists in the output MMR.
-	// Compare the hash in the output MMR at the expected pos.
-	fn validate_input(&self, input: &Input) -> Result<(), Error> {
+	/// Input is valid if it is spending an (unspent) output
+	/// that currently exists in the output MMR.
+	/// Compare the hash in the output MMR at the expected pos.
+	pub fn validate_input(&self, input: &Input) -> Result<(), Error> {
 		if let Ok(pos) = self.batch.get_output_pos(&input.commitment()) {
 			if let Some(hash) = self.output_pmmr.get_hash(pos) {
 				if hash == input.hash_with_index(pos - 1) {
@@ -86,12 +86,16 @@ impl<'a> UTXOView<'a> {
 		Err(ErrorKind::AlreadySpent(input.commitment()).into())
 	}
 
-	// Output is valid if it would not result in a duplicate commitment in the output MMR.
-	fn validate_output(&self, output: &Output) -> Result<(), Error> {
+	/// Output is valid if it would not result in a duplicate commitment in the output MMR.
+	/// i.e. it must not re-create an output that is currently unspent.
+	pub fn validate_output(&self, output: &Output) -> Result<(), Error> {
 		if let Ok(pos) = self.batch.get_output_pos(&output.commitment()) {
 			if let Some(out_mmr) = self.output_pmmr.get_data(pos) {
 				if out_mmr.commitment() == output.commitment() {
-					return Err(ErrorKind::DuplicateCommitment(output.commitment()).into());
+					return Err(ErrorKind::DuplicateUtxo {
+						commit: output.commitment(),
+					}
+					.into());
 				}
 			}
 		}
@@ -111,15 +115,17 @@ impl<'a> UTXOView<'a> {
 			.unwrap_or(0);
 
 		if pos > 0 {
+			let maturity = global::coinbase_maturity_at(height);
+
 			// If we have not yet reached 1,000 / 1,440 blocks then
 			// we can fail immediately as coinbase cannot be mature.
-			if height < global::coinbase_maturity() {
+			if height < maturity {
 				return Err(ErrorKind::ImmatureCoinbase.into());
 			}
 
 			// Find the "cutoff" pos in the output MMR based on the
 			// header from 1,000 blocks ago.
-			let cutoff_height = height.checked_sub(global::coinbase_maturity()).unwrap_or(0);
+			let cutoff_height = height.checked_sub(maturity).unwrap_or(0);
 			let cutoff_header = self.get_header_by_height(cutoff_height)?;
 			let cutoff_pos = cutoff_header.output_mmr_size;
 
@@ -133,6 +139,40 @@ impl<'a> UTXOView<'a> {
 		Ok(())
 	}
 
+	/// Height of the first block that can include the provided inputs as far
+	/// as coinbase maturity is concerned, 0 if they spend no coinbase output.
+	/// The coinbase outputs are looked for in the blocks up to head_height.
+	pub fn coinbase_mature_height(
+		&self,
+		inputs: &Vec<Input>,
+		head_height: u64,
+	) -> Result<u64, Error> {
+		let pos = inputs
+			.iter()
+			.filter(|x| x.is_coinbase())
+			.filter_map(|x| self.batch.get_output_pos(&x.commitment()).ok())
+			.max()
+			.unwrap_or(0);
+
+		if pos == 0 {
+			return Ok(0);
+		}
+
+		// Find the block that created the output at pos, the first one whose
+		// output MMR includes it.
+		let (mut low, mut high) = (0, head_height);
+		while low < high {
+			let mid = low + (high - low) / 2;
+			if self.get_header_by_height(mid)?.output_mmr_size < pos {
+				low = mid + 1;
+			} else {
+				high = mid;
+			}
+		}
+
+		Ok(low + global::coinbase_maturity_at(low))
+	}
+
 	/// Get the header hash for the specified pos from the underlying MMR backend.
 	fn get_header_hash(&self, pos: u64) -> Option<Hash> {
 		self.header_pmmr.get_data(pos).map(|x| x.hash())
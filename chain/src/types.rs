@@ -18,6 +18,8 @@ use crate::core::core::hash::{Hash, Hashed, ZERO_HASH};
 use crate::core::core::{Block, BlockHeader};
 use crate::core::pow::Difficulty;
 use crate::core::ser;
+use std::collections::VecDeque;
+use std::time::Duration;
 
 bitflags! {
 /// Options for block validation
@@ -30,6 +32,17 @@ bitflags! {
 		const SYNC = 0b00000010;
 		/// Block validation on a block we mined ourselves
 		const MINE = 0b00000100;
+		/// Skips the per block rangeproof verification, during initial block
+		/// download typically. The rangeproofs are then only checked by a full
+		/// (non fast) chain validation, which must be run before trusting the
+		/// resulting UTXO set: until then an output with an invalid rangeproof,
+		/// hiding a negative value and inflating the supply, could be accepted
+		/// as long as the PoW and the kernel sums are valid.
+		const SKIP_RANGEPROOFS = 0b00010000;
+		/// Skips the kernel signatures verification of the blocks at or below
+		/// the highest trusted checkpoint (assumevalid), the checkpoint hash
+		/// committing to them. Only as trustworthy as the checkpoints are.
+		const ASSUME_VALID = 0b00100000;
 	}
 }
 
@@ -125,6 +138,10 @@ pub trait ChainAdapter {
 	/// The blockchain pipeline has accepted this block as valid and added
 	/// it to our chain.
 	fn block_accepted(&self, block: &Block, status: BlockStatus, opts: Options);
+
+	/// The blockchain pipeline could not find the parent of this block and
+	/// has parked it in the orphan pool until the parent arrives.
+	fn block_orphaned(&self, _block: &Block) {}
 }
 
 /// Inform the caller of the current status of a txhashset write operation,
@@ -160,6 +177,84 @@ impl ChainAdapter for NoopAdapter {
 	fn block_accepted(&self, _b: &Block, _status: BlockStatus, _opts: Options) {}
 }
 
+/// On-disk size in bytes of the chain data.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct DiskUsage {
+	/// Header MMR files
+	pub headers_bytes: u64,
+	/// Output MMR files
+	pub outputs_bytes: u64,
+	/// Rangeproof MMR files
+	pub rangeproofs_bytes: u64,
+	/// Kernel MMR files
+	pub kernels_bytes: u64,
+	/// Everything under the chain data dir, MMRs and db included
+	pub total: u64,
+}
+
+/// Average time spent verifying a rangeproof and a kernel signature, in
+/// microseconds, over the recently processed blocks.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct VerificationTiming {
+	/// Average time per rangeproof verified
+	pub avg_rangeproof_us: u64,
+	/// Average time per kernel signature verified
+	pub avg_kernel_us: u64,
+}
+
+/// Number of recently processed blocks the verification timing is
+/// averaged over.
+pub const VERIFICATION_TIMING_WINDOW: usize = 100;
+
+/// Time spent verifying the rangeproofs and kernel signatures of the
+/// recently processed blocks.
+#[derive(Debug, Default)]
+pub struct VerificationStats {
+	// (rangeproofs time, rangeproofs count, kernels time, kernels count)
+	samples: VecDeque<(Duration, usize, Duration, usize)>,
+}
+
+impl VerificationStats {
+	/// Record the verification of a block, dropping the oldest block if over
+	/// the window.
+	pub fn add(
+		&mut self,
+		rangeproof_time: Duration,
+		rangeproofs: usize,
+		kernel_time: Duration,
+		kernels: usize,
+	) {
+		self.samples
+			.push_back((rangeproof_time, rangeproofs, kernel_time, kernels));
+		if self.samples.len() > VERIFICATION_TIMING_WINDOW {
+			let _ = self.samples.pop_front();
+		}
+	}
+
+	/// Average verification times over the recorded blocks.
+	pub fn timing(&self) -> VerificationTiming {
+		let avg_us = |time: Duration, count: usize| {
+			if count == 0 {
+				0
+			} else {
+				(time.as_secs() * 1_000_000 + time.subsec_micros() as u64) / count as u64
+			}
+		};
+		let (mut rp_time, mut rp_count) = (Duration::from_secs(0), 0);
+		let (mut kern_time, mut kern_count) = (Duration::from_secs(0), 0);
+		for x in &self.samples {
+			rp_time += x.0;
+			rp_count += x.1;
+			kern_time += x.2;
+			kern_count += x.3;
+		}
+		VerificationTiming {
+			avg_rangeproof_us: avg_us(rp_time, rp_count),
+			avg_kernel_us: avg_us(kern_time, kern_count),
+		}
+	}
+}
+
 /// Status of an accepted block.
 #[derive(Debug, Clone, PartialEq)]
 pub enum BlockStatus {
@@ -169,5 +264,26 @@ pub enum BlockStatus {
 	Fork,
 	/// Block updates the chain head via a (potentially disruptive) "reorg".
 	/// Previous block was not our previous chain head.
-	Reorg(u64),
+	Reorg {
+		/// Number of blocks disconnected from the previous chain head
+		depth: u64,
+		/// Hash of the chain head before the reorg
+		prev_head: Hash,
+		/// Hash of the chain head after the reorg
+		new_head: Hash,
+	},
+}
+
+/// Event broadcast to the chain subscribers, see `Chain::subscribe`.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ChainEvent {
+	/// A block has been processed and accepted.
+	Accepted {
+		/// Hash of the accepted block
+		hash: Hash,
+		/// Height of the accepted block
+		height: u64,
+		/// How the block relates to the chain head
+		status: BlockStatus,
+	},
 }
@@ -14,9 +14,9 @@
 
 use self::chain::types::NoopAdapter;
 use self::chain::Chain;
-use self::core::core::hash::Hashed;
+use self::core::core::hash::{Hash, Hashed};
 use self::core::core::verifier_cache::LruVerifierCache;
-use self::core::core::{Block, BlockHeader, OutputIdentifier, Transaction};
+use self::core::core::{Block, BlockHeader, OutputFeatures, OutputIdentifier, Transaction};
 use self::core::genesis;
 use self::core::global::ChainTypes;
 use self::core::libtx::{self, build, reward, ProofBuilder};
@@ -24,14 +24,16 @@ use self::core::pow::Difficulty;
 use self::core::{consensus, global, pow};
 use self::keychain::{ExtKeychain, ExtKeychainPath, Keychain};
 use self::util::RwLock;
-use chrono::Duration;
+use chrono::{Duration, TimeZone, Utc};
 use grin_chain as chain;
-use grin_chain::{BlockStatus, ChainAdapter, Options};
+use grin_chain::{BlockStatus, ChainAdapter, ChainEvent, Options, Tip};
 use grin_core as core;
 use grin_keychain as keychain;
 use grin_util as util;
+use std::collections::HashSet;
 use std::fs;
-use std::sync::Arc;
+use std::sync::{Arc, Barrier};
+use std::thread;
 
 fn clean_output_dir(dir_name: &str) {
 	let _ = fs::remove_dir_all(dir_name);
@@ -52,14 +54,18 @@ fn setup(dir_name: &str, genesis: Block) -> Chain {
 	.unwrap()
 }
 
-/// Adapter to retrieve last status
+/// Adapter to retrieve last status and last orphaned block
 pub struct StatusAdapter {
 	pub last_status: RwLock<Option<BlockStatus>>,
+	pub last_orphan: RwLock<Option<Hash>>,
 }
 
 impl StatusAdapter {
 	pub fn new(last_status: RwLock<Option<BlockStatus>>) -> Self {
-		StatusAdapter { last_status }
+		StatusAdapter {
+			last_status,
+			last_orphan: RwLock::new(None),
+		}
 	}
 }
 
@@ -67,6 +73,10 @@ impl ChainAdapter for StatusAdapter {
 	fn block_accepted(&self, _b: &Block, status: BlockStatus, _opts: Options) {
 		*self.last_status.write() = Some(status);
 	}
+
+	fn block_orphaned(&self, b: &Block) {
+		*self.last_orphan.write() = Some(b.hash());
+	}
 }
 
 /// Creates a `Chain` instance with `StatusAdapter` attached to it.
@@ -114,7 +124,7 @@ fn mine_genesis_reward_chain() {
 		0,
 		false,
 		0,
-	0)
+	)
 	.unwrap();
 	genesis = genesis.with_reward(reward.0, reward.1);
 
@@ -142,6 +152,45 @@ fn mine_genesis_reward_chain() {
 	clean_output_dir(".mwc.genesis");
 }
 
+#[test]
+fn genesis_reward_mmr_sizes() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+
+	let mut genesis = genesis::genesis_dev();
+	let keychain = keychain::ExtKeychain::from_random_seed(false).unwrap();
+	let key_id = keychain::ExtKeychain::derive_key_id(0, 1, 0, 0, 0);
+	let reward = reward::output(
+		&keychain,
+		&libtx::ProofBuilder::new(&keychain),
+		&key_id,
+		0,
+		false,
+		0,
+	)
+	.unwrap();
+	genesis = genesis.with_reward(reward.0, reward.1);
+	genesis.header.output_mmr_size = 0;
+	genesis.header.kernel_mmr_size = 0;
+
+	let chain_dir = ".mwc_genesis_mmr_sizes";
+	clean_output_dir(chain_dir);
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+	let res = chain::Chain::init(
+		chain_dir.to_string(),
+		Arc::new(NoopAdapter {}),
+		genesis,
+		pow::verify_size,
+		verifier_cache,
+		false,
+	);
+	match res {
+		Err(e) => assert_eq!(e.kind(), chain::ErrorKind::InvalidGenesisMmrSize),
+		Ok(_) => panic!("rewarded genesis with empty MMRs should be rejected"),
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
 fn mine_some_on_top<K>(dir: &str, genesis: Block, keychain: &K)
 where
 	K: Keychain,
@@ -264,7 +313,11 @@ fn mine_reorg() {
 		// Check that reorg is correctly reported in block status
 		assert_eq!(
 			*adapter.last_status.read(),
-			Some(BlockStatus::Reorg(REORG_DEPTH))
+			Some(BlockStatus::Reorg {
+				depth: REORG_DEPTH,
+				prev_head: head.hash(),
+				new_head: reorg_head.hash(),
+			})
 		);
 
 		// Chain should be switched to the reorganized chain
@@ -540,72 +593,1965 @@ fn spend_in_fork_and_compact() {
 	clean_output_dir(".mwc6");
 }
 
-/// Test ability to retrieve block headers for a given output
 #[test]
-fn output_header_mappings() {
+fn validate_parallel() {
 	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_validate_parallel";
+	let genesis = pow::mine_genesis_block().unwrap();
 	{
-		let chain = setup(".mwc_header_for_output", pow::mine_genesis_block().unwrap());
-		let keychain = ExtKeychain::from_random_seed(false).unwrap();
-		let mut reward_outputs = vec![];
+		let chain = setup(chain_dir, genesis.clone());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..8 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
 
-		for n in 1..15 {
-			let prev = chain.head_header().unwrap();
-			let next_header_info = consensus::next_difficulty(1, chain.difficulty_iter().unwrap());
-			let pk = ExtKeychainPath::new(1, n as u32, 0, 0, 0).to_identifier();
-			let reward = libtx::reward::output(
-				&keychain,
-				&libtx::ProofBuilder::new(&keychain),
-				&pk,
-				0,
-				false,
-				prev.height + 1,
-			)
-			.unwrap();
-			reward_outputs.push(reward.0.clone());
-			let mut b =
-				core::core::Block::new(&prev, vec![], next_header_info.clone().difficulty, reward)
-					.unwrap();
-			b.header.timestamp = prev.timestamp + Duration::seconds(60);
-			b.header.pow.secondary_scaling = next_header_info.secondary_scaling;
+		chain.validate(false).unwrap();
+		chain.validate_parallel(false).unwrap();
+		chain.validate_parallel(true).unwrap();
+	}
 
-			chain.set_txhashset_roots(&mut b).unwrap();
+	// tamper with the last rangeproof in the data file, the MMR hashes are
+	// left untouched so only the rangeproof verification can catch it
+	let data_file = std::path::Path::new(chain_dir)
+		.join("txhashset")
+		.join("rangeproof")
+		.join("pmmr_data.bin");
+	let mut data = fs::read(&data_file).unwrap();
+	let len = data.len();
+	data[len - 10] ^= 0xff;
+	fs::write(&data_file, data).unwrap();
 
-			let edge_bits = if n == 2 {
-				global::min_edge_bits() + 1
-			} else {
-				global::min_edge_bits()
-			};
-			b.header.pow.proof.edge_bits = edge_bits;
-			pow::pow_size(
-				&mut b.header,
-				next_header_info.difficulty,
-				global::proofsize(),
-				edge_bits,
-			)
-			.unwrap();
-			b.header.pow.proof.edge_bits = edge_bits;
+	{
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+		let chain = chain::Chain::init(
+			chain_dir.to_string(),
+			Arc::new(NoopAdapter {}),
+			genesis,
+			pow::verify_size,
+			verifier_cache,
+			false,
+		)
+		.unwrap();
 
-			chain.process_block(b, chain::Options::MINE).unwrap();
+		assert!(chain.validate(false).is_err());
+		assert!(chain.validate_parallel(false).is_err());
 
-			let header_for_output = chain
-				.get_header_for_output(&OutputIdentifier::from_output(&reward_outputs[n - 1]))
+		// fast validation skips the rangeproofs on both paths
+		chain.validate(true).unwrap();
+		chain.validate_parallel(true).unwrap();
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn validate_collect_errors() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_validate_collect_errors";
+	let genesis = pow::mine_genesis_block().unwrap();
+	{
+		let chain = setup(chain_dir, genesis.clone());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..8 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		assert!(chain.validate_collect_errors(false).is_empty());
+	}
+
+	// tamper with the last rangeproof and the last kernel signature, the MMR
+	// hashes are left untouched so only the verification steps can catch it
+	for mmr in &["rangeproof", "kernel"] {
+		let data_file = std::path::Path::new(chain_dir)
+			.join("txhashset")
+			.join(mmr)
+			.join("pmmr_data.bin");
+		let mut data = fs::read(&data_file).unwrap();
+		let len = data.len();
+		data[len - 10] ^= 0xff;
+		fs::write(&data_file, data).unwrap();
+	}
+
+	{
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+		let chain = chain::Chain::init(
+			chain_dir.to_string(),
+			Arc::new(NoopAdapter {}),
+			genesis,
+			pow::verify_size,
+			verifier_cache,
+			false,
+		)
+		.unwrap();
+
+		// validate stops at the first failure, both are reported here
+		assert!(chain.validate(false).is_err());
+		assert_eq!(chain.validate_collect_errors(false).len(), 2);
+
+		// fast validation skips the rangeproofs and kernel signatures
+		assert!(chain.validate_collect_errors(true).is_empty());
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn reindex() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_reindex";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let mut prev = chain.head_header().unwrap();
+		let mut blocks = vec![];
+		for n in 2..8 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain
+				.process_block(b.clone(), chain::Options::SKIP_POW)
 				.unwrap();
-			assert_eq!(header_for_output.height, n as u64);
+			blocks.push(b);
+		}
 
-			chain.validate(false).unwrap();
+		// lose the output positions and kernel indexes
+		{
+			let store = chain.store();
+			let batch = store.batch().unwrap();
+			batch.clear_output_pos().unwrap();
+			batch.clear_kernel_pos().unwrap();
+			batch.commit().unwrap();
+		}
+		let out_id = OutputIdentifier::from_output(&blocks[2].outputs()[0]);
+		assert!(chain.get_header_for_output(&out_id).is_err());
+		let excess = blocks[2].kernels()[0].excess;
+		assert_eq!(chain.get_kernel_by_excess(&excess).unwrap(), None);
+
+		chain.reindex().unwrap();
+
+		for b in &blocks {
+			let out_id = OutputIdentifier::from_output(&b.outputs()[0]);
+			assert_eq!(chain.get_header_for_output(&out_id).unwrap(), b.header);
+			let (_, height) = chain
+				.get_kernel_by_excess(&b.kernels()[0].excess)
+				.unwrap()
+				.unwrap();
+			assert_eq!(height, b.header.height);
+			assert_eq!(
+				chain.get_header_by_height(b.header.height).unwrap(),
+				b.header
+			);
+		}
+		assert_eq!(chain.head_header().unwrap(), prev);
+		chain.validate(false).unwrap();
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn process_block_concurrent_duplicates() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_process_block_concurrent";
+	{
+		let chain = Arc::new(setup(chain_dir, pow::mine_genesis_block().unwrap()));
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let accepted = chain.subscribe();
+
+		let prev = chain.head_header().unwrap();
+		let b = prepare_block(&kc, &prev, &chain, 2);
+		let barrier = Arc::new(Barrier::new(2));
+
+		let handles: Vec<_> = (0..2)
+			.map(|_| {
+				let chain = chain.clone();
+				let barrier = barrier.clone();
+				let b = b.clone();
+				thread::spawn(move || {
+					barrier.wait();
+					chain.process_block(b, chain::Options::SKIP_POW)
+				})
+			})
+			.collect();
+		let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+		// both submissions succeed but only one of them moved the head
+		assert!(results.iter().all(|r| r.is_ok()));
+		assert_eq!(
+			results
+				.iter()
+				.filter(|r| r.as_ref().unwrap().is_some())
+				.count(),
+			1
+		);
+		assert_eq!(accepted.try_iter().count(), 1);
+		assert_eq!(chain.head().unwrap().last_block_h, b.hash());
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn reorg_subsidy_delta() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_reorg_subsidy_delta";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+
+		// a common block to fork from, the fork needs a real ancestor
+		let genesis = chain.head_header().unwrap();
+		let fork_head = prepare_block(&kc, &genesis, &chain, 2);
+		chain
+			.process_block(fork_head.clone(), chain::Options::SKIP_POW)
+			.unwrap();
+		let fork_head = fork_head.header;
+
+		// 2 blocks on the main chain
+		let mut disconnected = vec![];
+		let mut prev = fork_head.clone();
+		for n in 3..5 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain
+				.process_block(b.clone(), chain::Options::SKIP_POW)
+				.unwrap();
+			disconnected.push(b);
 		}
 
-		// Check all output positions are as expected
-		for n in 1..15 {
-			let header_for_output = chain
-				.get_header_for_output(&OutputIdentifier::from_output(&reward_outputs[n - 1]))
+		// replaced by 3 blocks on a fork
+		let mut connected = vec![];
+		let mut prev = fork_head;
+		for n in 3..6 {
+			let b = prepare_fork_block(&kc, &prev, &chain, n * 2);
+			prev = b.header.clone();
+			chain
+				.process_block(b.clone(), chain::Options::SKIP_POW)
 				.unwrap();
-			assert_eq!(header_for_output.height, n as u64);
+			connected.push(b);
 		}
+		assert_eq!(chain.head().unwrap().last_block_h, prev.hash());
+
+		let subsidy = |height: u64| consensus::calc_mwc_block_reward(height) as i64;
+		assert_eq!(
+			chain.reorg_subsidy_delta(&disconnected, &connected),
+			subsidy(2) + subsidy(3) + subsidy(4) - subsidy(2) - subsidy(3)
+		);
+		assert_eq!(
+			chain.reorg_subsidy_delta(&connected, &disconnected),
+			-subsidy(4)
+		);
+		assert_eq!(chain.reorg_subsidy_delta(&[], &[]), 0);
 	}
 	// Cleanup chain directory
-	clean_output_dir(".mwc_header_for_output");
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn reject_zero_secondary_scaling() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_zero_secondary_scaling";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let prev = chain.head_header().unwrap();
+
+		let mut b = prepare_block(&kc, &prev, &chain, 2);
+		b.header.pow.proof.edge_bits = consensus::SECOND_POW_EDGE_BITS;
+		b.header.pow.secondary_scaling = 0;
+
+		match chain.process_block_header(&b.header, chain::Options::SKIP_POW) {
+			Err(e) => assert_eq!(e.kind(), chain::ErrorKind::InvalidSecondaryScaling),
+			Ok(_) => panic!("header with a zero secondary scaling accepted"),
+		}
+		assert!(chain
+			.process_block(b.clone(), chain::Options::SKIP_POW)
+			.is_err());
+		assert_eq!(chain.head().unwrap().height, 0);
+
+		// the minimum scaling is fine
+		b.header.pow.secondary_scaling = consensus::MIN_AR_SCALE as u32;
+		chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		assert_eq!(chain.head().unwrap().height, 1);
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn last_n_headers() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_last_n_headers";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..10 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+		assert_eq!(chain.head().unwrap().height, 8);
+
+		let heights = |n: usize| -> Vec<u64> {
+			chain
+				.last_n_headers(n)
+				.unwrap()
+				.iter()
+				.map(|h| h.height)
+				.collect()
+		};
+		assert_eq!(heights(5), (4..=8).collect::<Vec<u64>>());
+		assert_eq!(heights(1), vec![8]);
+		assert!(heights(0).is_empty());
+
+		// capped at genesis
+		assert_eq!(heights(20), (0..=8).collect::<Vec<u64>>());
+		assert_eq!(chain.last_n_headers(20).unwrap()[8].hash(), prev.hash());
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn best_header_tip() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let genesis = pow::mine_genesis_block().unwrap();
+	{
+		let chain_a = setup(".mwc_best_header_tip_a", genesis.clone());
+		let chain_b = setup(".mwc_best_header_tip_b", genesis);
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+
+		let mut headers = vec![];
+		let mut prev = chain_a.head_header().unwrap();
+		for n in 2..8 {
+			let b = prepare_block(&kc, &prev, &chain_a, n);
+			prev = b.header.clone();
+			headers.push(b.header.clone());
+			chain_a.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+		assert_eq!(chain_a.best_header_tip().unwrap(), chain_a.head().unwrap());
+
+		// b only gets the headers, its header tip leads the block head
+		// (the sync MMR is rebuilt when entering header sync, as header_sync does)
+		chain_b
+			.rebuild_sync_mmr(&chain_b.header_head().unwrap())
+			.unwrap();
+		chain_b
+			.sync_block_headers(&headers, chain::Options::SKIP_POW)
+			.unwrap();
+		let tip = chain_b.best_header_tip().unwrap();
+		assert_eq!(tip.height, 6);
+		assert_eq!(tip.last_block_h, prev.hash());
+		assert_eq!(chain_b.head().unwrap().height, 0);
+		assert!(tip.total_difficulty > chain_b.head().unwrap().total_difficulty);
+	}
+	// Cleanup chain directories
+	clean_output_dir(".mwc_best_header_tip_a");
+	clean_output_dir(".mwc_best_header_tip_b");
+}
+
+#[test]
+fn total_difficulty_at_height() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_total_difficulty_at_height";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let genesis_difficulty = chain.total_difficulty_at_height(0).unwrap();
+
+		// block at height n - 1 has a difficulty of n
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..8 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		for height in 1..7 {
+			let sum: u64 = (2..=height + 1).sum();
+			assert_eq!(
+				chain.total_difficulty_at_height(height).unwrap(),
+				genesis_difficulty + Difficulty::from_num(sum)
+			);
+		}
+		assert_eq!(
+			chain.total_difficulty_at_height(6).unwrap(),
+			chain.head().unwrap().total_difficulty
+		);
+		match chain.total_difficulty_at_height(7) {
+			Err(e) => assert_eq!(
+				e.kind(),
+				chain::ErrorKind::HeightAboveHead { height: 7, head: 6 }
+			),
+			Ok(_) => panic!("total difficulty above head"),
+		}
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn block_fees() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_block_fees";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let pb = ProofBuilder::new(&kc);
+
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..6 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+		assert_eq!(chain.block_fees(&prev.hash()).unwrap(), 0);
+
+		let key_id2 = ExtKeychainPath::new(1, 2, 0, 0, 0).to_identifier();
+		let key_id30 = ExtKeychainPath::new(1, 30, 0, 0, 0).to_identifier();
+		let tx1 = build::transaction(
+			vec![
+				build::coinbase_input(consensus::MWC_FIRST_GROUP_REWARD, key_id2),
+				build::output(consensus::MWC_FIRST_GROUP_REWARD - 20000, key_id30),
+				build::with_fee(20000),
+			],
+			&kc,
+			&pb,
+		)
+		.unwrap();
+		let b = prepare_block_tx(&kc, &prev, &chain, 7, vec![&tx1]);
+		let height = b.header.height;
+		let hash = b.hash();
+		chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+
+		let fees = chain.block_fees(&hash).unwrap();
+		assert_eq!(fees, 20000);
+		assert_eq!(
+			fees,
+			consensus::reward(fees, height) - consensus::calc_mwc_block_reward(height)
+		);
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn get_kernel_by_excess() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_kernel_by_excess";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let pb = ProofBuilder::new(&kc);
+
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..6 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		let key_id2 = ExtKeychainPath::new(1, 2, 0, 0, 0).to_identifier();
+		let key_id30 = ExtKeychainPath::new(1, 30, 0, 0, 0).to_identifier();
+		let tx1 = build::transaction(
+			vec![
+				build::coinbase_input(consensus::MWC_FIRST_GROUP_REWARD, key_id2),
+				build::output(consensus::MWC_FIRST_GROUP_REWARD - 20000, key_id30.clone()),
+				build::with_fee(20000),
+			],
+			&kc,
+			&pb,
+		)
+		.unwrap();
+		let b = prepare_block_tx(&kc, &prev, &chain, 7, vec![&tx1]);
+		prev = b.header.clone();
+		chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		let b = prepare_block(&kc, &prev, &chain, 8);
+		chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+
+		let kernel = tx1.kernels()[0].clone();
+		let (found, height) = chain.get_kernel_by_excess(&kernel.excess).unwrap().unwrap();
+		assert_eq!(found, kernel);
+		assert_eq!(height, 5);
+
+		// coinbase kernels are indexed as well
+		let block = chain.get_block(&prev.hash()).unwrap();
+		let coinbase = block.kernels().iter().find(|k| k.is_coinbase()).unwrap();
+		let (_, height) = chain
+			.get_kernel_by_excess(&coinbase.excess)
+			.unwrap()
+			.unwrap();
+		assert_eq!(height, 5);
+
+		// an unknown excess isn't found
+		let unknown = kc
+			.commit(1, &key_id30, &keychain::SwitchCommitmentType::Regular)
+			.unwrap();
+		assert_eq!(chain.get_kernel_by_excess(&unknown).unwrap(), None);
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn height_above_head_or_pruned() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_height_above_head_or_pruned";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let mut prev = chain.head_header().unwrap();
+		while prev.height < 101 {
+			let b = prepare_block(&kc, &prev, &chain, 100 + prev.height);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		match chain.get_header_by_height(102) {
+			Err(e) => assert_eq!(
+				e.kind(),
+				chain::ErrorKind::HeightAboveHead {
+					height: 102,
+					head: 101
+				}
+			),
+			Ok(_) => panic!("header above head"),
+		}
+		match chain.get_block_by_height(102) {
+			Err(e) => assert_eq!(
+				e.kind(),
+				chain::ErrorKind::HeightAboveHead {
+					height: 102,
+					head: 101
+				}
+			),
+			Ok(_) => panic!("block above head"),
+		}
+		assert_eq!(chain.get_block_by_height(10).unwrap().header.height, 10);
+
+		// keep 40 blocks of history, older blocks are pruned but not their headers
+		chain.compact_with_horizon(40).unwrap();
+		let tail = chain.tail().unwrap().height;
+		assert_eq!(tail, 61);
+		match chain.get_block_by_height(10) {
+			Err(e) => assert_eq!(e.kind(), chain::ErrorKind::HeightPruned { horizon: tail }),
+			Ok(_) => panic!("block below horizon"),
+		}
+		assert_eq!(chain.get_header_by_height(10).unwrap().height, 10);
+		assert_eq!(chain.get_block_by_height(tail).unwrap().header.height, tail);
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn skip_rangeproofs_deferred_to_validate() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_skip_rangeproofs";
+	clean_output_dir(chain_dir);
+	let genesis = pow::mine_genesis_block().unwrap();
+	{
+		let chain = setup(chain_dir, genesis);
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let opts = chain::Options::SKIP_POW | chain::Options::SKIP_RANGEPROOFS;
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..5 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, opts).unwrap();
+		}
+
+		// plant the rangeproof of another output in the coinbase, the roots are
+		// computed after the swap so only the rangeproof itself is bad
+		let other = prepare_block_nosum(&kc, &prev, 99, vec![]);
+		let mut b = prepare_block_nosum(&kc, &prev, 5, vec![]);
+		b.outputs_mut()[0].proof = other.outputs()[0].proof;
+		chain.set_txhashset_roots(&mut b).unwrap();
+
+		// rejected when the rangeproofs are checked
+		assert!(chain
+			.process_block(b.clone(), chain::Options::SKIP_POW)
+			.is_err());
+
+		// accepted when they are deferred
+		chain.process_block(b, opts).unwrap();
+		assert_eq!(chain.head().unwrap().height, 4);
+
+		// only the full validation catches it
+		chain.validate(true).unwrap();
+		assert!(chain.validate(false).is_err());
+	}
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn utxo_snapshot() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_utxo_snapshot";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let pb = ProofBuilder::new(&kc);
+
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..6 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		// spend the coinbase of the first block at height 5
+		let key_id2 = ExtKeychainPath::new(1, 2, 0, 0, 0).to_identifier();
+		let key_id30 = ExtKeychainPath::new(1, 30, 0, 0, 0).to_identifier();
+		let tx = build::transaction(
+			vec![
+				build::coinbase_input(consensus::MWC_FIRST_GROUP_REWARD, key_id2),
+				build::output(consensus::MWC_FIRST_GROUP_REWARD - 20000, key_id30),
+				build::with_fee(20000),
+			],
+			&kc,
+			&pb,
+		)
+		.unwrap();
+		let b = prepare_block_tx(&kc, &prev, &chain, 6, vec![&tx]);
+		let created: Vec<OutputIdentifier> = b
+			.outputs()
+			.iter()
+			.map(|out| OutputIdentifier::from_output(out))
+			.collect();
+		chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		let spent = OutputIdentifier::from_output(&chain.coinbase_output(1).unwrap());
+		let head = chain.head().unwrap();
+
+		let before = chain.utxo_snapshot(4).unwrap();
+		let after = chain.utxo_snapshot(5).unwrap();
+		assert_eq!(before.len(), 4);
+		assert_eq!(after.len(), 5);
+
+		// the spent coinbase only is gone, the block outputs only are new
+		let removed: Vec<_> = before.iter().filter(|o| !after.contains(o)).collect();
+		let added: Vec<_> = after.iter().filter(|o| !before.contains(o)).collect();
+		assert_eq!(removed, vec![&spent]);
+		assert_eq!(added.len(), created.len());
+		assert!(added.iter().all(|o| created.contains(o)));
+
+		// the live chain state is untouched
+		assert_eq!(chain.head().unwrap(), head);
+		chain.validate(false).unwrap();
+
+		assert!(chain.utxo_snapshot(6).is_err());
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn kernel_merkle_proof() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_kernel_merkle_proof";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let pb = ProofBuilder::new(&kc);
+
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..6 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		let key_id2 = ExtKeychainPath::new(1, 2, 0, 0, 0).to_identifier();
+		let key_id30 = ExtKeychainPath::new(1, 30, 0, 0, 0).to_identifier();
+		let tx1 = build::transaction(
+			vec![
+				build::coinbase_input(consensus::MWC_FIRST_GROUP_REWARD, key_id2),
+				build::output(consensus::MWC_FIRST_GROUP_REWARD - 20000, key_id30),
+				build::with_fee(20000),
+			],
+			&kc,
+			&pb,
+		)
+		.unwrap();
+		let b = prepare_block_tx(&kc, &prev, &chain, 7, vec![&tx1]);
+		let coinbase = b
+			.kernels()
+			.iter()
+			.find(|k| k.is_coinbase())
+			.unwrap()
+			.clone();
+		prev = b.header.clone();
+		chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+
+		let kernel = tx1.kernels()[0].clone();
+		let (proof, pos) = chain.kernel_merkle_proof(&kernel.excess).unwrap();
+		assert_eq!(proof.mmr_size, chain.head_header().unwrap().kernel_mmr_size);
+		chain
+			.verify_kernel_merkle_proof(&kernel, &proof, pos)
+			.unwrap();
+
+		// the proof doesn't hold for another kernel, or another position
+		assert!(chain
+			.verify_kernel_merkle_proof(&coinbase, &proof, pos)
+			.is_err());
+		let (cb_proof, cb_pos) = chain.kernel_merkle_proof(&coinbase.excess).unwrap();
+		assert_ne!(cb_pos, pos);
+		assert!(chain
+			.verify_kernel_merkle_proof(&kernel, &proof, cb_pos)
+			.is_err());
+		chain
+			.verify_kernel_merkle_proof(&coinbase, &cb_proof, cb_pos)
+			.unwrap();
+
+		// the proof doesn't rely on the kernel index
+		{
+			let store = chain.store();
+			let batch = store.batch().unwrap();
+			batch.clear_kernel_pos().unwrap();
+			batch.commit().unwrap();
+		}
+		chain
+			.verify_kernel_merkle_proof(&coinbase, &cb_proof, cb_pos)
+			.unwrap();
+
+		// no proof from a stale index entry pointing to another kernel
+		{
+			let store = chain.store();
+			let batch = store.batch().unwrap();
+			batch.save_kernel_pos(&kernel.excess, cb_pos).unwrap();
+			batch.commit().unwrap();
+		}
+		assert!(chain.kernel_merkle_proof(&kernel.excess).is_err());
+		chain.reindex().unwrap();
+
+		// a proof has to be rebuilt once the head moved
+		let b = prepare_block(&kc, &prev, &chain, 8);
+		chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		assert!(chain
+			.verify_kernel_merkle_proof(&coinbase, &cb_proof, cb_pos)
+			.is_err());
+		let (proof, pos) = chain.kernel_merkle_proof(&kernel.excess).unwrap();
+		chain
+			.verify_kernel_merkle_proof(&kernel, &proof, pos)
+			.unwrap();
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn orphan_pool_limits() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let source_dir = ".mwc_orphan_limits_source";
+	let chain_dir = ".mwc_orphan_limits";
+	let genesis = pow::mine_genesis_block().unwrap();
+	{
+		let source = setup(source_dir, genesis.clone());
+		let chain = setup(chain_dir, genesis);
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		chain.set_orphan_limits(3, 300);
+		assert_eq!(chain.max_orphans(), 3);
+
+		let mut prev = source.head_header().unwrap();
+		let mut blocks = vec![];
+		for n in 2..10 {
+			let b = prepare_block(&kc, &prev, &source, n);
+			prev = b.header.clone();
+			source
+				.process_block(b.clone(), chain::Options::SKIP_POW)
+				.unwrap();
+			blocks.push(b);
+		}
+
+		// withhold the first block, all its descendants are orphans
+		for b in blocks.iter().skip(1) {
+			assert!(chain
+				.process_block(b.clone(), chain::Options::SKIP_POW)
+				.is_err());
+			assert!(chain.orphan_count() <= 3);
+		}
+		assert_eq!(chain.orphan_count(), 3);
+		assert_eq!(chain.orphans_evicted_len(), 4);
+
+		// the oldest orphans were evicted
+		assert!(!chain.is_orphan(&blocks[1].hash()));
+		assert!(chain.is_orphan(&blocks[7].hash()));
+
+		// and expired ones are dropped
+		chain.set_orphan_limits(3, 0);
+		assert_eq!(chain.orphan_count(), 0);
+	}
+	clean_output_dir(source_dir);
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn checkpoints() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_checkpoints";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+
+		let prev = chain.head_header().unwrap();
+		let b1 = prepare_block(&kc, &prev, &chain, 2);
+		chain
+			.process_block(b1.clone(), chain::Options::SKIP_POW)
+			.unwrap();
+
+		// two competing blocks at height 2, only one of them checkpointed
+		let b2 = prepare_block(&kc, &b1.header, &chain, 3);
+		let b2_alt = prepare_block(&kc, &b1.header, &chain, 4);
+		chain.add_checkpoint(2, b2.hash());
+
+		match chain.process_block(b2_alt, chain::Options::SKIP_POW) {
+			Ok(_) => panic!("block conflicting with a checkpoint accepted"),
+			Err(e) => assert_eq!(e.kind(), chain::ErrorKind::CheckpointMismatch { height: 2 }),
+		}
+		chain
+			.process_block(b2.clone(), chain::Options::SKIP_POW)
+			.unwrap();
+		assert_eq!(chain.head().unwrap().last_block_h, b2.hash());
+
+		// a block with a bad kernel signature, below a checkpoint vouching for it
+		let mut b3 = prepare_block_nosum(&kc, &b2.header, 5, vec![]);
+		b3.kernels_mut()[0].excess_sig = b2.kernels()[0].excess_sig.clone();
+		chain.set_txhashset_roots(&mut b3).unwrap();
+		chain.add_checkpoint(3, b3.hash());
+
+		assert!(chain
+			.process_block(b3.clone(), chain::Options::SKIP_POW)
+			.is_err());
+		// not assumed valid until its header is on the header chain we sync
+		assert!(chain
+			.process_block(
+				b3.clone(),
+				chain::Options::SKIP_POW | chain::Options::ASSUME_VALID,
+			)
+			.is_err());
+		chain
+			.rebuild_sync_mmr(&chain.header_head().unwrap())
+			.unwrap();
+		chain
+			.sync_block_headers(&[b3.header.clone()], chain::Options::SKIP_POW)
+			.unwrap();
+		chain
+			.process_block(
+				b3.clone(),
+				chain::Options::SKIP_POW | chain::Options::ASSUME_VALID,
+			)
+			.unwrap();
+		assert_eq!(chain.head().unwrap().last_block_h, b3.hash());
+
+		// above the highest checkpoint the signatures are always verified
+		let mut b4 = prepare_block_nosum(&kc, &b3.header, 6, vec![]);
+		b4.kernels_mut()[0].excess_sig = b2.kernels()[0].excess_sig.clone();
+		chain.set_txhashset_roots(&mut b4).unwrap();
+		assert!(chain
+			.process_block(b4, chain::Options::SKIP_POW | chain::Options::ASSUME_VALID)
+			.is_err());
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn all_tips() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_all_tips";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+
+		// only the genesis to start with
+		let genesis = chain.head().unwrap();
+		assert_eq!(chain.all_tips().unwrap(), vec![genesis]);
+
+		let prev = chain.head_header().unwrap();
+		let b1 = prepare_block(&kc, &prev, &chain, 2);
+		chain
+			.process_block(b1.clone(), chain::Options::SKIP_POW)
+			.unwrap();
+
+		// two competing branches from b1, the winning one 2 blocks long
+		let b2 = prepare_block(&kc, &b1.header, &chain, 4);
+		let bfork = prepare_block(&kc, &b1.header, &chain, 3);
+		chain
+			.process_block(b2.clone(), chain::Options::SKIP_POW)
+			.unwrap();
+		let b3 = prepare_block(&kc, &b2.header, &chain, 5);
+		chain
+			.process_block(bfork.clone(), chain::Options::SKIP_POW)
+			.unwrap();
+		chain.process_block(b3, chain::Options::SKIP_POW).unwrap();
+
+		let tips = chain.all_tips().unwrap();
+		assert_eq!(tips.len(), 2);
+		assert_eq!(tips[0], chain.head().unwrap());
+		assert_eq!(tips[0].height, 3);
+		assert_eq!(tips[1], Tip::from_header(&bfork.header));
+		assert_eq!(tips[1].height, 2);
+		assert!(tips[0].total_difficulty > tips[1].total_difficulty);
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn export_difficulty_window() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_export_difficulty_window";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..8 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		// recompute the next difficulty from the exported window of the head
+		let data = chain.export_difficulty_window(prev.height).unwrap();
+		let expected =
+			consensus::next_difficulty(prev.height + 1, chain.difficulty_iter().unwrap());
+		assert_eq!(
+			consensus::next_difficulty_from_bytes(prev.height + 1, &data).unwrap(),
+			expected
+		);
+
+		// and from a window further back in the history
+		let header = chain.get_header_by_height(3).unwrap();
+		let data = chain.export_difficulty_window(3).unwrap();
+		let expected = consensus::next_difficulty(4, chain.difficulty_iter_from(&header).unwrap());
+		assert_eq!(
+			consensus::next_difficulty_from_bytes(4, &data).unwrap(),
+			expected
+		);
+
+		// a truncated window is rejected
+		assert!(consensus::next_difficulty_from_bytes(4, &data[..data.len() / 2]).is_err());
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn verification_timing() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_verification_timing";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let pb = ProofBuilder::new(&kc);
+		assert_eq!(
+			chain.verification_timing(),
+			chain::VerificationTiming::default()
+		);
+
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..6 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		// a block spending the first coinbase to several outputs
+		let key_id2 = ExtKeychainPath::new(1, 2, 0, 0, 0).to_identifier();
+		let mut parts = vec![
+			build::coinbase_input(consensus::MWC_FIRST_GROUP_REWARD, key_id2),
+			build::with_fee(20000),
+		];
+		let value = (consensus::MWC_FIRST_GROUP_REWARD - 20000) / 4;
+		for i in 0..4 {
+			let key_id = ExtKeychainPath::new(1, 40 + i, 0, 0, 0).to_identifier();
+			parts.push(build::output(value, key_id));
+		}
+		let tx = build::transaction(parts, &kc, &pb).unwrap();
+		assert_eq!(tx.outputs().len(), 4);
+
+		let b = prepare_block_tx(&kc, &prev, &chain, 6, vec![&tx]);
+		assert_eq!(b.outputs().len(), 5);
+		chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+
+		let timing = chain.verification_timing();
+		assert!(timing.avg_rangeproof_us > 0);
+		assert!(timing.avg_kernel_us > 0);
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn total_supply() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_total_supply";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+
+		// the testing genesis has no reward
+		assert_eq!(chain.total_supply().unwrap(), 0);
+
+		let mut prev = chain.head_header().unwrap();
+		let mut supply = 0;
+		for n in 1..6 {
+			let b = prepare_block(&kc, &prev, &chain, n + 1);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+
+			let new_supply = chain.total_supply().unwrap();
+			assert_eq!(new_supply, supply + consensus::calc_mwc_block_reward(n));
+			supply = new_supply;
+		}
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn disk_usage() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let chain = setup(".mwc_disk_usage", pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let pb = ProofBuilder::new(&kc);
+
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..6 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		// spend the first two coinbases (sibling leaves in the rangeproof MMR) so
+		// compaction has a subtree to prune, a lone spent leaf keeps its data
+		let key_id2 = ExtKeychainPath::new(1, 2, 0, 0, 0).to_identifier();
+		let key_id3 = ExtKeychainPath::new(1, 3, 0, 0, 0).to_identifier();
+		let key_id30 = ExtKeychainPath::new(1, 30, 0, 0, 0).to_identifier();
+		let tx1 = build::transaction(
+			vec![
+				build::coinbase_input(consensus::MWC_FIRST_GROUP_REWARD, key_id2.clone()),
+				build::coinbase_input(consensus::MWC_FIRST_GROUP_REWARD, key_id3.clone()),
+				build::output(
+					2 * consensus::MWC_FIRST_GROUP_REWARD - 20000,
+					key_id30.clone(),
+				),
+				build::with_fee(20000),
+			],
+			&kc,
+			&pb,
+		)
+		.unwrap();
+		let b = prepare_block_tx(&kc, &prev, &chain, 7, vec![&tx1]);
+		prev = b.header.clone();
+		chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+
+		let usage = chain.disk_usage().unwrap();
+		assert!(usage.headers_bytes > 0);
+		assert!(usage.outputs_bytes > 0);
+		assert!(usage.rangeproofs_bytes > 0);
+		assert!(usage.kernels_bytes > 0);
+		let mmr_bytes = usage.headers_bytes
+			+ usage.outputs_bytes
+			+ usage.rangeproofs_bytes
+			+ usage.kernels_bytes;
+		assert!(usage.total >= mmr_bytes);
+
+		// go far enough past the horizon for compaction to kick in
+		let threshold = global::cut_through_horizon() as u64 + 60;
+		for n in 0..(threshold + 1) {
+			let b = prepare_block(&kc, &prev, &chain, 100 + n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		let before = chain.disk_usage().unwrap();
+		chain.compact().unwrap();
+		chain.validate(false).unwrap();
+		let after = chain.disk_usage().unwrap();
+		assert!(after.rangeproofs_bytes < before.rangeproofs_bytes);
+		assert!(after.total > 0);
+	}
+	// Cleanup chain directory
+	clean_output_dir(".mwc_disk_usage");
+}
+
+#[test]
+fn cumulative_fees() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_cumulative_fees";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let pb = ProofBuilder::new(&kc);
+
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..6 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		// spends a coinbase from the first blocks, paying the provided fee
+		let spend_coinbase = |prev: &BlockHeader, coinbase_key: u32, fee: u64, diff: u64| {
+			let key_id = ExtKeychainPath::new(1, coinbase_key, 0, 0, 0).to_identifier();
+			let out_key_id = ExtKeychainPath::new(1, 30 + coinbase_key, 0, 0, 0).to_identifier();
+			let tx = build::transaction(
+				vec![
+					build::coinbase_input(consensus::MWC_FIRST_GROUP_REWARD, key_id),
+					build::output(consensus::MWC_FIRST_GROUP_REWARD - fee, out_key_id),
+					build::with_fee(fee),
+				],
+				&kc,
+				&pb,
+			)
+			.unwrap();
+			let b = prepare_block_tx(&kc, prev, &chain, diff, vec![&tx]);
+			chain
+				.process_block(b.clone(), chain::Options::SKIP_POW)
+				.unwrap();
+			b.header
+		};
+
+		let prev = spend_coinbase(&prev, 2, 20000, 7);
+		let prev = spend_coinbase(&prev, 3, 30000, 8);
+		let b = prepare_block(&kc, &prev, &chain, 9);
+		chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		assert_eq!(chain.head().unwrap().height, 7);
+
+		assert_eq!(chain.cumulative_fees(0, 7).unwrap(), 50000);
+		assert_eq!(chain.cumulative_fees(0, 4).unwrap(), 0);
+		assert_eq!(chain.cumulative_fees(5, 5).unwrap(), 20000);
+		assert_eq!(chain.cumulative_fees(6, 7).unwrap(), 30000);
+		assert!(chain.cumulative_fees(6, 5).is_err());
+		assert!(chain.cumulative_fees(7, 8).is_err());
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn scan_coinbase_outputs() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_scan_coinbase_outputs";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let mut prev = chain.head_header().unwrap();
+		let mut blocks = vec![];
+		for n in 2..7 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain
+				.process_block(b.clone(), chain::Options::SKIP_POW)
+				.unwrap();
+			blocks.push(b);
+		}
+
+		// our coinbase at height 3 and a commitment not on chain
+		let ours = &blocks[2].outputs()[0];
+		assert_eq!(blocks[2].header.height, 3);
+		let key_id = ExtKeychainPath::new(1, 100, 0, 0, 0).to_identifier();
+		let unknown = kc
+			.commit(100, &key_id, &keychain::SwitchCommitmentType::Regular)
+			.unwrap();
+		let mut commits = HashSet::new();
+		commits.insert(ours.commitment());
+		commits.insert(unknown);
+
+		let found = chain.scan_coinbase_outputs(0, 5, &commits).unwrap();
+		assert_eq!(found, vec![(3, OutputIdentifier::from_output(ours))]);
+		assert!(chain
+			.scan_coinbase_outputs(4, 5, &commits)
+			.unwrap()
+			.is_empty());
+		assert!(chain.scan_coinbase_outputs(3, 2, &commits).is_err());
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn compact_with_horizon() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let chain = setup(".mwc_compact_horizon", pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let pb = ProofBuilder::new(&kc);
+
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..6 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		// spends two coinbases from the first blocks at the next height, the
+		// first one at an even leaf so both make a prunable subtree
+		let spend_coinbases = |prev: &BlockHeader, coinbase_key: u32, diff: u64| {
+			let key_id = ExtKeychainPath::new(1, coinbase_key, 0, 0, 0).to_identifier();
+			let key_id_next = ExtKeychainPath::new(1, coinbase_key + 1, 0, 0, 0).to_identifier();
+			let out_key_id = ExtKeychainPath::new(1, 30 + coinbase_key, 0, 0, 0).to_identifier();
+			let tx = build::transaction(
+				vec![
+					build::coinbase_input(consensus::MWC_FIRST_GROUP_REWARD, key_id),
+					build::coinbase_input(consensus::MWC_FIRST_GROUP_REWARD, key_id_next),
+					build::output(2 * consensus::MWC_FIRST_GROUP_REWARD - 20000, out_key_id),
+					build::with_fee(20000),
+				],
+				&kc,
+				&pb,
+			)
+			.unwrap();
+			let b = prepare_block_tx(&kc, prev, &chain, diff, vec![&tx]);
+			chain
+				.process_block(b.clone(), chain::Options::SKIP_POW)
+				.unwrap();
+			b.header
+		};
+		let mine_to = |mut prev: BlockHeader, height: u64| {
+			while prev.height < height {
+				let b = prepare_block(&kc, &prev, &chain, 100 + prev.height);
+				prev = b.header.clone();
+				chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+			}
+			prev
+		};
+		let has_block =
+			|height: u64| chain.get_block(&chain.get_header_by_height(height).unwrap().hash()).is_ok();
+
+		// one spend early on, one later
+		let prev = spend_coinbases(&prev, 2, 7);
+		assert_eq!(prev.height, 5);
+		let prev = mine_to(prev, 89);
+		let prev = spend_coinbases(&prev, 4, 8);
+		assert_eq!(prev.height, 90);
+		let prev = mine_to(prev, 101);
+
+		// below the state sync threshold is not reorg-safe
+		let min_horizon = global::state_sync_threshold();
+		assert!(chain.compact_with_horizon(min_horizon - 1).is_err());
+
+		// keep 40 blocks of history
+		let usage = chain.disk_usage().unwrap();
+		chain.compact_with_horizon(40).unwrap();
+		chain.validate(false).unwrap();
+		assert_eq!(chain.tail().unwrap().height, 61);
+		assert!(!has_block(60));
+		assert!(has_block(61));
+		assert!(has_block(90));
+		// the early spent rangeproofs got pruned
+		let compacted = chain.disk_usage().unwrap();
+		assert!(compacted.rangeproofs_bytes < usage.rangeproofs_bytes);
+
+		// more aggressive, keep only the state sync threshold worth of blocks
+		let prev = mine_to(prev, 141);
+		let usage = chain.disk_usage().unwrap();
+		chain.compact_with_horizon(min_horizon).unwrap();
+		chain.validate(false).unwrap();
+		assert_eq!(chain.tail().unwrap().height, 141 - min_horizon as u64);
+		assert!(!has_block(90));
+		assert!(has_block(141 - min_horizon as u64));
+		assert!(has_block(prev.height));
+		// the later spent rangeproofs got pruned as well
+		let compacted = chain.disk_usage().unwrap();
+		assert!(compacted.rangeproofs_bytes < usage.rangeproofs_bytes);
+	}
+	// Cleanup chain directory
+	clean_output_dir(".mwc_compact_horizon");
+}
+
+/// Test ability to retrieve block headers for a given output
+#[test]
+fn output_header_mappings() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let chain = setup(".mwc_header_for_output", pow::mine_genesis_block().unwrap());
+		let keychain = ExtKeychain::from_random_seed(false).unwrap();
+		let mut reward_outputs = vec![];
+
+		for n in 1..15 {
+			let prev = chain.head_header().unwrap();
+			let next_header_info = consensus::next_difficulty(1, chain.difficulty_iter().unwrap());
+			let pk = ExtKeychainPath::new(1, n as u32, 0, 0, 0).to_identifier();
+			let reward = libtx::reward::output(
+				&keychain,
+				&libtx::ProofBuilder::new(&keychain),
+				&pk,
+				0,
+				false,
+				prev.height + 1,
+			)
+			.unwrap();
+			reward_outputs.push(reward.0.clone());
+			let mut b =
+				core::core::Block::new(&prev, vec![], next_header_info.clone().difficulty, reward)
+					.unwrap();
+			b.header.timestamp = prev.timestamp + Duration::seconds(60);
+			b.header.pow.secondary_scaling = next_header_info.secondary_scaling;
+
+			chain.set_txhashset_roots(&mut b).unwrap();
+
+			let edge_bits = if n == 2 {
+				global::min_edge_bits() + 1
+			} else {
+				global::min_edge_bits()
+			};
+			b.header.pow.proof.edge_bits = edge_bits;
+			pow::pow_size(
+				&mut b.header,
+				next_header_info.difficulty,
+				global::proofsize(),
+				edge_bits,
+			)
+			.unwrap();
+			b.header.pow.proof.edge_bits = edge_bits;
+
+			chain.process_block(b, chain::Options::MINE).unwrap();
+
+			let header_for_output = chain
+				.get_header_for_output(&OutputIdentifier::from_output(&reward_outputs[n - 1]))
+				.unwrap();
+			assert_eq!(header_for_output.height, n as u64);
+
+			chain.validate(false).unwrap();
+		}
+
+		// Check all output positions are as expected
+		for n in 1..15 {
+			let header_for_output = chain
+				.get_header_for_output(&OutputIdentifier::from_output(&reward_outputs[n - 1]))
+				.unwrap();
+			assert_eq!(header_for_output.height, n as u64);
+		}
+
+		// The coinbase output of each block is its reward output
+		for n in 1..15 {
+			let coinbase = chain.coinbase_output(n as u64).unwrap();
+			assert_eq!(coinbase.features, OutputFeatures::Coinbase);
+			assert_eq!(coinbase, reward_outputs[n - 1]);
+		}
+
+		// Batch lookup matches the per-output results, in the provided order
+		let mut output_ids: Vec<OutputIdentifier> = reward_outputs
+			.iter()
+			.map(|out| OutputIdentifier::from_output(out))
+			.collect();
+		output_ids.reverse();
+		output_ids.swap(2, 9);
+		let headers_for_outputs = chain.get_headers_for_outputs(&output_ids).unwrap();
+		assert_eq!(headers_for_outputs.len(), output_ids.len());
+		for (output_id, (batch_id, batch_header)) in output_ids.iter().zip(headers_for_outputs) {
+			assert_eq!(*output_id, batch_id);
+			let header_for_output = chain.get_header_for_output(output_id).unwrap();
+			assert_eq!(header_for_output.hash(), batch_header.hash());
+		}
+		assert!(chain.get_headers_for_outputs(&[]).unwrap().is_empty());
+
+		// Unknown output fails the whole batch
+		let unknown = OutputIdentifier::new(OutputFeatures::Plain, &reward_outputs[0].commitment());
+		let mut with_unknown = output_ids.clone();
+		with_unknown.push(unknown);
+		assert!(chain.get_headers_for_outputs(&with_unknown).is_err());
+	}
+	// Cleanup chain directory
+	clean_output_dir(".mwc_header_for_output");
+}
+
+#[test]
+fn next_coinbase_value() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let chain = setup(".mwc_coinbase_value", pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+
+		let mut prev = chain.head_header().unwrap();
+		for n in 1..4 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		let head = chain.head().unwrap();
+		assert_eq!(head.height, 3);
+		assert_eq!(
+			chain.next_coinbase_value(0).unwrap(),
+			consensus::reward(0, head.height + 1)
+		);
+		assert_eq!(
+			chain.next_coinbase_value(20_000).unwrap(),
+			consensus::reward(20_000, head.height + 1)
+		);
+	}
+	// Cleanup chain directory
+	clean_output_dir(".mwc_coinbase_value");
+}
+
+#[test]
+fn reject_duplicate_utxo() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let chain = setup(".mwc_duplicate_utxo", pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+
+		// mine a coinbase output
+		let prev = chain.head_header().unwrap();
+		let b1 = prepare_block(&kc, &prev, &chain, 1);
+		let coinbase = b1.outputs()[0].commitment();
+		chain.process_block(b1, chain::Options::SKIP_POW).unwrap();
+
+		// same key and same reward, so the coinbase commitment is reused
+		let prev = chain.head_header().unwrap();
+		let mut b2 = prepare_block_nosum(&kc, &prev, 1, vec![]);
+		assert_eq!(b2.outputs()[0].commitment(), coinbase);
+
+		// the header itself is fine, take the header MMR root from a valid block
+		let valid = prepare_block(&kc, &prev, &chain, 2);
+		b2.header.prev_root = valid.header.prev_root;
+
+		let res = chain.process_block(b2, chain::Options::SKIP_POW);
+		match res {
+			Ok(_) => panic!("block re-creating an unspent output must be rejected"),
+			Err(e) => assert!(format!("{}", e.kind()).contains("Duplicate UTXO")),
+		}
+		assert_eq!(chain.head().unwrap().height, 1);
+	}
+	// Cleanup chain directory
+	clean_output_dir(".mwc_duplicate_utxo");
+}
+
+#[test]
+fn invalid_block_tx_identified() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let chain = setup(".mwc_invalid_block_tx", pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let pb = ProofBuilder::new(&kc);
+
+		// mine enough blocks for the first coinbase to mature
+		for n in 2..7 {
+			let prev = chain.head_header().unwrap();
+			let b = prepare_block(&kc, &prev, &chain, n);
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		let key_id2 = ExtKeychainPath::new(1, 2, 0, 0, 0).to_identifier();
+		let key_id30 = ExtKeychainPath::new(1, 30, 0, 0, 0).to_identifier();
+		let key_id31 = ExtKeychainPath::new(1, 31, 0, 0, 0).to_identifier();
+		let key_id40 = ExtKeychainPath::new(1, 40, 0, 0, 0).to_identifier();
+		let key_id41 = ExtKeychainPath::new(1, 41, 0, 0, 0).to_identifier();
+
+		// valid tx spending the first coinbase
+		let tx1 = build::transaction(
+			vec![
+				build::coinbase_input(consensus::MWC_FIRST_GROUP_REWARD, key_id2.clone()),
+				build::output(consensus::MWC_FIRST_GROUP_REWARD - 20000, key_id30.clone()),
+				build::with_fee(20000),
+			],
+			&kc,
+			&pb,
+		)
+		.unwrap();
+
+		// tx spending an output that does not exist
+		let tx2 = build::transaction(
+			vec![
+				build::input(1_000_000, key_id40),
+				build::output(1_000_000 - 20000, key_id41),
+				build::with_fee(20000),
+			],
+			&kc,
+			&pb,
+		)
+		.unwrap();
+
+		// tx spending the same coinbase as tx1
+		let tx3 = build::transaction(
+			vec![
+				build::coinbase_input(consensus::MWC_FIRST_GROUP_REWARD, key_id2.clone()),
+				build::output(consensus::MWC_FIRST_GROUP_REWARD - 20000, key_id31),
+				build::with_fee(20000),
+			],
+			&kc,
+			&pb,
+		)
+		.unwrap();
+
+		// the block template itself can't be assembled
+		let prev = chain.head_header().unwrap();
+		let mut b = prepare_block_nosum(&kc, &prev, 7, vec![&tx1, &tx2]);
+		assert!(chain.set_txhashset_roots(&mut b).is_err());
+
+		// but the offending tx can be identified by its kernel
+		chain.validate_block_txs(&[tx1.clone()]).unwrap();
+		let res = chain.validate_block_txs(&[tx1.clone(), tx2.clone()]);
+		match res.unwrap_err().kind() {
+			chain::ErrorKind::InvalidBlockTx { kernel, .. } => {
+				assert_eq!(kernel, Some(tx2.kernels()[0].excess()))
+			}
+			e => panic!("unexpected error {:?}", e),
+		}
+		let res = chain.validate_block_txs(&[tx1.clone(), tx3.clone()]);
+		match res.unwrap_err().kind() {
+			chain::ErrorKind::InvalidBlockTx { kernel, .. } => {
+				assert_eq!(kernel, Some(tx3.kernels()[0].excess()))
+			}
+			e => panic!("unexpected error {:?}", e),
+		}
+
+		// dropping the bad tx allows the block to be built
+		let b = prepare_block_tx(&kc, &prev, &chain, 7, vec![&tx1]);
+		chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		assert_eq!(chain.head().unwrap().height, 6);
+	}
+	// Cleanup chain directory
+	clean_output_dir(".mwc_invalid_block_tx");
+}
+
+#[test]
+fn validate_kernel_offsets() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let chain = setup(".mwc_kernel_offsets", pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let pb = ProofBuilder::new(&kc);
+
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..6 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		// spend the first coinbase so the block carries a non zero kernel offset
+		let key_id2 = ExtKeychainPath::new(1, 2, 0, 0, 0).to_identifier();
+		let key_id30 = ExtKeychainPath::new(1, 30, 0, 0, 0).to_identifier();
+		let tx1 = build::transaction(
+			vec![
+				build::coinbase_input(consensus::MWC_FIRST_GROUP_REWARD, key_id2.clone()),
+				build::output(consensus::MWC_FIRST_GROUP_REWARD - 20000, key_id30.clone()),
+				build::with_fee(20000),
+			],
+			&kc,
+			&pb,
+		)
+		.unwrap();
+		let b = prepare_block_tx(&kc, &prev, &chain, 7, vec![&tx1]);
+		chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+
+		chain.validate_kernel_offsets().unwrap();
+
+		// tamper with the sums committed for the last block
+		let head = chain.head_header().unwrap();
+		let prev = chain.get_previous_header(&head).unwrap();
+		{
+			let store = chain.store();
+			let batch = store.batch().unwrap();
+			let prev_sums = batch.get_block_sums(&prev.hash()).unwrap();
+			batch.save_block_sums(&head.hash(), &prev_sums).unwrap();
+			batch.commit().unwrap();
+		}
+		assert!(chain.validate_kernel_offsets().is_err());
+	}
+	// Cleanup chain directory
+	clean_output_dir(".mwc_kernel_offsets");
+}
+
+#[test]
+fn difficulty_iter_from_header() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let chain = setup(".mwc_diff_iter_from", pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+
+		// mine blocks at the difficulty required by the chain, with irregular
+		// timestamps so the difficulty moves around
+		for n in 1..8 {
+			let prev = chain.head_header().unwrap();
+			let next_header_info =
+				consensus::next_difficulty(prev.height + 1, chain.difficulty_iter().unwrap());
+			let pk = ExtKeychainPath::new(1, n as u32, 0, 0, 0).to_identifier();
+			let reward =
+				libtx::reward::output(&kc, &ProofBuilder::new(&kc), &pk, 0, false, prev.height + 1)
+					.unwrap();
+			let mut b = Block::new(&prev, vec![], next_header_info.difficulty, reward).unwrap();
+			b.header.timestamp = prev.timestamp + Duration::seconds(10 * n);
+			b.header.pow.secondary_scaling = next_header_info.secondary_scaling;
+			b.header.pow.proof = pow::Proof::random(global::proofsize());
+			chain.set_txhashset_roots(&mut b).unwrap();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		// next difficulty as of a mid-chain header matches the following block
+		for height in 1..7 {
+			let header = chain.get_header_by_height(height).unwrap();
+			let next = chain.get_header_by_height(height + 1).unwrap();
+			let next_header_info =
+				consensus::next_difficulty(height + 1, chain.difficulty_iter_from(&header).unwrap());
+			assert_eq!(
+				next_header_info.difficulty,
+				next.total_difficulty() - header.total_difficulty()
+			);
+			assert_eq!(
+				next_header_info.secondary_scaling,
+				next.pow.secondary_scaling
+			);
+		}
+
+		// starting from the head is the same as the regular iterator
+		let head = chain.head_header().unwrap();
+		assert_eq!(
+			consensus::next_difficulty(head.height + 1, chain.difficulty_iter_from(&head).unwrap()),
+			consensus::next_difficulty(head.height + 1, chain.difficulty_iter().unwrap())
+		);
+	}
+	// Cleanup chain directory
+	clean_output_dir(".mwc_diff_iter_from");
+}
+
+#[test]
+fn difficulty_iter_bounded() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let chain = setup(".mwc_diff_iter_bounded", pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..7 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		let height = chain.head().unwrap().height as usize;
+		assert_eq!(chain.difficulty_iter().unwrap().count(), height + 1);
+		for max in &[0, 1, 3, height, height + 1, height + 5] {
+			let count = chain.difficulty_iter_bounded(*max).unwrap().count();
+			assert_eq!(count, std::cmp::min(*max, height + 1));
+		}
+
+		// the bounded window gives the same next difficulty
+		let window = consensus::DIFFICULTY_ADJUST_WINDOW as usize + 1;
+		assert_eq!(
+			consensus::next_difficulty(height as u64 + 1, chain.difficulty_iter().unwrap()),
+			consensus::next_difficulty(
+				height as u64 + 1,
+				chain.difficulty_iter_bounded(window).unwrap()
+			)
+		);
+	}
+	// Cleanup chain directory
+	clean_output_dir(".mwc_diff_iter_bounded");
+}
+
+#[test]
+fn block_locator_fork_point() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let genesis = pow::mine_genesis_block().unwrap();
+	{
+		let chain_a = setup(".mwc_locator_a", genesis.clone());
+		let chain_b = setup(".mwc_locator_b", genesis);
+		let kc_a = ExtKeychain::from_random_seed(false).unwrap();
+		let kc_b = ExtKeychain::from_random_seed(false).unwrap();
+
+		// both chains share the first 6 blocks
+		let mut prev = chain_a.head_header().unwrap();
+		for n in 2..8 {
+			let b = prepare_block(&kc_a, &prev, &chain_a, n);
+			prev = b.header.clone();
+			chain_a.process_block(b.clone(), chain::Options::SKIP_POW).unwrap();
+			chain_b.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+		let fork_header = prev.clone();
+
+		// then diverge, 4 more blocks on a and 20 on b
+		let mut prev_a = fork_header.clone();
+		for n in 8..12 {
+			let b = prepare_block(&kc_a, &prev_a, &chain_a, n);
+			prev_a = b.header.clone();
+			chain_a.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+		let mut prev_b = fork_header.clone();
+		for n in 8..28 {
+			let b = prepare_block(&kc_b, &prev_b, &chain_b, n);
+			prev_b = b.header.clone();
+			chain_b.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		// heights 26, 25, 24, 22, 18, 10, 0
+		let locator_b = chain_b.block_locator().unwrap();
+		let heights: Vec<u64> = locator_b
+			.iter()
+			.map(|h| chain_b.get_block_header(h).unwrap().height)
+			.collect();
+		assert_eq!(heights, vec![26, 25, 24, 22, 18, 10, 0]);
+		assert_eq!(locator_b[0], prev_b.hash());
+		assert_eq!(locator_b.last(), Some(&chain_b.get_header_by_height(0).unwrap().hash()));
+
+		// the most recent shared header is at or below the fork point
+		let common = chain_a.locate_fork_point(&locator_b).unwrap();
+		assert!(common.height <= fork_header.height);
+		assert_eq!(common.hash(), chain_b.get_header_by_height(common.height).unwrap().hash());
+
+		// a denser locator from a finds the exact fork point on b
+		let locator_a = chain_a.block_locator().unwrap();
+		let heights: Vec<u64> = locator_a
+			.iter()
+			.map(|h| chain_a.get_block_header(h).unwrap().height)
+			.collect();
+		assert_eq!(heights, vec![10, 9, 8, 6, 2, 0]);
+		let common = chain_b.locate_fork_point(&locator_a).unwrap();
+		assert_eq!(common.hash(), fork_header.hash());
+
+		// a chain always shares its own head
+		assert_eq!(chain_a.locate_fork_point(&locator_a).unwrap().hash(), prev_a.hash());
+
+		// nothing in common
+		assert!(chain_a.locate_fork_point(&[prev_b.hash()]).is_err());
+	}
+	// Cleanup chain directories
+	clean_output_dir(".mwc_locator_a");
+	clean_output_dir(".mwc_locator_b");
+}
+
+#[test]
+fn block_timestamp() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let chain = setup(".mwc_block_timestamp", pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..8 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		for height in 0..7 {
+			let header = chain.get_header_by_height(height).unwrap();
+			assert_eq!(
+				chain.block_timestamp(height).unwrap(),
+				header.timestamp.timestamp() as u64
+			);
+		}
+		// blocks are mined 60s apart
+		for height in 1..7 {
+			assert_eq!(
+				chain.block_timestamp(height).unwrap() - chain.block_timestamp(height - 1).unwrap(),
+				60
+			);
+		}
+		assert!(chain.block_timestamp(7).is_err());
+	}
+	// Cleanup chain directory
+	clean_output_dir(".mwc_block_timestamp");
+}
+
+#[test]
+fn median_time_past() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let chain = setup(".mwc_median_time_past", pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..14 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		// blocks are 60s apart, the median of the last 11 is the 6th one back
+		let head = chain.head_header().unwrap();
+		assert_eq!(head.height, 12);
+		let mtp = chain.median_time_past(&head).unwrap();
+		assert_eq!(mtp, head.timestamp.timestamp() as u64 - 5 * 60);
+		assert!(consensus::validate_block_time(&head, mtp));
+
+		// a block at exactly the median time past doesn't pass, one second above does
+		let mut b = prepare_block(&kc, &head, &chain, 14);
+		b.header.timestamp = Utc.timestamp(mtp as i64, 0);
+		assert!(!consensus::validate_block_time(&b.header, mtp));
+		b.header.timestamp = Utc.timestamp(mtp as i64 + 1, 0);
+		assert!(consensus::validate_block_time(&b.header, mtp));
+	}
+	// Cleanup chain directory
+	clean_output_dir(".mwc_median_time_past");
+}
+
+#[test]
+fn subscribe_accepted_blocks() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let chain = setup(".mwc_subscribe", pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let rx = chain.subscribe();
+
+		let prev = chain.head_header().unwrap();
+		let b1 = prepare_block(&kc, &prev, &chain, 2);
+		chain
+			.process_block(b1.clone(), chain::Options::SKIP_POW)
+			.unwrap();
+		let b2 = prepare_block(&kc, &b1.header, &chain, 4);
+		let b2_hash = b2.hash();
+		chain.process_block(b2, chain::Options::SKIP_POW).unwrap();
+
+		// a fork block with less work, not updating the head
+		let b3 = prepare_fork_block(&kc, &b1.header, &chain, 3);
+		let b3_hash = b3.hash();
+		chain.process_block(b3, chain::Options::SKIP_POW).unwrap();
+
+		let accepted = |hash, height, status| ChainEvent::Accepted {
+			hash,
+			height,
+			status,
+		};
+		assert_eq!(
+			rx.try_recv().unwrap(),
+			accepted(b1.hash(), 1, BlockStatus::Next)
+		);
+		assert_eq!(
+			rx.try_recv().unwrap(),
+			accepted(b2_hash, 2, BlockStatus::Next)
+		);
+		assert_eq!(
+			rx.try_recv().unwrap(),
+			accepted(b3_hash, 2, BlockStatus::Fork)
+		);
+		assert!(rx.try_recv().is_err());
+
+		// dropped subscribers are not an issue
+		drop(rx);
+		let prev = chain.head_header().unwrap();
+		let b4 = prepare_block(&kc, &prev, &chain, 5);
+		chain.process_block(b4, chain::Options::SKIP_POW).unwrap();
+		assert_eq!(chain.head().unwrap().height, 3);
+	}
+	// Cleanup chain directory
+	clean_output_dir(".mwc_subscribe");
+}
+
+#[test]
+fn subscribe_multiple_subscribers() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let chain_dir = ".mwc_subscribe_multiple";
+	{
+		let chain = setup(chain_dir, pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+		let rpc = chain.subscribe();
+		let metrics = chain.subscribe();
+
+		let mut prev = chain.head_header().unwrap();
+		for n in 2..5 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+
+		// every subscriber gets the three blocks, in ascending heights
+		for rx in vec![rpc, metrics] {
+			let heights: Vec<u64> = rx
+				.try_iter()
+				.map(|event| match event {
+					ChainEvent::Accepted { height, status, .. } => {
+						assert_eq!(status, BlockStatus::Next);
+						height
+					}
+				})
+				.collect();
+			assert_eq!(heights, vec![1, 2, 3]);
+		}
+	}
+	// Cleanup chain directory
+	clean_output_dir(chain_dir);
+}
+
+#[test]
+fn rewind_to_height() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let chain = setup(".mwc_rewind_to_height", pow::mine_genesis_block().unwrap());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+
+		let mut blocks = vec![];
+		let mut prev = chain.head_header().unwrap();
+		for n in 1..11 {
+			let b = prepare_block(&kc, &prev, &chain, n);
+			prev = b.header.clone();
+			blocks.push(b.clone());
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+		assert_eq!(chain.head().unwrap().height, 10);
+
+		// cannot rewind forward
+		assert!(chain.rewind_to_height(11).is_err());
+
+		let tip = chain.rewind_to_height(5).unwrap();
+		assert_eq!(tip.height, 5);
+		assert_eq!(chain.head().unwrap().height, 5);
+		assert_eq!(chain.header_head().unwrap().height, 5);
+		assert_eq!(chain.head_header().unwrap().hash(), blocks[4].hash());
+		assert_eq!(chain.get_header_by_height(5).unwrap().hash(), blocks[4].hash());
+		chain.validate(false).unwrap();
+
+		// rewound blocks can be processed again
+		for b in blocks.into_iter().skip(5) {
+			chain.process_block(b, chain::Options::SKIP_POW).unwrap();
+		}
+		assert_eq!(chain.head().unwrap().height, 10);
+		chain.validate(false).unwrap();
+	}
+	// Cleanup chain directory
+	clean_output_dir(".mwc_rewind_to_height");
+}
+
+#[test]
+fn orphan_block_signal() {
+	const DIR_NAME: &str = ".mwc_orphan_signal";
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	{
+		let genesis = pow::mine_genesis_block().unwrap();
+		let last_status = RwLock::new(None);
+		let adapter = Arc::new(StatusAdapter::new(last_status));
+		let chain = setup_with_status_adapter(DIR_NAME, genesis.clone(), adapter.clone());
+		let kc = ExtKeychain::from_random_seed(false).unwrap();
+
+		// build two blocks on a separate chain
+		let (b1, b2) = {
+			let builder = setup(".mwc_orphan_signal_builder", genesis);
+			let prev = builder.head_header().unwrap();
+			let b1 = prepare_block(&kc, &prev, &builder, 1);
+			builder.process_block(b1.clone(), chain::Options::SKIP_POW).unwrap();
+			let b2 = prepare_block(&kc, &b1.header, &builder, 2);
+			(b1, b2)
+		};
+		let b2_hash = b2.hash();
+
+		// only feed the second one, its parent is missing
+		let res = chain.process_block(b2, chain::Options::SKIP_POW);
+		match res {
+			Err(e) => assert_eq!(e.kind(), chain::ErrorKind::Orphan),
+			Ok(_) => panic!("block without parent should be an orphan"),
+		}
+		assert_eq!(*adapter.last_orphan.read(), Some(b2_hash));
+		assert_eq!(*adapter.last_status.read(), None);
+		assert!(chain.is_orphan(&b2_hash));
+		assert_eq!(chain.head().unwrap().height, 0);
+
+		// parent arrives, the orphan gets processed as well
+		chain.process_block(b1, chain::Options::SKIP_POW).unwrap();
+		assert!(!chain.is_orphan(&b2_hash));
+		assert_eq!(chain.head().unwrap().last_block_h, b2_hash);
+		assert_eq!(*adapter.last_status.read(), Some(BlockStatus::Next));
+	}
+	// Cleanup chain directories
+	clean_output_dir(DIR_NAME);
+	clean_output_dir(".mwc_orphan_signal_builder");
 }
 
 fn prepare_block<K>(kc: &K, prev: &BlockHeader, chain: &Chain, diff: u64) -> Block
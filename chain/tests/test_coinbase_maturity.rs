@@ -122,6 +122,9 @@ fn test_coinbase_maturity() {
 
 		chain.set_txhashset_roots(&mut block).unwrap();
 
+		// The coinbase output mined at height 1 matures at lock_height.
+		assert_eq!(chain.coinbase_mature_height(&coinbase_txn).unwrap(), lock_height);
+
 		// Confirm the tx attempting to spend the coinbase output
 		// is not valid at the current block height given the current chain state.
 		match chain.verify_coinbase_maturity(&coinbase_txn) {
@@ -78,6 +78,7 @@ fn comments() -> HashMap<String, String> {
 #parameters used for mining as well as wallet output coinbase maturity. Can be:
 #AutomatedTesting - For CI builds and instant blockchain creation
 #UserTesting - For regular user testing (cuckoo 16)
+#Regtest - For local regression testing with 1 second blocks
 #Floonet - For the long term floonet test network
 #Mainnet - For mainnet
 "
@@ -317,6 +318,14 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"max_pool_weight".to_string(),
+		"
+#maximum total weight of the transactions in the pool, lowest fee ones are evicted beyond it
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"mineable_max_weight".to_string(),
 		"
@@ -325,6 +334,56 @@ fn comments() -> HashMap<String, String> {
 		.to_string(),
 	);
 
+	retval.insert(
+		"replace_by_fee".to_string(),
+		"
+#allow a transaction paying a higher fee to replace the txpool transactions
+#spending the same inputs
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"replace_by_fee_increment".to_string(),
+		"
+#minimum fee increment over the replaced transactions fees for a replacement
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"accept_future_lock_window".to_string(),
+		"
+#number of blocks a transaction lock height can be ahead of the chain for the
+#transaction to be held until it can be mined instead of rejected, 0 disables it
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"source_rate_window_secs".to_string(),
+		"
+#time window in seconds over which the transactions received from a peer are rate limited
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"source_max_txs".to_string(),
+		"
+#maximum number of transactions accepted from a single peer within the window
+"
+		.to_string(),
+	);
+
+	retval.insert(
+		"source_max_weight".to_string(),
+		"
+#maximum total weight of the transactions accepted from a single peer within the window
+"
+		.to_string(),
+	);
+
 	retval.insert(
 		"[server.stratum_mining_config]".to_string(),
 		"
@@ -191,6 +191,21 @@ impl GlobalConfig {
 					.unwrap()
 					.wallet_listener_url = "http://127.0.0.1:23415".to_owned();
 			}
+			global::ChainTypes::Regtest => {
+				defaults.api_http_addr = "127.0.0.1:33413".to_owned();
+				defaults.p2p_config.port = 33414;
+				defaults.p2p_config.seeding_type = p2p::Seeding::None;
+				defaults
+					.stratum_mining_config
+					.as_mut()
+					.unwrap()
+					.stratum_server_addr = Some("127.0.0.1:33416".to_owned());
+				defaults
+					.stratum_mining_config
+					.as_mut()
+					.unwrap()
+					.wallet_listener_url = "http://127.0.0.1:33415".to_owned();
+			}
 			global::ChainTypes::AutomatedTesting => {
 				panic!("Can't run automated testing directly");
 			}
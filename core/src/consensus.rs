@@ -20,9 +20,13 @@
 
 use std::cmp::{max, min};
 
-use crate::core::block::HeaderVersion;
+use chrono::prelude::Utc;
+use chrono::Duration;
+
+use crate::core::block::{BlockHeader, HeaderVersion};
 use crate::global;
 use crate::pow::Difficulty;
+use crate::ser::{self, Readable, Reader, Writeable, Writer};
 
 /// A grin is divisible to 10^9, following the SI prefixes
 pub const GRIN_BASE: u64 = 1_000_000_000;
@@ -43,11 +47,33 @@ pub const BLOCK_TIME_SEC: u64 = 60;
 /// The block subsidy amount, one grin per second on average
 //pub const REWARD: u64 = BLOCK_TIME_SEC * GRIN_BASE;
 
+/// Breakdown of the coinbase value at a given height: the scheduled subsidy,
+/// the fees collected by the block and their (saturating) sum.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RewardComponents {
+	/// Block subsidy from the emission schedule
+	pub subsidy: u64,
+	/// Total fees of the block transactions
+	pub fees: u64,
+	/// Total coinbase value, subsidy plus fees
+	pub total: u64,
+}
+
+/// Reward components for a block at the given height collecting the given
+/// fees. All reward related computations should go through here.
+pub fn reward_components(height: u64, fees: u64) -> RewardComponents {
+	// MWC has block reward schedule similar to bitcoin
+	let subsidy = calc_mwc_block_reward(height);
+	RewardComponents {
+		subsidy,
+		fees,
+		total: subsidy.saturating_add(fees),
+	}
+}
+
 /// Actual block reward for a given total fee amount
 pub fn reward(fee: u64, height: u64) -> u64 {
-	// MWC has block reward schedule similar to bitcoin
-	let block_reward = calc_mwc_block_reward(height);
-	block_reward.saturating_add(fee)
+	reward_components(height, fee).total
 }
 
 /// MWC  genesis block reward in nanocoins (10M coins)
@@ -62,9 +88,27 @@ pub const WEEK_HEIGHT: u64 = 7 * DAY_HEIGHT;
 /// A year is 524_160 blocks
 pub const YEAR_HEIGHT: u64 = 52 * WEEK_HEIGHT;
 
+/// Approximate number of years since genesis at the given height,
+/// display helper based on the nominal block time.
+pub fn approx_years_since_genesis(height: u64) -> f64 {
+	height as f64 / YEAR_HEIGHT as f64
+}
+
+/// Approximate number of days since genesis at the given height,
+/// display helper based on the nominal block time.
+pub fn approx_days_since_genesis(height: u64) -> f64 {
+	height as f64 / DAY_HEIGHT as f64
+}
+
 /// Number of blocks before a coinbase matures and can be spent
 pub const COINBASE_MATURITY: u64 = DAY_HEIGHT;
 
+/// Number of blocks before a coinbase matures and can be spent, for a spend
+/// at the given height. Constant for now.
+pub fn coinbase_maturity(_height: u64) -> u64 {
+	COINBASE_MATURITY
+}
+
 /// Ratio the secondary proof of work should take over the primary, as a
 /// function of block height (time). Starts at 90% losing a percent
 /// approximately every week. Represented as an integer between 0 and 100.
@@ -130,46 +174,113 @@ pub const BLOCK_KERNEL_WEIGHT: usize = 3;
 ///
 pub const MAX_BLOCK_WEIGHT: usize = 40_000;
 
+/// Weight of a block with only the reward, a single coinbase output and a
+/// single coinbase kernel. What's left of the max block weight is the room
+/// available to the txs.
+pub fn coinbase_block_weight() -> usize {
+	BLOCK_OUTPUT_WEIGHT + BLOCK_KERNEL_WEIGHT
+}
+
 /// Fork every 6 months.
 pub const HARD_FORK_INTERVAL: u64 = YEAR_HEIGHT / 2;
 
 /// Floonet first hard fork height, set to happen around 2019-06-20
 pub const FLOONET_FIRST_HARD_FORK: u64 = 185_040;
 
+/// Mainnet (and testing chains) header versions schedule, as
+/// (activation height, header version) sorted by activation height.
+/// Add a row at each new hard fork.
+const MAINNET_HEADER_VERSIONS: &[(u64, HeaderVersion)] =
+	&[(0, HeaderVersion(1)), (HARD_FORK_INTERVAL, HeaderVersion(2))];
+
+/// Floonet header versions schedule, see `MAINNET_HEADER_VERSIONS`.
+const FLOONET_HEADER_VERSIONS: &[(u64, HeaderVersion)] =
+	&[(0, HeaderVersion(1)), (FLOONET_FIRST_HARD_FORK, HeaderVersion(2))];
+
+/// End of the known header versions schedule, no header version is valid
+/// from this height until the schedule is extended.
+const HEADER_VERSIONS_END: u64 = 2 * HARD_FORK_INTERVAL;
+
+fn header_versions() -> &'static [(u64, HeaderVersion)] {
+	match *global::CHAIN_TYPE.read() {
+		global::ChainTypes::Floonet => FLOONET_HEADER_VERSIONS,
+		// everything else just like mainnet
+		_ => MAINNET_HEADER_VERSIONS,
+	}
+}
+
+/// Header version scheduled at a given height. Past the end of the known
+/// schedule this is the latest scheduled version.
+pub fn header_version_at(height: u64) -> HeaderVersion {
+	header_versions()
+		.iter()
+		.rev()
+		.find(|(activation_height, _)| *activation_height <= height)
+		.map(|(_, version)| *version)
+		.unwrap_or_default()
+}
+
+/// Height of the next scheduled hard fork strictly after the given height,
+/// including the end of the known schedule. None if there is none.
+pub fn next_fork_height(height: u64) -> Option<u64> {
+	header_versions()
+		.iter()
+		.map(|(activation_height, _)| *activation_height)
+		.chain(std::iter::once(HEADER_VERSIONS_END))
+		.find(|activation_height| *activation_height > height)
+}
+
 /// Check whether the block version is valid at a given height, implements
 /// 6 months interval scheduled hard forks for the first 2 years.
 pub fn valid_header_version(height: u64, version: HeaderVersion) -> bool {
-	let chain_type = global::CHAIN_TYPE.read().clone();
-	match chain_type {
-		global::ChainTypes::Floonet => {
-			if height < FLOONET_FIRST_HARD_FORK {
-				version == HeaderVersion::default()
-			// add branches one by one as we go from hard fork to hard fork
-			// } else if height < FLOONET_SECOND_HARD_FORK {
-			} else if height < 2 * HARD_FORK_INTERVAL {
-				version == HeaderVersion::new(2)
-			} else {
-				false
-			}
-		}
-		// everything else just like mainnet
-		_ => {
-			if height < HARD_FORK_INTERVAL {
-				version == HeaderVersion::default()
-			} else if height < 2 * HARD_FORK_INTERVAL {
-				version == HeaderVersion::new(2)
-			// uncomment branches one by one as we go from hard fork to hard fork
-			/*} else if height < 3 * HARD_FORK_INTERVAL {
-				version == HeaderVersion::new(3)
-			} else if height < 4 * HARD_FORK_INTERVAL {
-				version == HeaderVersion::new(4)
-			} else {
-				version > HeaderVersion::new(4) */
-			} else {
-				false
-			}
+	height < HEADER_VERSIONS_END && version == header_version_at(height)
+}
+
+/// Number of blocks whose median timestamp (median time past) is computed,
+/// see validate_block_time
+pub const MEDIAN_TIME_WINDOW: u64 = 11;
+
+/// Number of block intervals a block timestamp can be ahead of our local time
+pub const FUTURE_TIME_LIMIT_BLOCKS: u64 = 12;
+
+/// Whether the header timestamp is too far in the future, more than
+/// FUTURE_TIME_LIMIT_BLOCKS block intervals ahead of our local time (as in
+/// bitcoin). Not enforced in automated testing mode.
+pub fn block_time_too_far_in_future(header: &BlockHeader) -> bool {
+	let limit = Duration::seconds((FUTURE_TIME_LIMIT_BLOCKS * global::block_time_sec()) as i64);
+	header.timestamp > Utc::now() + limit && !global::is_automated_testing_mode()
+}
+
+/// Number of seconds a header timestamp can fall behind the latest timestamp
+/// seen before it, honest clocks should not disagree by more than the future
+/// time limit.
+pub fn timewarp_tolerance() -> u64 {
+	FUTURE_TIME_LIMIT_BLOCKS * global::block_time_sec()
+}
+
+/// Detect a timewarp in a window of difficulty data, ordered from the most
+/// recent header backward (as provided by the difficulty iterator). Flags the
+/// window if a timestamp regresses more than timewarp_tolerance() below the
+/// latest timestamp preceding it, which backdated timestamps manipulating the
+/// difficulty adjustment would produce.
+pub fn detect_timewarp(diff_data: &[HeaderInfo]) -> bool {
+	let tolerance = timewarp_tolerance();
+	let mut latest = 0;
+	for info in diff_data.iter().rev() {
+		if info.timestamp.saturating_add(tolerance) < latest {
+			return true;
 		}
+		latest = max(latest, info.timestamp);
 	}
+	false
+}
+
+/// Check the header timestamp is strictly above the median time past of the
+/// previous blocks and not too far in the future. Not a block validation rule
+/// (there's no activation height for it), headers are only required to have
+/// a timestamp above the previous one.
+pub fn validate_block_time(header: &BlockHeader, mtp: u64) -> bool {
+	header.timestamp.timestamp() > mtp as i64 && !block_time_too_far_in_future(header)
 }
 
 /// Number of blocks used to calculate difficulty adjustments
@@ -178,6 +289,12 @@ pub const DIFFICULTY_ADJUST_WINDOW: u64 = HOUR_HEIGHT;
 /// Average time span of the difficulty adjustment window
 pub const BLOCK_TIME_WINDOW: u64 = DIFFICULTY_ADJUST_WINDOW * BLOCK_TIME_SEC;
 
+/// Average time span of the difficulty adjustment window for the current
+/// chain type, same as BLOCK_TIME_WINDOW except on regtest.
+pub fn block_time_window() -> u64 {
+	DIFFICULTY_ADJUST_WINDOW * global::block_time_sec()
+}
+
 /// Clamp factor to use for difficulty adjustment
 /// Limit value to within this factor of goal
 pub const CLAMP_FACTOR: u64 = 2;
@@ -223,7 +340,7 @@ pub const INITIAL_DIFFICULTY: u64 = 1_000_000 * UNIT_DIFFICULTY;
 
 /// Minimal header information required for the Difficulty calculation to
 /// take place
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug, Eq, PartialEq, Serialize, Deserialize)]
 pub struct HeaderInfo {
 	/// Timestamp of the header, 1 when not used (returned info)
 	pub timestamp: u64,
@@ -275,9 +392,38 @@ impl HeaderInfo {
 	}
 }
 
+impl Writeable for HeaderInfo {
+	fn write<W: Writer>(&self, writer: &mut W) -> Result<(), ser::Error> {
+		writer.write_u64(self.timestamp)?;
+		self.difficulty.write(writer)?;
+		writer.write_u32(self.secondary_scaling)?;
+		writer.write_u8(if self.is_secondary { 1 } else { 0 })
+	}
+}
+
+impl Readable for HeaderInfo {
+	fn read(reader: &mut dyn Reader) -> Result<HeaderInfo, ser::Error> {
+		let timestamp = reader.read_u64()?;
+		let difficulty = Difficulty::read(reader)?;
+		let secondary_scaling = reader.read_u32()?;
+		let is_secondary = match reader.read_u8()? {
+			0 => false,
+			1 => true,
+			_ => return Err(ser::Error::CorruptedData),
+		};
+		Ok(HeaderInfo::new(
+			timestamp,
+			difficulty,
+			secondary_scaling,
+			is_secondary,
+		))
+	}
+}
+
 /// Move value linearly toward a goal
 pub fn damp(actual: u64, goal: u64, damp_factor: u64) -> u64 {
-	(actual + (damp_factor - 1) * goal) / damp_factor
+	// saturate, a very long gap between blocks shouldn't overflow
+	actual.saturating_add((damp_factor - 1) * goal) / damp_factor
 }
 
 /// limit value to be within some factor from a goal
@@ -299,6 +445,33 @@ pub fn clamp(actual: u64, goal: u64, clamp_factor: u64) -> u64 {
 /// The secondary proof-of-work factor is calculated along the same lines, as
 /// an adjustment on the deviation against the ideal value.
 pub fn next_difficulty<T>(height: u64, cursor: T) -> HeaderInfo
+where
+	T: IntoIterator<Item = HeaderInfo>,
+{
+	next_difficulty_verbose(height, cursor).0
+}
+
+/// Intermediate values computed by `next_difficulty`, useful to understand
+/// why the next target moved.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DifficultyDiag {
+	/// Timestamp delta across the window
+	pub ts_delta: u64,
+	/// Difficulty sum of the last DIFFICULTY_ADJUST_WINDOW blocks
+	pub diff_sum: u64,
+	/// Time delta after dampening (before clamping)
+	pub damped_ts: u64,
+	/// Time delta after dampening and clamping
+	pub adj_ts: u64,
+	/// Number of secondary blocks in the window, in units of 1/100
+	pub ar_count: u64,
+	/// Secondary PoW scaling factor for the next block
+	pub sec_pow_scaling: u32,
+}
+
+/// Same as `next_difficulty` but also returns the intermediate values
+/// of the calculation.
+pub fn next_difficulty_verbose<T>(height: u64, cursor: T) -> (HeaderInfo, DifficultyDiag)
 where
 	T: IntoIterator<Item = HeaderInfo>,
 {
@@ -323,15 +496,117 @@ where
 		.sum();
 
 	// adjust time delta toward goal subject to dampening and clamping
-	let adj_ts = clamp(
-		damp(ts_delta, BLOCK_TIME_WINDOW, DIFFICULTY_DAMP_FACTOR),
-		BLOCK_TIME_WINDOW,
-		CLAMP_FACTOR,
-	);
+	let block_time_window = block_time_window();
+	let damped_ts = damp(ts_delta, block_time_window, DIFFICULTY_DAMP_FACTOR);
+	let adj_ts = clamp(damped_ts, block_time_window, CLAMP_FACTOR);
 	// minimum difficulty avoids getting stuck due to dampening
-	let difficulty = max(MIN_DIFFICULTY, diff_sum * BLOCK_TIME_SEC / adj_ts);
+	let difficulty = max(
+		MIN_DIFFICULTY,
+		diff_sum.saturating_mul(global::block_time_sec()) / adj_ts,
+	);
+
+	let diag = DifficultyDiag {
+		ts_delta,
+		diff_sum,
+		damped_ts,
+		adj_ts,
+		ar_count: ar_count(height, &diff_data[1..]),
+		sec_pow_scaling,
+	};
 
-	HeaderInfo::from_diff_scaling(Difficulty::from_num(difficulty), sec_pow_scaling)
+	(
+		HeaderInfo::from_diff_scaling(Difficulty::from_num(difficulty), sec_pow_scaling),
+		diag,
+	)
+}
+
+/// Simulates the difficulty series `next_difficulty` would produce for a
+/// sequence of actual block intervals (in seconds), to study the retargeting
+/// stability. The history starts with a perfectly timed window at the initial
+/// difficulty, each returned difficulty is the one of the block mined after
+/// the corresponding interval.
+pub fn simulate_difficulty(initial: Difficulty, block_intervals: &[u64]) -> Vec<Difficulty> {
+	let block_time = global::block_time_sec();
+	let mut history: Vec<HeaderInfo> = (0..=DIFFICULTY_ADJUST_WINDOW)
+		.map(|n| HeaderInfo::from_ts_diff((n + 1) * block_time, initial))
+		.collect();
+
+	let mut series = Vec::with_capacity(block_intervals.len());
+	for interval in block_intervals {
+		let height = history.len() as u64;
+		let next = next_difficulty(height, history.iter().rev().cloned());
+		let last_ts = history[history.len() - 1].timestamp;
+		history.push(HeaderInfo::new(
+			last_ts.saturating_add(*interval),
+			next.difficulty,
+			next.secondary_scaling,
+			true,
+		));
+		series.push(next.difficulty);
+	}
+	series
+}
+
+/// Materializes the difficulty window used by `next_difficulty`, ordered from
+/// oldest to latest and padded with simulated pre-genesis data if needed.
+/// Useful to export the window for offline analysis.
+pub fn dump_difficulty_window<T>(cursor: T) -> Vec<HeaderInfo>
+where
+	T: IntoIterator<Item = HeaderInfo>,
+{
+	global::difficulty_data_to_vector(cursor)
+}
+
+/// Recomputes the next difficulty from a difficulty window serialized in
+/// binary, as exported by `dump_difficulty_window` (oldest to latest).
+/// The window must hold exactly DIFFICULTY_ADJUST_WINDOW + 1 entries.
+pub fn next_difficulty_from_bytes(height: u64, data: &[u8]) -> Result<HeaderInfo, ser::Error> {
+	let window: Vec<HeaderInfo> = ser::deserialize(&mut &data[..])?;
+	if window.len() != DIFFICULTY_ADJUST_WINDOW as usize + 1 {
+		return Err(ser::Error::CountError);
+	}
+	Ok(next_difficulty(height, window.into_iter().rev()))
+}
+
+/// Estimates the network hashrate (graphs per second) of the primary PoW over
+/// the provided window, ordered from oldest to latest as for `next_difficulty`.
+/// The first header only bounds the window timespan. Each block contributes
+/// proofsize * difficulty / graph_weight graphs, as for the stratum stats.
+/// Returns 0.0 if the window timespan isn't positive.
+pub fn estimate_network_hashrate(height: u64, diff_data: &[HeaderInfo]) -> f64 {
+	estimate_network_hashrate_split(height, diff_data).0
+}
+
+/// Same as `estimate_network_hashrate` but returns the primary and the
+/// secondary PoW contributions separately.
+pub fn estimate_network_hashrate_split(height: u64, diff_data: &[HeaderInfo]) -> (f64, f64) {
+	if diff_data.len() < 2 {
+		return (0.0, 0.0);
+	}
+	let first_ts = diff_data[0].timestamp;
+	let last_ts = diff_data[diff_data.len() - 1].timestamp;
+	if last_ts <= first_ts {
+		return (0.0, 0.0);
+	}
+	let timespan = (last_ts - first_ts) as f64;
+
+	let mut primary_sum: u64 = 0;
+	let mut secondary_sum: u64 = 0;
+	for dd in diff_data.iter().skip(1) {
+		if dd.is_secondary {
+			secondary_sum = secondary_sum.saturating_add(dd.difficulty.to_num());
+		} else {
+			primary_sum = primary_sum.saturating_add(dd.difficulty.to_num());
+		}
+	}
+
+	let proof_size = global::proofsize() as f64;
+	let primary_weight = graph_weight(height, global::min_edge_bits()) as f64;
+	let secondary_weight = graph_weight(height, SECOND_POW_EDGE_BITS) as f64;
+	(
+		proof_size * primary_sum as f64 / primary_weight / timespan,
+		proof_size * secondary_sum as f64 / secondary_weight / timespan,
+	)
 }
 
 /// Count, in units of 1/100 (a percent), the number of "secondary" (AR) blocks in the provided window of blocks.
@@ -372,6 +647,139 @@ const MWC_BLOCKS_PER_GROUP_FLOO: u64 = 2_100_000; // 4 years
 /// MWC Block reward for the first group
 pub const MWC_FIRST_GROUP_REWARD: u64 = 2_380_952_380;
 const MWC_GROUPS_NUM: u64 = 32;
+
+/// MWC emission schedule. Block reward is constant within a group of blocks
+/// and halves from one group to the next, until all groups are exhausted.
+/// Mainnet and floonet use the schedule returned by `emission_schedule()`,
+/// custom networks (and tests) can build a compressed one.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct EmissionSchedule {
+	/// Number of blocks in every group (halving interval)
+	pub blocks_per_group: u64,
+	/// Block reward for the blocks of the first group
+	pub first_group_reward: u64,
+	/// Number of groups with a non zero reward
+	pub groups_num: u64,
+	/// Reward of the genesis block
+	pub genesis_reward: u64,
+}
+
+impl EmissionSchedule {
+	/// Default emission schedule for the given chain type
+	pub fn for_chain_type(chain_type: &global::ChainTypes) -> EmissionSchedule {
+		let blocks_per_group = match *chain_type {
+			global::ChainTypes::Floonet => MWC_BLOCKS_PER_GROUP_FLOO,
+			_ => MWC_BLOCKS_PER_GROUP,
+		};
+		EmissionSchedule {
+			blocks_per_group,
+			first_group_reward: MWC_FIRST_GROUP_REWARD,
+			groups_num: MWC_GROUPS_NUM,
+			genesis_reward: GENESIS_BLOCK_REWARD,
+		}
+	}
+
+	/// Block reward at the given height. See `calc_mwc_block_reward`.
+	pub fn reward(&self, height: u64) -> u64 {
+		if height == 0 {
+			// Genesis block
+			return self.genesis_reward;
+		}
+
+		// Excluding the genesis block from any group
+		let group_num = (height - 1) / self.blocks_per_group;
+
+		if group_num >= self.groups_num || group_num >= 64 {
+			0 // far far future, no rewards, sorry
+		} else {
+			self.first_group_reward >> group_num
+		}
+	}
+
+	/// Whether the block at the given height is the first of a new group, its
+	/// reward being half the reward of the previous block (or zero past the
+	/// last group). See `is_halving_block`.
+	pub fn is_halving_block(&self, height: u64) -> bool {
+		height > 1
+			&& (height - 1) % self.blocks_per_group == 0
+			&& (height - 1) / self.blocks_per_group <= self.groups_num
+	}
+
+	/// Start height and block reward of every group, followed by the start
+	/// height of the terminal zero reward step. See `emission_steps`.
+	pub fn steps(&self) -> impl Iterator<Item = (u64, u64)> {
+		let schedule = *self;
+		(0..=self.groups_num).map(move |group_num| {
+			let start_height = group_num * schedule.blocks_per_group + 1;
+			(start_height, schedule.reward(start_height))
+		})
+	}
+
+	/// Block reward of every group along with the amount (in nanocoins) lost
+	/// over the whole group by the integer halving, compared to an exact
+	/// halving of the first group reward. See `audit_reward_rounding`.
+	pub fn rounding_audit(&self) -> Vec<(u64, u64, u64)> {
+		let group_total = self.blocks_per_group * self.first_group_reward;
+		(0..self.groups_num.min(64))
+			.map(|group_num| {
+				let reward = self.first_group_reward >> group_num;
+				let loss = (group_total >> group_num) - self.blocks_per_group * reward;
+				(group_num, reward, loss)
+			})
+			.collect()
+	}
+
+	/// Factor dividing the first group reward to get the block reward at the
+	/// given height, i.e. the "halving level". See `reward_divisor`.
+	pub fn reward_divisor(&self, height: u64) -> u64 {
+		if height == 0 {
+			return 1;
+		}
+
+		let group_num = (height - 1) / self.blocks_per_group;
+
+		if group_num >= self.groups_num || group_num >= 64 {
+			<u64>::max_value()
+		} else {
+			1 << group_num
+		}
+	}
+
+	/// Total number of rewarded coins in all blocks including this one.
+	/// See `calc_mwc_block_overage`.
+	pub fn overage(&self, height: u64, genesis_had_reward: bool) -> u64 {
+		// including this one happens implicitly.
+		// Because "this block is included", but 0 block (genesis) block is excluded, we will keep height as it is
+		let mut block_count = height;
+		let mut reward_per_block = self.first_group_reward;
+		let mut overage: u64 = self.genesis_reward; // genesis block reward
+
+		for _x in 0..self.groups_num {
+			overage += min(block_count, self.blocks_per_group) * reward_per_block;
+			reward_per_block /= 2;
+
+			if block_count < self.blocks_per_group {
+				break;
+			}
+
+			block_count -= self.blocks_per_group;
+		}
+
+		if !genesis_had_reward {
+			// Deducting the first block reward if it is 0. This case is used into the tests.
+			overage -= self.genesis_reward;
+		}
+
+		overage
+	}
+}
+
+/// Emission schedule for the currently configured chain type
+pub fn emission_schedule() -> EmissionSchedule {
+	let param_ref = global::CHAIN_TYPE.read();
+	EmissionSchedule::for_chain_type(&*param_ref)
+}
+
 /// Calculate MWC block reward. The scedure is similar to bitcoints.
 /// 1st 2.1 million blocks - 2.38095238 MWC
 /// 2nd 2.1 million blocks - 1.19047619 MWC
@@ -383,58 +791,47 @@ const MWC_GROUPS_NUM: u64 = 32;
 /// 32nd 2.1 million blocks - 0.000000001 MWC
 //All blocks after that - 0 MWC (miner fees only)
 pub fn calc_mwc_block_reward(height: u64) -> u64 {
-	if height == 0 {
-		// Genesis block
-		return GENESIS_BLOCK_REWARD;
-	}
+	emission_schedule().reward(height)
+}
 
-	// Excluding the genesis block from any group
-	let group_num = if global::is_floonet() {
-		(height - 1) / MWC_BLOCKS_PER_GROUP_FLOO
-	} else {
-		(height - 1) / MWC_BLOCKS_PER_GROUP
-	};
+/// MWC whether the block at the given height starts a new emission group,
+/// i.e. whether its reward is halved compared to the previous block.
+pub fn is_halving_block(height: u64) -> bool {
+	emission_schedule().is_halving_block(height)
+}
 
-	if group_num >= MWC_GROUPS_NUM {
-		0 // far far future, no rewards, sorry
-	} else {
-		let start_reward = MWC_FIRST_GROUP_REWARD;
-		let group_div = 1 << group_num;
-		start_reward / group_div
-	}
+/// MWC emission steps as (start height, block reward), one for each group
+/// followed by the terminal step where the reward drops to zero. The genesis
+/// block isn't part of any group.
+pub fn emission_steps() -> impl Iterator<Item = (u64, u64)> {
+	emission_schedule().steps()
+}
+
+/// MWC rounding audit of the emission schedule, as (group number, block
+/// reward, rounding loss of the group). The block reward of a group is the
+/// first group reward divided by a power of two, truncated. The rounding loss
+/// is how much the group emits less than with an exact division.
+pub fn audit_reward_rounding() -> Vec<(u64, u64, u64)> {
+	emission_schedule().rounding_audit()
 }
 
 /// MWC  calculate the total number of rewarded coins in all blocks including this one
 pub fn calc_mwc_block_overage(height: u64, genesis_had_reward: bool) -> u64 {
-	let blocks_per_group = if global::is_floonet() {
-		MWC_BLOCKS_PER_GROUP_FLOO
-	} else {
-		MWC_BLOCKS_PER_GROUP
-	};
-
-	// including this one happens implicitly.
-	// Because "this block is included", but 0 block (genesis) block is excluded, we will keep height as it is
-	let mut block_count = height;
-	let mut reward_per_block = MWC_FIRST_GROUP_REWARD;
-	let mut overage: u64 = GENESIS_BLOCK_REWARD; // genesis block reward
-
-	for _x in 0..MWC_GROUPS_NUM {
-		overage += min(block_count, blocks_per_group) * reward_per_block;
-		reward_per_block /= 2;
-
-		if block_count < blocks_per_group {
-			break;
-		}
-
-		block_count -= blocks_per_group;
-	}
+	emission_schedule().overage(height, genesis_had_reward)
+}
 
-	if !genesis_had_reward {
-		// Deducting the first block reward if it is 0. This case is used into the tests.
-		overage -= GENESIS_BLOCK_REWARD;
-	}
+/// MWC reward divisor at the given height, the block reward being the first
+/// group reward divided by it (1, 2, 4, ...). u64::MAX past the last group,
+/// when there is no reward anymore. The genesis block is not part of any group
+/// and has a divisor of 1.
+pub fn reward_divisor(height: u64) -> u64 {
+	emission_schedule().reward_divisor(height)
+}
 
-	overage
+/// Whether the block subsidy has run out at the given height, past the last
+/// emission group only the fees remain to reward miners.
+pub fn is_supply_capped(height: u64) -> bool {
+	calc_mwc_block_reward(height) == 0
 }
 
 #[cfg(test)]
@@ -597,4 +994,88 @@ mod test {
 		// Expected 20M in total. The coin base is exactly 20M
 		assert_eq!(total_blocks_reward, 20_000_000 * GRIN_BASE);
 	}
+	#[test]
+	fn test_emission_schedule() {
+		// Default schedules must match the hardcoded MWC curve
+		let main = EmissionSchedule::for_chain_type(&global::ChainTypes::Mainnet);
+		let floo = EmissionSchedule::for_chain_type(&global::ChainTypes::Floonet);
+		for height in &[
+			0,
+			1,
+			2_100_000,
+			2_100_000 + 1,
+			2_100_000 * 5 + 200,
+			2_100_000 * 32 + 200,
+		] {
+			assert_eq!(main.reward(*height), calc_mwc_block_reward(*height));
+			assert_eq!(floo.reward(*height), calc_mwc_block_reward(*height));
+			assert_eq!(
+				main.overage(*height, true),
+				calc_mwc_block_overage(*height, true)
+			);
+			assert_eq!(
+				floo.overage(*height, false),
+				calc_mwc_block_overage(*height, false)
+			);
+		}
+
+		// Compressed schedule, halving every 10 blocks, 3 groups only
+		let schedule = EmissionSchedule {
+			blocks_per_group: 10,
+			first_group_reward: 1_000,
+			groups_num: 3,
+			genesis_reward: 50,
+		};
+		assert_eq!(schedule.reward(0), 50);
+		assert_eq!(schedule.reward(1), 1_000);
+		assert_eq!(schedule.reward(10), 1_000);
+		assert_eq!(schedule.reward(11), 500);
+		assert_eq!(schedule.reward(21), 250);
+		assert_eq!(schedule.reward(30), 250);
+		assert_eq!(schedule.reward(31), 0);
+
+		assert_eq!(schedule.overage(0, true), 50);
+		assert_eq!(schedule.overage(0, false), 0);
+		assert_eq!(schedule.overage(11, true), 50 + 10 * 1_000 + 500);
+		assert_eq!(schedule.overage(1_000, false), 10 * (1_000 + 500 + 250));
+	}
+
+	#[test]
+	fn test_reward_divisor() {
+		global::set_mining_mode(global::ChainTypes::Mainnet);
+
+		assert_eq!(reward_divisor(0), 1);
+		assert_eq!(reward_divisor(1), 1);
+		assert_eq!(reward_divisor(MWC_BLOCKS_PER_GROUP), 1);
+		assert_eq!(reward_divisor(MWC_BLOCKS_PER_GROUP + 1), 2);
+		assert_eq!(reward_divisor(MWC_BLOCKS_PER_GROUP * 2), 2);
+		assert_eq!(reward_divisor(MWC_BLOCKS_PER_GROUP * 2 + 1), 4);
+		assert_eq!(reward_divisor(MWC_BLOCKS_PER_GROUP * 31 + 1), 1 << 31);
+		assert_eq!(reward_divisor(MWC_BLOCKS_PER_GROUP * 32), 1 << 31);
+		assert_eq!(
+			reward_divisor(MWC_BLOCKS_PER_GROUP * 32 + 1),
+			<u64>::max_value()
+		);
+
+		for group in 0..MWC_GROUPS_NUM {
+			let height = MWC_BLOCKS_PER_GROUP * group + 1;
+			assert_eq!(
+				calc_mwc_block_reward(height),
+				MWC_FIRST_GROUP_REWARD / reward_divisor(height)
+			);
+		}
+	}
+
+	#[test]
+	fn test_is_supply_capped() {
+		global::set_mining_mode(global::ChainTypes::Mainnet);
+
+		let last_rewarded = MWC_BLOCKS_PER_GROUP * MWC_GROUPS_NUM;
+		assert!(!is_supply_capped(0));
+		assert!(!is_supply_capped(1));
+		assert!(!is_supply_capped(MWC_BLOCKS_PER_GROUP + 1));
+		assert!(!is_supply_capped(last_rewarded));
+		assert!(is_supply_capped(last_rewarded + 1));
+		assert!(is_supply_capped(2 * last_rewarded));
+	}
 }
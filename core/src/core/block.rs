@@ -46,12 +46,20 @@ pub enum Error {
 	InvalidTotalKernelSum,
 	/// Same as above but for the coinbase part of a block, including reward
 	CoinbaseSumMismatch,
+	/// A block must have exactly one coinbase kernel
+	InvalidCoinbaseKernelCount {
+		/// Number of coinbase kernels found in the block
+		found: usize,
+	},
 	/// Restrict block total weight.
 	TooHeavy,
 	/// Block weight (based on inputs|outputs|kernels) exceeded.
 	WeightExceeded,
 	/// Kernel not valid due to lock_height exceeding block header height
 	KernelLockHeight(u64),
+	/// Coinbase kernels are never height locked, coinbase maturity is enforced
+	/// on the outputs instead
+	InvalidCoinbaseLockHeight(u64),
 	/// Underlying tx related error
 	Transaction(transaction::Error),
 	/// Underlying Secp256k1 error (signature validation or invalid public key
@@ -161,6 +169,13 @@ impl FixedLength for HeaderEntry {
 	const LEN: usize = Hash::LEN + 8 + Difficulty::LEN + 4 + 1;
 }
 
+impl HeaderEntry {
+	/// The timestamp of the underlying block header, in seconds.
+	pub fn timestamp(&self) -> u64 {
+		self.timestamp
+	}
+}
+
 impl Hashed for HeaderEntry {
 	/// The hash of the underlying block.
 	fn hash(&self) -> Hash {
@@ -552,7 +567,9 @@ impl Block {
 		// Finally return the full block.
 		// Note: we have not actually validated the block here,
 		// caller must validate the block.
-		Block { header, body }.cut_through()
+		let mut block = Block { header, body };
+		block.cut_through()?;
+		Ok(block)
 	}
 
 	/// Build a new empty block from a specified header
@@ -599,7 +616,7 @@ impl Block {
 		// Now build the block with all the above information.
 		// Note: We have not validated the block here.
 		// Caller must validate the block as necessary.
-		Block {
+		let mut block = Block {
 			header: BlockHeader {
 				version,
 				height,
@@ -613,8 +630,9 @@ impl Block {
 				..Default::default()
 			},
 			body: agg_tx.into(),
-		}
-		.cut_through()
+		};
+		block.cut_through()?;
+		Ok(block)
 	}
 
 	/// Consumes this block and returns a new block with the coinbase output
@@ -655,6 +673,16 @@ impl Block {
 		&mut self.body.kernels
 	}
 
+	/// Fee paid by each kernel of the block, by kernel excess. Adds up to
+	/// total_fees, the coinbase kernel paying none.
+	pub fn fees_by_kernel(&self) -> Vec<(Commitment, u64)> {
+		self.body
+			.kernels
+			.iter()
+			.map(|k| (k.excess(), k.fee()))
+			.collect()
+	}
+
 	/// Sum of all fees (inputs less outputs) in the block
 	pub fn total_fees(&self) -> u64 {
 		self.body
@@ -664,10 +692,11 @@ impl Block {
 	}
 
 	/// Matches any output with a potential spending input, eliminating them
-	/// from the block. Provides a simple way to cut-through the block. The
-	/// elimination is stable with respect to the order of inputs and outputs.
-	/// Method consumes the block.
-	pub fn cut_through(self) -> Result<Block, Error> {
+	/// from the block. Provides a simple way to cut-through the block, for
+	/// example after merging the bodies of several blocks. The elimination is
+	/// stable with respect to the order of inputs and outputs, kernels are
+	/// left untouched.
+	pub fn cut_through(&mut self) -> Result<(), Error> {
 		let mut inputs = self.inputs().clone();
 		let mut outputs = self.outputs().clone();
 		transaction::cut_through(&mut inputs, &mut outputs)?;
@@ -675,12 +704,8 @@ impl Block {
 		let kernels = self.kernels().clone();
 
 		// Initialize tx body and sort everything.
-		let body = TransactionBody::init(inputs, outputs, kernels, false)?;
-
-		Ok(Block {
-			header: self.header,
-			body,
-		})
+		self.body = TransactionBody::init(inputs, outputs, kernels, false)?;
+		Ok(())
 	}
 
 	/// "Lightweight" validation that we can perform quickly during read/deserialization.
@@ -698,6 +723,24 @@ impl Block {
 		Ok(())
 	}
 
+	/// Verifies the rangeproofs of the block outputs not already in the
+	/// verifier cache. Returns the number of rangeproofs actually verified.
+	pub fn verify_rangeproofs(
+		&self,
+		verifier: Arc<RwLock<dyn VerifierCache>>,
+	) -> Result<usize, Error> {
+		Ok(self.body.verify_rangeproofs(verifier)?)
+	}
+
+	/// Verifies the signatures of the block kernels not already in the
+	/// verifier cache. Returns the number of signatures actually verified.
+	pub fn verify_kernel_signatures(
+		&self,
+		verifier: Arc<RwLock<dyn VerifierCache>>,
+	) -> Result<usize, Error> {
+		Ok(self.body.verify_kernel_signatures(verifier)?)
+	}
+
 	fn block_kernel_offset(
 		&self,
 		prev_kernel_offset: BlindingFactor,
@@ -722,13 +765,35 @@ impl Block {
 		&self,
 		prev_kernel_offset: &BlindingFactor,
 		verifier: Arc<RwLock<dyn VerifierCache>>,
+	) -> Result<Commitment, Error> {
+		self.validate_partial(prev_kernel_offset, verifier, true, true)
+	}
+
+	/// Same as validate, except for the output rangeproofs and the kernel
+	/// signatures that are only verified when asked to. A block validated
+	/// without them can still hide an inflating output or spend outputs it
+	/// doesn't own, they have to be checked (or trusted) otherwise.
+	pub fn validate_partial(
+		&self,
+		prev_kernel_offset: &BlindingFactor,
+		verifier: Arc<RwLock<dyn VerifierCache>>,
+		rangeproofs: bool,
+		kernel_signatures: bool,
 	) -> Result<Commitment, Error> {
 		if global::is_mainnet() && self.header.pow.edge_bits() < 31 {
 			return Err(Error::Other("C29 Disabled".to_string()));
 		}
-		self.body.validate(Weighting::AsBlock, verifier)?;
+		self.body.validate_read(Weighting::AsBlock)?;
+		self.verify_coinbase_lock_height()?;
+		if rangeproofs {
+			self.body.verify_rangeproofs(verifier.clone())?;
+		}
+		if kernel_signatures {
+			self.body.verify_kernel_signatures(verifier)?;
+		}
 
 		self.verify_kernel_lock_heights()?;
+		self.verify_coinbase_kernel_count()?;
 		self.verify_coinbase()?;
 
 		// take the kernel offset for this block (block offset minus previous) and
@@ -759,22 +824,46 @@ impl Block {
 			.filter(|kernel| kernel.is_coinbase())
 			.collect::<Vec<&TxKernel>>();
 
-		{
+		let over_commit = {
 			let secp = static_secp_instance();
 			let secp = secp.lock();
-			let over_commit = secp.commit_value(reward(self.total_fees(), self.header.height))?;
+			secp.commit_value(reward(self.total_fees(), self.header.height))?
+		};
 
-			let out_adjust_sum =
-				secp.commit_sum(map_vec!(cb_outs, |x| x.commitment()), vec![over_commit])?;
+		let out_adjust_sum =
+			transaction::sum_commitments(&map_vec!(cb_outs, |x| x.commitment()), &[over_commit])?;
 
-			let kerns_sum = secp.commit_sum(cb_kerns.iter().map(|x| x.excess).collect(), vec![])?;
+		let kerns_sum = transaction::sum_commitments(&map_vec!(cb_kerns, |x| x.excess), &[])?;
 
-			// Verify the kernel sum equals the output sum accounting for block fees.
-			if kerns_sum != out_adjust_sum {
-				return Err(Error::CoinbaseSumMismatch);
-			}
+		// Verify the kernel sum equals the output sum accounting for block fees.
+		if kerns_sum != out_adjust_sum {
+			return Err(Error::CoinbaseSumMismatch);
+		}
+
+		Ok(())
+	}
+
+	/// Check the block has exactly one coinbase kernel.
+	pub fn verify_coinbase_kernel_count(&self) -> Result<(), Error> {
+		let found = self
+			.body
+			.kernels
+			.iter()
+			.filter(|kernel| kernel.is_coinbase())
+			.count();
+		if found != 1 {
+			return Err(Error::InvalidCoinbaseKernelCount { found });
 		}
+		Ok(())
+	}
 
+	/// Check every coinbase kernel has a zero lock_height.
+	pub fn verify_coinbase_lock_height(&self) -> Result<(), Error> {
+		for k in self.body.kernels.iter().filter(|k| k.is_coinbase()) {
+			if k.lock_height != 0 {
+				return Err(Error::InvalidCoinbaseLockHeight(k.lock_height));
+			}
+		}
 		Ok(())
 	}
 
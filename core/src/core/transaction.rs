@@ -259,6 +259,11 @@ impl TxKernel {
 		self.excess
 	}
 
+	/// Fee paid by this tx_kernel, always 0 for a coinbase kernel.
+	pub fn fee(&self) -> u64 {
+		self.fee
+	}
+
 	/// The msg signed as part of the tx kernel.
 	/// Consists of the fee and the lock_height.
 	pub fn msg_to_sign(&self) -> Result<secp::Message, Error> {
@@ -270,17 +275,23 @@ impl TxKernel {
 	/// as a public key and checking the signature verifies with the fee as
 	/// message.
 	pub fn verify(&self) -> Result<(), Error> {
+		let secp = static_secp_instance();
+		let secp = secp.lock();
+		self.verify_with(&secp)
+	}
+
+	/// Same as verify but against the provided secp context, allowing callers
+	/// verifying on several threads to avoid contending on the static instance.
+	pub fn verify_with(&self, secp: &secp::Secp256k1) -> Result<(), Error> {
 		if self.is_coinbase() && self.fee != 0 || !self.is_height_locked() && self.lock_height != 0
 		{
 			return Err(Error::InvalidKernelFeatures);
 		}
-		let secp = static_secp_instance();
-		let secp = secp.lock();
 		let sig = &self.excess_sig;
 		// Verify aggsig directly in libsecp
-		let pubkey = &self.excess.to_pubkey(&secp)?;
+		let pubkey = &self.excess.to_pubkey(secp)?;
 		if !secp::aggsig::verify_single(
-			&secp,
+			secp,
 			&sig,
 			&self.msg_to_sign()?,
 			None,
@@ -613,7 +624,7 @@ impl TransactionBody {
 	fn verify_weight(&self, weighting: Weighting) -> Result<(), Error> {
 		// A coinbase reward is a single output and a single kernel (for now).
 		// We need to account for this when verifying max tx weights.
-		let coinbase_weight = consensus::BLOCK_OUTPUT_WEIGHT + consensus::BLOCK_KERNEL_WEIGHT;
+		let coinbase_weight = consensus::coinbase_block_weight();
 
 		// If "tx" body then remember to reduce the max_block_weight by the weight of a kernel.
 		// If "limited tx" then compare against the provided max_weight.
@@ -716,7 +727,18 @@ impl TransactionBody {
 		verifier: Arc<RwLock<dyn VerifierCache>>,
 	) -> Result<(), Error> {
 		self.validate_read(weighting)?;
+		self.verify_rangeproofs(verifier.clone())?;
+		self.verify_kernel_signatures(verifier)?;
+		Ok(())
+	}
 
+	/// Batch verifies the rangeproofs of the outputs not already in the
+	/// verifier cache, caching the successful results.
+	/// Returns the number of rangeproofs actually verified.
+	pub fn verify_rangeproofs(
+		&self,
+		verifier: Arc<RwLock<dyn VerifierCache>>,
+	) -> Result<usize, Error> {
 		// Find all the outputs that have not had their rangeproofs verified.
 		let outputs = {
 			let mut verifier = verifier.write();
@@ -734,6 +756,19 @@ impl TransactionBody {
 			Output::batch_verify_proofs(&commits, &proofs)?;
 		}
 
+		// Cache the successful verification results for the new outputs.
+		let count = outputs.len();
+		verifier.write().add_rangeproof_verified(outputs);
+		Ok(count)
+	}
+
+	/// Verifies the signatures of the kernels not already in the verifier
+	/// cache, caching the successful results.
+	/// Returns the number of kernel signatures actually verified.
+	pub fn verify_kernel_signatures(
+		&self,
+		verifier: Arc<RwLock<dyn VerifierCache>>,
+	) -> Result<usize, Error> {
 		// Find all the kernels that have not yet been verified.
 		let kernels = {
 			let mut verifier = verifier.write();
@@ -747,13 +782,10 @@ impl TransactionBody {
 			x.verify()?;
 		}
 
-		// Cache the successful verification results for the new outputs and kernels.
-		{
-			let mut verifier = verifier.write();
-			verifier.add_rangeproof_verified(outputs);
-			verifier.add_kernel_sig_verified(kernels);
-		}
-		Ok(())
+		// Cache the successful verification results for the new kernels.
+		let count = kernels.len();
+		verifier.write().add_kernel_sig_verified(kernels);
+		Ok(count)
 	}
 }
 
@@ -967,6 +999,12 @@ impl Transaction {
 		self.fee() * 1_000 / self.tx_weight() as u64
 	}
 
+	/// Fee/weight ratio using block weighing, i.e. the fee a miner earns for the
+	/// block capacity taken by this tx. Same precision multiplier as fee_to_weight.
+	pub fn fee_to_weight_as_block(&self) -> u64 {
+		self.fee() * 1_000 / max(self.tx_weight_as_block(), 1) as u64
+	}
+
 	/// Calculate transaction weight
 	pub fn tx_weight(&self) -> usize {
 		self.body.body_weight()
@@ -1020,6 +1058,14 @@ pub fn cut_through(inputs: &mut Vec<Input>, outputs: &mut Vec<Output>) -> Result
 	Ok(())
 }
 
+/// Sum the positive commitments minus the negative ones. Thin wrapper over
+/// the secp commit_sum, used by block validation to sum outputs and kernels.
+pub fn sum_commitments(pos: &[Commitment], neg: &[Commitment]) -> Result<Commitment, Error> {
+	let secp = static_secp_instance();
+	let secp = secp.lock();
+	Ok(secp.commit_sum(pos.to_vec(), neg.to_vec())?)
+}
+
 /// Aggregate a vec of txs into a multi-kernel tx with cut_through.
 pub fn aggregate(mut txs: Vec<Transaction>) -> Result<Transaction, Error> {
 	// convenience short-circuiting
@@ -1358,8 +1404,17 @@ impl Output {
 		proofs: &Vec<RangeProof>,
 	) -> Result<(), Error> {
 		let secp = static_secp_instance();
-		secp.lock()
-			.verify_bullet_proof_multi(commits.clone(), proofs.clone(), None)?;
+		let secp = secp.lock();
+		Output::batch_verify_proofs_with(&secp, commits, proofs)
+	}
+
+	/// Same as batch_verify_proofs but against the provided secp context.
+	pub fn batch_verify_proofs_with(
+		secp: &secp::Secp256k1,
+		commits: &Vec<Commitment>,
+		proofs: &Vec<RangeProof>,
+	) -> Result<(), Error> {
+		secp.verify_bullet_proof_multi(commits.clone(), proofs.clone(), None)?;
 		Ok(())
 	}
 }
@@ -1426,6 +1481,20 @@ impl OutputIdentifier {
 			util::to_hex(self.commit.0.to_vec()),
 		)
 	}
+
+	/// Binary encoding of this output_identifier, the features byte followed
+	/// by the commitment. Must remain stable as it backs the output indexes.
+	pub fn to_bytes(&self) -> Vec<u8> {
+		ser::ser_vec(self).expect("serialization failed")
+	}
+
+	/// Reads an output_identifier back from its binary encoding (see `to_bytes`).
+	pub fn from_bytes(bytes: &[u8]) -> Result<OutputIdentifier, ser::Error> {
+		if bytes.len() != OutputIdentifier::LEN {
+			return Err(ser::Error::CountError);
+		}
+		ser::deserialize(&mut &bytes[..])
+	}
 }
 
 impl FixedLength for OutputIdentifier {
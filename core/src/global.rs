@@ -17,14 +17,15 @@
 //! should be used sparingly.
 
 use crate::consensus::{
-	graph_weight, valid_header_version, HeaderInfo, BASE_EDGE_BITS, BLOCK_TIME_SEC,
+	self, graph_weight, valid_header_version, HeaderInfo, BASE_EDGE_BITS, BLOCK_TIME_SEC,
 	COINBASE_MATURITY, CUT_THROUGH_HORIZON, DAY_HEIGHT, DEFAULT_MIN_EDGE_BITS,
 	DIFFICULTY_ADJUST_WINDOW, INITIAL_DIFFICULTY, MAX_BLOCK_WEIGHT, PROOFSIZE,
 	SECOND_POW_EDGE_BITS, STATE_SYNC_THRESHOLD,
 };
 use crate::core::block::HeaderVersion;
+use crate::core::hash::Hash;
 use crate::pow::{
-	self, new_cuckaroo_ctx, new_cuckarood_ctx, new_cuckatoo_ctx, EdgeType, PoWContext,
+	self, new_cuckaroo_ctx, new_cuckarood_ctx, new_cuckatoo_ctx, Difficulty, EdgeType, PoWContext,
 };
 /// An enum collecting sets of parameters used throughout the
 /// code wherever mining is needed. This should allow for
@@ -68,6 +69,9 @@ pub const TESTING_INITIAL_DIFFICULTY: u64 = 1;
 /// Testing max_block_weight (artifically low, just enough to support a few txs).
 pub const TESTING_MAX_BLOCK_WEIGHT: usize = 150;
 
+/// Regtest block interval in seconds
+pub const REGTEST_BLOCK_TIME_SEC: u64 = 1;
+
 /// If a peer's last updated difficulty is 2 hours ago and its difficulty's lower than ours,
 /// we're sure this peer is a stuck node, and we will kick out such kind of stuck peers.
 pub const STUCK_PEER_KICK_TIME: i64 = 2 * 3600 * 1000;
@@ -93,6 +97,8 @@ pub enum ChainTypes {
 	AutomatedTesting,
 	/// For User testing
 	UserTesting,
+	/// Local regression testing network with fast blocks
+	Regtest,
 	/// Protocol testing network
 	Floonet,
 	/// Main production network
@@ -105,6 +111,7 @@ impl ChainTypes {
 		match *self {
 			ChainTypes::AutomatedTesting => "auto".to_owned(),
 			ChainTypes::UserTesting => "user".to_owned(),
+			ChainTypes::Regtest => "reg".to_owned(),
 			ChainTypes::Floonet => "floo".to_owned(),
 			ChainTypes::Mainnet => "main".to_owned(),
 		}
@@ -136,6 +143,10 @@ lazy_static! {
 	/// PoW context type to instantiate
 	pub static ref POW_CONTEXT_TYPE: RwLock<PoWContextTypes> =
 			RwLock::new(PoWContextTypes::Cuckoo);
+
+	/// Sign with deterministic nonces, for reproducible test fixtures
+	pub static ref DETERMINISTIC_SIGNING: RwLock<bool> =
+			RwLock::new(false);
 }
 
 /// Set the mining mode
@@ -182,6 +193,7 @@ pub fn min_edge_bits() -> u8 {
 	match *param_ref {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_MIN_EDGE_BITS,
 		ChainTypes::UserTesting => USER_TESTING_MIN_EDGE_BITS,
+		ChainTypes::Regtest => AUTOMATED_TESTING_MIN_EDGE_BITS,
 		_ => DEFAULT_MIN_EDGE_BITS,
 	}
 }
@@ -194,6 +206,7 @@ pub fn base_edge_bits() -> u8 {
 	match *param_ref {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_MIN_EDGE_BITS,
 		ChainTypes::UserTesting => USER_TESTING_MIN_EDGE_BITS,
+		ChainTypes::Regtest => AUTOMATED_TESTING_MIN_EDGE_BITS,
 		_ => BASE_EDGE_BITS,
 	}
 }
@@ -204,6 +217,7 @@ pub fn proofsize() -> usize {
 	match *param_ref {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_PROOF_SIZE,
 		ChainTypes::UserTesting => USER_TESTING_PROOF_SIZE,
+		ChainTypes::Regtest => AUTOMATED_TESTING_PROOF_SIZE,
 		_ => PROOFSIZE,
 	}
 }
@@ -214,16 +228,30 @@ pub fn coinbase_maturity() -> u64 {
 	match *param_ref {
 		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_COINBASE_MATURITY,
 		ChainTypes::UserTesting => USER_TESTING_COINBASE_MATURITY,
+		ChainTypes::Regtest => AUTOMATED_TESTING_COINBASE_MATURITY,
 		_ => COINBASE_MATURITY,
 	}
 }
 
+/// Coinbase maturity for coinbases to be spent at the given height, see
+/// `consensus::coinbase_maturity`. Testing chains use their own.
+pub fn coinbase_maturity_at(height: u64) -> u64 {
+	let param_ref = CHAIN_TYPE.read();
+	match *param_ref {
+		ChainTypes::AutomatedTesting => AUTOMATED_TESTING_COINBASE_MATURITY,
+		ChainTypes::UserTesting => USER_TESTING_COINBASE_MATURITY,
+		ChainTypes::Regtest => AUTOMATED_TESTING_COINBASE_MATURITY,
+		_ => consensus::coinbase_maturity(height),
+	}
+}
+
 /// Initial mining difficulty
 pub fn initial_block_difficulty() -> u64 {
 	let param_ref = CHAIN_TYPE.read();
 	match *param_ref {
 		ChainTypes::AutomatedTesting => TESTING_INITIAL_DIFFICULTY,
 		ChainTypes::UserTesting => TESTING_INITIAL_DIFFICULTY,
+		ChainTypes::Regtest => TESTING_INITIAL_DIFFICULTY,
 		ChainTypes::Floonet => INITIAL_DIFFICULTY,
 		ChainTypes::Mainnet => INITIAL_DIFFICULTY,
 	}
@@ -234,6 +262,7 @@ pub fn initial_graph_weight() -> u32 {
 	match *param_ref {
 		ChainTypes::AutomatedTesting => TESTING_INITIAL_GRAPH_WEIGHT,
 		ChainTypes::UserTesting => TESTING_INITIAL_GRAPH_WEIGHT,
+		ChainTypes::Regtest => TESTING_INITIAL_GRAPH_WEIGHT,
 		ChainTypes::Floonet => graph_weight(0, SECOND_POW_EDGE_BITS) as u32,
 		ChainTypes::Mainnet => graph_weight(0, SECOND_POW_EDGE_BITS) as u32,
 	}
@@ -245,17 +274,29 @@ pub fn max_block_weight() -> usize {
 	match *param_ref {
 		ChainTypes::AutomatedTesting => TESTING_MAX_BLOCK_WEIGHT,
 		ChainTypes::UserTesting => TESTING_MAX_BLOCK_WEIGHT,
+		ChainTypes::Regtest => TESTING_MAX_BLOCK_WEIGHT,
 		ChainTypes::Floonet => MAX_BLOCK_WEIGHT,
 		ChainTypes::Mainnet => MAX_BLOCK_WEIGHT,
 	}
 }
 
+/// Block interval, in seconds, the network will tune its next_target for.
+/// Regtest uses a much shorter interval for fast local testing.
+pub fn block_time_sec() -> u64 {
+	let param_ref = CHAIN_TYPE.read();
+	match *param_ref {
+		ChainTypes::Regtest => REGTEST_BLOCK_TIME_SEC,
+		_ => BLOCK_TIME_SEC,
+	}
+}
+
 /// Horizon at which we can cut-through and do full local pruning
 pub fn cut_through_horizon() -> u32 {
 	let param_ref = CHAIN_TYPE.read();
 	match *param_ref {
 		ChainTypes::AutomatedTesting => TESTING_CUT_THROUGH_HORIZON,
 		ChainTypes::UserTesting => TESTING_CUT_THROUGH_HORIZON,
+		ChainTypes::Regtest => TESTING_CUT_THROUGH_HORIZON,
 		_ => CUT_THROUGH_HORIZON,
 	}
 }
@@ -266,6 +307,7 @@ pub fn state_sync_threshold() -> u32 {
 	match *param_ref {
 		ChainTypes::AutomatedTesting => TESTING_STATE_SYNC_THRESHOLD,
 		ChainTypes::UserTesting => TESTING_STATE_SYNC_THRESHOLD,
+		ChainTypes::Regtest => TESTING_STATE_SYNC_THRESHOLD,
 		_ => STATE_SYNC_THRESHOLD,
 	}
 }
@@ -276,6 +318,19 @@ pub fn is_automated_testing_mode() -> bool {
 	ChainTypes::AutomatedTesting == *param_ref
 }
 
+/// Make the aggsig signatures use nonces derived from the key and the message
+/// instead of random ones, so the same inputs always produce the same
+/// signature. Only honored in automated testing mode.
+pub fn set_deterministic_signing(deterministic: bool) {
+	let mut param_ref = DETERMINISTIC_SIGNING.write();
+	*param_ref = deterministic;
+}
+
+/// Are the aggsig signatures using deterministic nonces?
+pub fn is_deterministic_signing() -> bool {
+	is_automated_testing_mode() && *DETERMINISTIC_SIGNING.read()
+}
+
 /// Are we in user testing mode?
 pub fn is_user_testing_mode() -> bool {
 	let param_ref = CHAIN_TYPE.read();
@@ -315,6 +370,8 @@ pub fn get_genesis_nonce() -> u64 {
 		ChainTypes::AutomatedTesting => 0,
 		// Magic nonce for current genesis block at cuckatoo15
 		ChainTypes::UserTesting => 27944,
+		// won't make a difference
+		ChainTypes::Regtest => 0,
 		// Placeholder, obviously not the right value
 		ChainTypes::Floonet => 0,
 		// Placeholder, obviously not the right value
@@ -322,6 +379,28 @@ pub fn get_genesis_nonce() -> u64 {
 	}
 }
 
+/// Trusted mainnet checkpoints, (height, block hash in hex)
+const MAINNET_CHECKPOINTS: &[(u64, &str)] = &[];
+
+/// Trusted floonet checkpoints, (height, block hash in hex)
+const FLOONET_CHECKPOINTS: &[(u64, &str)] = &[];
+
+/// Trusted checkpoints of the current chain type, as (height, block hash).
+/// A block conflicting with a checkpoint at its height is always rejected.
+/// Testing chains have none.
+pub fn checkpoints() -> Vec<(u64, Hash)> {
+	let param_ref = CHAIN_TYPE.read();
+	let checkpoints = match *param_ref {
+		ChainTypes::Mainnet => MAINNET_CHECKPOINTS,
+		ChainTypes::Floonet => FLOONET_CHECKPOINTS,
+		_ => &[],
+	};
+	checkpoints
+		.iter()
+		.map(|(height, hash)| (*height, Hash::from_hex(hash).expect("invalid checkpoint")))
+		.collect()
+}
+
 /// Short name representing the current chain type ("floo", "main", etc.)
 pub fn chain_shortname() -> String {
 	let param_ref = CHAIN_TYPE.read();
@@ -340,6 +419,14 @@ where
 	let needed_block_count = DIFFICULTY_ADJUST_WINDOW as usize + 1;
 	let mut last_n: Vec<HeaderInfo> = cursor.into_iter().take(needed_block_count).collect();
 
+	// Not even a genesis block, start from a perfectly timed block at the
+	// initial difficulty (padded below).
+	if last_n.is_empty() {
+		let ts = needed_block_count as u64 * block_time_sec();
+		let diff = Difficulty::from_num(initial_block_difficulty());
+		last_n.push(HeaderInfo::from_ts_diff(ts, diff));
+	}
+
 	// Only needed just after blockchain launch... basically ensures there's
 	// always enough data by simulating perfectly timed pre-genesis
 	// blocks at the genesis difficulty as needed.
@@ -348,7 +435,7 @@ where
 		let last_ts_delta = if n > 1 {
 			last_n[0].timestamp - last_n[1].timestamp
 		} else {
-			BLOCK_TIME_SEC
+			block_time_sec()
 		};
 		let last_diff = last_n[0].difficulty;
 
@@ -360,5 +447,6 @@ where
 		}
 	}
 	last_n.reverse();
+	debug_assert_eq!(last_n.len(), needed_block_count);
 	last_n
 }
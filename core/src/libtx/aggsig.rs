@@ -16,6 +16,8 @@
 //! This module interfaces into the underlying
 //! [Rust Aggsig library](https://github.com/mimblewimble/rust-secp256k1-zkp/blob/master/src/aggsig.rs)
 
+use crate::blake2::blake2b::blake2b;
+use crate::global;
 use crate::keychain::{BlindingFactor, Identifier, Keychain};
 use crate::libtx::error::{Error, ErrorKind};
 use crate::util::secp::key::{PublicKey, SecretKey};
@@ -254,7 +256,7 @@ pub fn verify_partial_sig(
 /// let msg = kernel_sig_msg(0, height, KernelFeatures::HeightLocked).unwrap();
 /// let excess = secp.commit_sum(vec![out_commit], vec![over_commit]).unwrap();
 /// let pubkey = excess.to_pubkey(&secp).unwrap();
-/// let sig = aggsig::sign_from_key_id(&secp, &keychain, &msg, value, &key_id, None, Some(&pubkey)).unwrap();
+/// let sig = aggsig::sign_from_key_id(&secp, &keychain, &msg, value, &key_id, switch, None, Some(&pubkey)).unwrap();
 /// ```
 
 pub fn sign_from_key_id<K>(
@@ -263,14 +265,25 @@ pub fn sign_from_key_id<K>(
 	msg: &Message,
 	value: u64,
 	key_id: &Identifier,
+	switch: &SwitchCommitmentType,
 	s_nonce: Option<&SecretKey>,
 	blind_sum: Option<&PublicKey>,
 ) -> Result<Signature, Error>
 where
 	K: Keychain,
 {
-	let skey = k.derive_key(value, key_id, &SwitchCommitmentType::Regular)?; // TODO: proper support for different switch commitment schemes
-	let sig = aggsig::sign_single(secp, &msg, &skey, s_nonce, None, None, blind_sum, None)?;
+	let skey = k.derive_key(value, key_id, switch)?;
+	let det_nonce = nonce_or_deterministic(secp, s_nonce, &skey, msg)?;
+	let sig = aggsig::sign_single(
+		secp,
+		&msg,
+		&skey,
+		det_nonce.as_ref(),
+		None,
+		None,
+		blind_sum,
+		None,
+	)?;
 	Ok(sig)
 }
 
@@ -322,7 +335,7 @@ where
 /// let msg = kernel_sig_msg(0, height, KernelFeatures::HeightLocked).unwrap();
 /// let excess = secp.commit_sum(vec![out_commit], vec![over_commit]).unwrap();
 /// let pubkey = excess.to_pubkey(&secp).unwrap();
-/// let sig = aggsig::sign_from_key_id(&secp, &keychain, &msg, value, &key_id, None, Some(&pubkey)).unwrap();
+/// let sig = aggsig::sign_from_key_id(&secp, &keychain, &msg, value, &key_id, switch, None, Some(&pubkey)).unwrap();
 ///
 /// // Verify the signature from the excess commit
 /// let sig_verifies =
@@ -435,7 +448,17 @@ pub fn sign_single(
 	snonce: Option<&SecretKey>,
 	pubkey_sum: Option<&PublicKey>,
 ) -> Result<Signature, Error> {
-	let sig = aggsig::sign_single(secp, &msg, skey, snonce, None, None, pubkey_sum, None)?;
+	let snonce = nonce_or_deterministic(secp, snonce, skey, msg)?;
+	let sig = aggsig::sign_single(
+		secp,
+		&msg,
+		skey,
+		snonce.as_ref(),
+		None,
+		None,
+		pubkey_sum,
+		None,
+	)?;
 	Ok(sig)
 }
 
@@ -463,6 +486,37 @@ pub fn sign_with_blinding(
 ) -> Result<Signature, Error> {
 	let skey = &blinding.secret_key(&secp)?;
 	//let pubkey_sum = PublicKey::from_secret_key(&secp, &skey)?;
-	let sig = aggsig::sign_single(secp, &msg, skey, None, None, None, pubkey_sum, None)?;
+	let snonce = nonce_or_deterministic(secp, None, skey, msg)?;
+	let sig = aggsig::sign_single(
+		secp,
+		&msg,
+		skey,
+		snonce.as_ref(),
+		None,
+		None,
+		pubkey_sum,
+		None,
+	)?;
 	Ok(sig)
 }
+
+/// The nonce to sign with, the provided one if any. Otherwise, when
+/// deterministic signing is on (see `global::set_deterministic_signing`), a
+/// nonce derived from the secret key and the message. None lets the signature
+/// generate a random nonce.
+fn nonce_or_deterministic(
+	secp: &Secp256k1,
+	snonce: Option<&SecretKey>,
+	skey: &SecretKey,
+	msg: &Message,
+) -> Result<Option<SecretKey>, Error> {
+	if let Some(nonce) = snonce {
+		return Ok(Some(nonce.clone()));
+	}
+	if !global::is_deterministic_signing() {
+		return Ok(None);
+	}
+	let res = blake2b(32, &skey.0, &msg[..]);
+	let nonce = SecretKey::from_slice(secp, res.as_bytes())?;
+	Ok(Some(nonce))
+}
@@ -44,6 +44,9 @@ pub enum ErrorKind {
 	/// Rangeproof error
 	#[fail(display = "Rangeproof Error")]
 	RangeProof(String),
+	/// Invalid coinbase output or kernel
+	#[fail(display = "Coinbase Error")]
+	Coinbase(String),
 }
 
 impl Fail for Error {
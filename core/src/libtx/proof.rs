@@ -43,9 +43,43 @@ where
 	// it is not supported at the wallet level (yet).
 	let secp = k.secp();
 	let commit = k.commit(amount, key_id, switch)?;
-	let skey = k.derive_key(amount, key_id, switch)?;
 	let rewind_nonce = b.rewind_nonce(secp, &commit)?;
 	let private_nonce = b.private_nonce(secp, &commit)?;
+	create_with_nonces(
+		k,
+		b,
+		amount,
+		key_id,
+		switch,
+		rewind_nonce,
+		private_nonce,
+		extra_data,
+	)
+}
+
+/// Create a bulletproof using the provided rewind and private nonces instead
+/// of deriving them from the commitment. Calling it twice with the same key
+/// and nonces produces the same proof. Note the proof can only be rewound by
+/// a builder that derives the same rewind nonce.
+/// The private nonce must never be used for proofs under different keys,
+/// anyone holding two such proofs can recover both blinding factors. Derive
+/// it from the commitment with `ProofBuild::private_nonce`.
+pub fn create_with_nonces<K, B>(
+	k: &K,
+	b: &B,
+	amount: u64,
+	key_id: &Identifier,
+	switch: &SwitchCommitmentType,
+	rewind_nonce: SecretKey,
+	private_nonce: SecretKey,
+	extra_data: Option<Vec<u8>>,
+) -> Result<RangeProof, Error>
+where
+	K: Keychain,
+	B: ProofBuild,
+{
+	let secp = k.secp();
+	let skey = k.derive_key(amount, key_id, switch)?;
 	let message = b.proof_message(secp, key_id, switch)?;
 	Ok(secp.bullet_proof(
 		amount,
@@ -18,16 +18,20 @@ use crate::consensus::reward;
 use crate::core::transaction::kernel_sig_msg;
 use crate::core::{KernelFeatures, Output, OutputFeatures, TxKernel};
 use crate::keychain::{Identifier, Keychain};
-use crate::libtx::error::Error;
+use crate::libtx::error::{Error, ErrorKind};
 use crate::libtx::{
 	aggsig,
 	proof::{self, ProofBuild},
 };
-use crate::util::{secp, static_secp_instance};
+use crate::util::secp::key::SecretKey;
+use crate::util::secp::pedersen::{Commitment, RangeProof};
+use crate::util::secp::Secp256k1;
+use crate::util::static_secp_instance;
 use grin_keychain::SwitchCommitmentType;
 
 // MWC - add height because reward depends on the block height
-/// output a reward output
+/// output a reward output. In test mode the kernel is signed with a fixed
+/// nonce, see `output_with_nonce` to provide one.
 pub fn output<K, B>(
 	keychain: &K,
 	builder: &B,
@@ -36,10 +40,127 @@ pub fn output<K, B>(
 	test_mode: bool,
 	height: u64,
 ) -> Result<(Output, TxKernel), Error>
+where
+	K: Keychain,
+	B: ProofBuild,
+{
+	let nonce = test_nonce(test_mode)?;
+	output_with_nonce(keychain, builder, key_id, fees, nonce, height)
+}
+
+// MWC - add height because reward depends on the block height
+/// output a reward output, signing its kernel with the provided nonce when
+/// there is one so the signature is deterministic (and reproducible across
+/// runs). A random nonce is used otherwise.
+pub fn output_with_nonce<K, B>(
+	keychain: &K,
+	builder: &B,
+	key_id: &Identifier,
+	fees: u64,
+	nonce: Option<SecretKey>,
+	height: u64,
+) -> Result<(Output, TxKernel), Error>
+where
+	K: Keychain,
+	B: ProofBuild,
+{
+	output_with_switch(
+		keychain,
+		builder,
+		key_id,
+		fees,
+		nonce,
+		height,
+		SwitchCommitmentType::Regular,
+	)
+}
+
+/// output a reward output using the provided switch commitment scheme for
+/// both its commitment and its range proof (and kernel signature).
+pub fn output_with_switch<K, B>(
+	keychain: &K,
+	builder: &B,
+	key_id: &Identifier,
+	fees: u64,
+	nonce: Option<SecretKey>,
+	height: u64,
+	switch: SwitchCommitmentType,
+) -> Result<(Output, TxKernel), Error>
 where
 	K: Keychain,
 	B: ProofBuild,
 {
+	let value = reward(fees, height);
+	let commit = keychain.commit(value, key_id, &switch)?;
+
+	trace!("Block reward - Pedersen Commit is: {:?}", commit,);
+
+	let rproof = proof::create(keychain, builder, value, key_id, &switch, commit, None)?;
+
+	let secp = static_secp_instance();
+	let secp = secp.lock();
+	build_reward(
+		&secp,
+		keychain,
+		key_id,
+		&switch,
+		value,
+		commit,
+		rproof,
+		nonce.as_ref(),
+	)
+}
+
+// MWC - add height because reward depends on the block height
+/// output a batch of reward outputs, one for each (key_id, fees, height)
+/// request, as `output` would build them. The secp instance is only locked
+/// once for the whole batch.
+pub fn outputs_batch<K, B>(
+	keychain: &K,
+	builder: &B,
+	requests: &[(Identifier, u64, u64)],
+) -> Result<Vec<(Output, TxKernel)>, Error>
+where
+	K: Keychain,
+	B: ProofBuild,
+{
+	let switch = &SwitchCommitmentType::Regular;
+	let secp = static_secp_instance();
+	let secp = secp.lock();
+	requests
+		.iter()
+		.map(|(key_id, fees, height)| {
+			let value = reward(*fees, *height);
+			let commit = keychain.commit(value, key_id, switch)?;
+			let rproof = proof::create(keychain, builder, value, key_id, switch, commit, None)?;
+			build_reward(&secp, keychain, key_id, switch, value, commit, rproof, None)
+		})
+		.collect()
+}
+
+// MWC - add height because reward depends on the block height
+/// output a reward output, building its range proof from the provided rewind
+/// nonce rather than deriving it from the commitment.
+/// The private nonce is still derived from the commitment by the builder, a
+/// private nonce shared by proofs under different keys would leak their
+/// blinding factors. The proof is deterministic for a given key and rewind
+/// nonce, so it can be cached by the caller. It is still bound to the output
+/// commitment and can't be shared between reward outputs built with
+/// different keys.
+pub fn output_reuse_nonce<K, B>(
+	keychain: &K,
+	builder: &B,
+	key_id: &Identifier,
+	fees: u64,
+	test_mode: bool,
+	height: u64,
+	rewind_nonce: &SecretKey,
+) -> Result<(Output, TxKernel), Error>
+where
+	K: Keychain,
+	B: ProofBuild,
+{
+	let nonce = test_nonce(test_mode)?;
 	let value = reward(fees, height);
 	// TODO: proper support for different switch commitment schemes
 	let switch = &SwitchCommitmentType::Regular;
@@ -47,41 +168,146 @@ where
 
 	trace!("Block reward - Pedersen Commit is: {:?}", commit,);
 
-	let rproof = proof::create(keychain, builder, value, key_id, switch, commit, None)?;
+	let private_nonce = builder.private_nonce(keychain.secp(), &commit)?;
+	let rproof = proof::create_with_nonces(
+		keychain,
+		builder,
+		value,
+		key_id,
+		switch,
+		rewind_nonce.clone(),
+		private_nonce,
+		None,
+	)?;
+
+	let secp = static_secp_instance();
+	let secp = secp.lock();
+	build_reward(
+		&secp,
+		keychain,
+		key_id,
+		switch,
+		value,
+		commit,
+		rproof,
+		nonce.as_ref(),
+	)
+}
+
+/// Verify a coinbase output and kernel pair is a valid reward for a block at
+/// the provided height collecting the provided fees, the inverse of `output`.
+/// Checks the output and kernel features, the kernel fee and lock height,
+/// the kernel excess against the output commitment minus the reward value,
+/// and the kernel signature against the excess.
+/// The output range proof is not verified.
+pub fn verify(output: &Output, kernel: &TxKernel, height: u64, fees: u64) -> Result<(), Error> {
+	if !output.is_coinbase() || !kernel.is_coinbase() {
+		return Err(ErrorKind::Coinbase("not a coinbase output and kernel".to_string()).into());
+	}
+	if kernel.fee != 0 || kernel.lock_height != 0 {
+		return Err(ErrorKind::Coinbase("non zero kernel fee or lock height".to_string()).into());
+	}
+
+	let secp = static_secp_instance();
+	let secp = secp.lock();
+	let over_commit = secp.commit_value(reward(fees, height))?;
+	let excess = secp.commit_sum(vec![output.commitment()], vec![over_commit])?;
+	if excess != kernel.excess {
+		return Err(ErrorKind::Coinbase("kernel excess mismatch".to_string()).into());
+	}
+
+	let msg = kernel_sig_msg(0, 0, KernelFeatures::Coinbase)?;
+	aggsig::verify_single_from_commit(&secp, &kernel.excess_sig, &msg, &excess)
+}
+
+// MWC - add height because reward depends on the block height
+/// Rewind the range proof of a coinbase output, returning its value when the
+/// output belongs to the provided keychain and None otherwise. The value must
+/// be the reward of a block at the provided height without any fees.
+pub fn rewind_coinbase<K, B>(
+	keychain: &K,
+	builder: &B,
+	output: &Output,
+	height: u64,
+) -> Result<Option<u64>, Error>
+where
+	K: Keychain,
+	B: ProofBuild,
+{
+	if !output.is_coinbase() {
+		return Err(ErrorKind::Coinbase("not a coinbase output".to_string()).into());
+	}
+	let info = proof::rewind(
+		keychain.secp(),
+		builder,
+		output.commitment(),
+		None,
+		output.proof,
+	)?;
+	match info {
+		Some((value, _, _)) => {
+			if value != reward(0, height) {
+				return Err(ErrorKind::Coinbase(format!(
+					"rewound value {} is not the reward at height {}",
+					value, height
+				))
+				.into());
+			}
+			Ok(Some(value))
+		}
+		None => Ok(None),
+	}
+}
+
+/// Fixed kernel signature nonce used in test mode.
+fn test_nonce(test_mode: bool) -> Result<Option<SecretKey>, Error> {
+	if !test_mode {
+		return Ok(None);
+	}
+	let secp = static_secp_instance();
+	let secp = secp.lock();
+	Ok(Some(SecretKey::from_slice(&secp, &[1; 32])?))
+}
 
+/// Builds the coinbase output and its signed kernel from the commitment and
+/// range proof of the reward.
+fn build_reward<K>(
+	secp: &Secp256k1,
+	keychain: &K,
+	key_id: &Identifier,
+	switch: &SwitchCommitmentType,
+	value: u64,
+	commit: Commitment,
+	rproof: RangeProof,
+	nonce: Option<&SecretKey>,
+) -> Result<(Output, TxKernel), Error>
+where
+	K: Keychain,
+{
 	let output = Output {
 		features: OutputFeatures::Coinbase,
 		commit: commit,
 		proof: rproof,
 	};
 
-	let secp = static_secp_instance();
-	let secp = secp.lock();
-	let over_commit = secp.commit_value(reward(fees, height))?;
+	let over_commit = secp.commit_value(value)?;
 	let out_commit = output.commitment();
 	let excess = secp.commit_sum(vec![out_commit], vec![over_commit])?;
-	let pubkey = excess.to_pubkey(&secp)?;
+	let pubkey = excess.to_pubkey(secp)?;
 
 	// NOTE: Remember we sign the fee *and* the lock_height.
 	// For a coinbase output the fee is 0 and the lock_height is 0
 	let msg = kernel_sig_msg(0, 0, KernelFeatures::Coinbase)?;
-	let sig = match test_mode {
-		true => {
-			let test_nonce = secp::key::SecretKey::from_slice(&secp, &[1; 32])?;
-			aggsig::sign_from_key_id(
-				&secp,
-				keychain,
-				&msg,
-				value,
-				&key_id,
-				Some(&test_nonce),
-				Some(&pubkey),
-			)?
-		}
-		false => {
-			aggsig::sign_from_key_id(&secp, keychain, &msg, value, &key_id, None, Some(&pubkey))?
-		}
-	};
+	let sig = aggsig::sign_from_key_id(
+		secp,
+		keychain,
+		&msg,
+		value,
+		key_id,
+		switch,
+		nonce,
+		Some(&pubkey),
+	)?;
 
 	let proof = TxKernel {
 		features: KernelFeatures::Coinbase,
@@ -45,7 +45,10 @@ mod types;
 use crate::core::{Block, BlockHeader};
 use crate::genesis;
 use crate::global;
+use byteorder::{BigEndian, ByteOrder};
 use chrono::prelude::{DateTime, NaiveDateTime, Utc};
+use std::cmp::{max, min};
+use std::sync::atomic::{AtomicBool, Ordering};
 
 pub use self::common::EdgeType;
 pub use self::types::*;
@@ -103,6 +106,19 @@ pub fn pow_size(
 	diff: Difficulty,
 	proof_size: usize,
 	sz: u8,
+) -> Result<(), Error> {
+	pow_size_cancellable(bh, diff, proof_size, sz, &AtomicBool::new(false))
+}
+
+/// Same as `pow_size` but gives up with a `Cancelled` error as soon as the
+/// cancel flag is set (checked between nonce attempts), when the job went
+/// stale because of a new block typically.
+pub fn pow_size_cancellable(
+	bh: &mut BlockHeader,
+	diff: Difficulty,
+	proof_size: usize,
+	sz: u8,
+	cancel: &AtomicBool,
 ) -> Result<(), Error> {
 	let start_nonce = bh.pow.nonce;
 
@@ -113,6 +129,10 @@ pub fn pow_size(
 
 	// try to find a cuckoo cycle on that header hash
 	loop {
+		if cancel.load(Ordering::Relaxed) {
+			return Err(ErrorKind::Cancelled.into());
+		}
+
 		// if we found a cycle (not guaranteed) and the proof hash is higher that the
 		// diff, we're all good
 		let mut ctx = global::create_pow_context::<u32>(bh.height, sz, proof_size, MAX_SOLS)?;
@@ -136,12 +156,52 @@ pub fn pow_size(
 	}
 }
 
+/// Converts a difficulty into the 256 bits (big endian) target a proof hash
+/// must not exceed, as used by stratum mining proxies. Matches the node
+/// validation, where the difficulty of a (unscaled) proof is 2^64 divided by
+/// the first 64 bits of its hash.
+pub fn difficulty_to_target(difficulty: Difficulty) -> [u8; 32] {
+	let max_hash = (1u128 << 64) / max(1, difficulty.to_num()) as u128;
+	let max_hash = min(max_hash, <u64>::max_value() as u128) as u64;
+	let mut target = [0xff; 32];
+	BigEndian::write_u64(&mut target[..8], max_hash);
+	target
+}
+
+/// Converts a 256 bits (big endian) target back into a difficulty. Inverse of
+/// `difficulty_to_target` for difficulties up to 2^32, beyond that the 64 bits
+/// precision of the target starts rounding the difficulty up.
+pub fn target_to_difficulty(target: &[u8; 32]) -> Difficulty {
+	let max_hash = BigEndian::read_u64(&target[..8]);
+	let diff = (1u128 << 64) / max(1, max_hash) as u128;
+	Difficulty::from_num(min(diff, <u64>::max_value() as u128) as u64)
+}
+
+/// Expected number of cycles (solutions) a miner has to find to meet the
+/// provided difficulty. A cycle of unit graph weight (see `Difficulty::unit`
+/// and `consensus::graph_weight`) meets a difficulty D with probability
+/// unit / D, so this is D / unit rounded up, at least 1.
+pub fn expected_attempts(difficulty: Difficulty) -> u64 {
+	unit_attempts(difficulty, Difficulty::unit().to_num())
+}
+
+/// Number of cycles of the provided graph weight needed to meet the difficulty.
+fn unit_attempts(difficulty: Difficulty, unit: u64) -> u64 {
+	let unit = max(1, unit);
+	let diff = difficulty.to_num();
+	max(1, diff / unit + if diff % unit > 0 { 1 } else { 0 })
+}
+
 #[cfg(test)]
 mod test {
 	use super::*;
+	use crate::consensus;
 	use crate::genesis;
 	use crate::global;
 	use crate::global::ChainTypes;
+	use std::sync::Arc;
+	use std::thread;
+	use std::time::Duration;
 
 	/// We'll be generating genesis blocks differently
 	#[test]
@@ -164,4 +224,92 @@ mod test {
 		assert!(b.header.pow.to_difficulty(0) >= Difficulty::min());
 		assert!(verify_size(&b.header).is_ok());
 	}
+
+	#[test]
+	fn difficulty_target_round_trip() {
+		for diff in &[1, 2, 3, 10, 1_000, 123_456, 1 << 20, (1 << 32) - 1] {
+			let target = difficulty_to_target(Difficulty::from_num(*diff));
+			assert_eq!(target_to_difficulty(&target), Difficulty::from_num(*diff));
+		}
+
+		// The lowest difficulty accepts any hash.
+		assert_eq!(difficulty_to_target(Difficulty::from_num(1)), [0xff; 32]);
+
+		// Higher difficulty, numerically smaller target.
+		let mut prev_target = difficulty_to_target(Difficulty::from_num(1));
+		for diff in &[2, 3, 100, 101, 1 << 20, 1 << 40, <u64>::max_value()] {
+			let target = difficulty_to_target(Difficulty::from_num(*diff));
+			assert!(target < prev_target);
+			prev_target = target;
+		}
+	}
+
+	#[test]
+	fn pow_size_cancelled() {
+		global::set_mining_mode(ChainTypes::AutomatedTesting);
+		let mut b = genesis::genesis_dev();
+		b.header.pow.proof.edge_bits = global::min_edge_bits();
+		// a difficulty no solution can meet
+		let diff = Difficulty::from_num(<u64>::max_value());
+
+		// already cancelled, not a single attempt
+		let nonce = b.header.pow.nonce;
+		let res = pow_size_cancellable(
+			&mut b.header,
+			diff,
+			global::proofsize(),
+			global::min_edge_bits(),
+			&AtomicBool::new(true),
+		);
+		assert_eq!(res.unwrap_err().kind(), ErrorKind::Cancelled);
+		assert_eq!(b.header.pow.nonce, nonce);
+
+		// cancelled while solving
+		let cancel = Arc::new(AtomicBool::new(false));
+		let flag = cancel.clone();
+		let handle = thread::spawn(move || {
+			thread::sleep(Duration::from_millis(100));
+			flag.store(true, Ordering::Relaxed);
+		});
+		let res = pow_size_cancellable(
+			&mut b.header,
+			diff,
+			global::proofsize(),
+			global::min_edge_bits(),
+			&cancel,
+		);
+		handle.join().unwrap();
+		assert_eq!(res.unwrap_err().kind(), ErrorKind::Cancelled);
+		assert!(b.header.pow.to_difficulty(0) < diff);
+	}
+
+	#[test]
+	fn difficulty_expected_attempts() {
+		// production chains unit, without switching the global chain type
+		let unit = consensus::UNIT_DIFFICULTY;
+		let attempts = |diff: u64| unit_attempts(Difficulty::from_num(diff), unit);
+
+		// round trip through the target
+		for diff in &[unit, 1_000_000, 1 << 24, 123_456_789, (1 << 32) - 1] {
+			let diff = Difficulty::from_num(*diff);
+			assert_eq!(Difficulty::from_target(&diff.to_target()), diff);
+		}
+
+		// a single unit cycle meets the unit difficulty, k units take k cycles
+		assert_eq!(attempts(Difficulty::min().to_num()), 1);
+		assert_eq!(attempts(unit), 1);
+		assert_eq!(attempts(unit + 1), 2);
+		assert_eq!(attempts(unit * 1000), 1000);
+
+		// higher difficulty, more expected attempts
+		let mut prev = attempts(unit);
+		for k in &[2, 10, 1_000, 1 << 20, 1 << 40] {
+			let cur = attempts(unit * k);
+			assert!(cur > prev);
+			prev = cur;
+		}
+
+		// testing chains have a unit of 1, every difficulty point is a cycle
+		assert_eq!(unit_attempts(Difficulty::from_num(1000), 1), 1000);
+	}
 }
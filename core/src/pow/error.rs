@@ -50,6 +50,9 @@ pub enum ErrorKind {
 	/// No Solution
 	#[fail(display = "No Solution")]
 	NoSolution,
+	/// Solving cancelled before a solution was found
+	#[fail(display = "Cancelled")]
+	Cancelled,
 }
 
 impl Fail for Error {
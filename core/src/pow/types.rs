@@ -69,7 +69,8 @@ impl Difficulty {
 		}
 	}
 
-	/// Difficulty unit, which is the graph weight of minimal graph
+	/// Difficulty unit, which is the graph weight of the secondary PoW graph
+	/// (UNIT_DIFFICULTY) on production chains, 1 on testing chains
 	pub fn unit() -> Difficulty {
 		Difficulty {
 			num: global::initial_graph_weight() as u64,
@@ -102,6 +103,18 @@ impl Difficulty {
 	pub fn to_num(&self) -> u64 {
 		self.num
 	}
+
+	/// The 256 bits (big endian) target a proof hash must not exceed to meet
+	/// this difficulty, see `pow::difficulty_to_target`.
+	pub fn to_target(&self) -> [u8; 32] {
+		crate::pow::difficulty_to_target(*self)
+	}
+
+	/// The difficulty met by a proof hash equal to the provided target, see
+	/// `pow::target_to_difficulty`.
+	pub fn from_target(target: &[u8; 32]) -> Difficulty {
+		crate::pow::target_to_difficulty(target)
+	}
 }
 
 impl fmt::Display for Difficulty {
@@ -14,18 +14,19 @@
 
 pub mod common;
 use crate::common::{new_block, tx1i2o, tx2i1o, txspend1i1o};
-use crate::core::consensus::BLOCK_OUTPUT_WEIGHT;
+use crate::core::consensus::{self, BLOCK_OUTPUT_WEIGHT};
 use crate::core::core::block::Error;
 use crate::core::core::hash::Hashed;
 use crate::core::core::id::ShortIdentifiable;
 use crate::core::core::transaction::{self, Transaction};
 use crate::core::core::verifier_cache::{LruVerifierCache, VerifierCache};
-use crate::core::core::Committed;
+use crate::core::core::{committed, Committed};
 use crate::core::core::{
 	Block, BlockHeader, CompactBlock, HeaderVersion, KernelFeatures, OutputFeatures,
 };
 use crate::core::libtx::build::{self, input, output, with_fee};
-use crate::core::libtx::ProofBuilder;
+use crate::core::libtx::{reward, ProofBuilder};
+use crate::core::pow::Difficulty;
 use crate::core::{global, ser};
 use crate::keychain::{BlindingFactor, ExtKeychain, Keychain};
 use crate::util::secp;
@@ -84,6 +85,50 @@ fn very_empty_block() {
 	);
 }
 
+#[test]
+// the per kernel fees of a block add up to the fee portion of its coinbase
+fn block_fees_by_kernel() {
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = ProofBuilder::new(&keychain);
+	let key_id1 = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let key_id2 = ExtKeychain::derive_key_id(1, 2, 0, 0, 0);
+
+	let mut btx1 = tx2i1o();
+	let mut btx2 = build::transaction(
+		vec![input(7, key_id1), output(5, key_id2), with_fee(2)],
+		&keychain,
+		&builder,
+	)
+	.unwrap();
+	let prev = BlockHeader::default();
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let b = new_block(
+		vec![&mut btx1, &mut btx2],
+		&keychain,
+		&builder,
+		&prev,
+		&key_id,
+	);
+
+	let fees = b.fees_by_kernel();
+	assert_eq!(fees.len(), 3);
+	for tx in &[&btx1, &btx2] {
+		let kernel = &tx.kernels()[0];
+		assert!(fees.contains(&(kernel.excess(), kernel.fee())));
+	}
+	let coinbase = b.kernels().iter().find(|k| k.is_coinbase()).unwrap();
+	assert!(fees.contains(&(coinbase.excess(), 0)));
+
+	let sum: u64 = fees.iter().map(|x| x.1).sum();
+	assert!(sum > 0);
+	assert_eq!(sum, btx1.fee() + btx2.fee());
+	let height = b.header.height;
+	assert_eq!(
+		sum,
+		consensus::reward(b.total_fees(), height) - consensus::reward(0, height)
+	);
+}
+
 #[test]
 // builds a block with a tx spending another and check that cut_through occurred
 fn block_with_cut_through() {
@@ -122,6 +167,58 @@ fn block_with_cut_through() {
 	assert_eq!(b.outputs().len(), 3);
 }
 
+#[test]
+// merges the body of a tx spending an output into a block containing it and
+// checks the redundant input/output pair is cut-through, kernels preserved
+fn block_cut_through_after_merge() {
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = ProofBuilder::new(&keychain);
+	let key_id1 = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let key_id2 = ExtKeychain::derive_key_id(1, 2, 0, 0, 0);
+	let key_id3 = ExtKeychain::derive_key_id(1, 3, 0, 0, 0);
+
+	let btx1 = build::transaction(
+		vec![input(7, key_id1), output(5, key_id2.clone()), with_fee(2)],
+		&keychain,
+		&builder,
+	)
+	.unwrap();
+	let btx2 = txspend1i1o(5, &keychain, &builder, key_id2.clone(), key_id3);
+
+	// the reward collects the fees of both txs, btx2 being merged below
+	let prev = BlockHeader::default();
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let fees = btx1.fee() + btx2.fee();
+	let reward =
+		reward::output(&keychain, &builder, &key_id, fees, false, prev.height + 1).unwrap();
+	let mut b = Block::new(&prev, vec![btx1.clone()], Difficulty::min(), reward).unwrap();
+	assert_eq!(b.inputs().len(), 1);
+	assert_eq!(b.outputs().len(), 2);
+
+	// merge btx2 without cut-through, spending the output of btx1
+	let spent = btx1.outputs()[0].commitment();
+	b.inputs_mut().extend(btx2.inputs().clone());
+	b.outputs_mut().extend(btx2.outputs().clone());
+	b.kernels_mut().extend(btx2.kernels().clone());
+	b.header.total_kernel_offset =
+		committed::sum_kernel_offsets(vec![b.header.total_kernel_offset(), btx2.offset], vec![])
+			.unwrap();
+	assert_eq!(b.inputs().len(), 2);
+	assert_eq!(b.outputs().len(), 3);
+	let kernels = b.kernels().clone();
+
+	b.cut_through().unwrap();
+
+	assert_eq!(b.inputs().len(), 1);
+	assert_eq!(b.outputs().len(), 2);
+	assert!(b.inputs().iter().all(|x| x.commitment() != spent));
+	assert!(b.outputs().iter().all(|x| x.commitment() != spent));
+	assert_eq!(b.kernels().len(), 3);
+	assert!(kernels.iter().all(|k| b.kernels().contains(k)));
+	b.validate(&BlindingFactor::zero(), verifier_cache())
+		.unwrap();
+}
+
 #[test]
 fn empty_block_with_coinbase_is_valid() {
 	let keychain = ExtKeychain::from_random_seed(false).unwrap();
@@ -208,6 +305,76 @@ fn remove_coinbase_kernel_flag() {
 	);
 }
 
+#[test]
+// test that a block with a second coinbase kernel is rejected
+fn two_coinbase_kernels() {
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = ProofBuilder::new(&keychain);
+	let prev = BlockHeader::default();
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let mut b = new_block(vec![], &keychain, &builder, &prev, &key_id);
+
+	let key_id2 = ExtKeychain::derive_key_id(1, 2, 0, 0, 0);
+	let (_, kernel) = reward::output(&keychain, &builder, &key_id2, 0, false, 1).unwrap();
+	b.kernels_mut().push(kernel);
+	b.kernels_mut().sort_by_key(|k| k.hash());
+
+	assert_eq!(
+		b.verify_coinbase_kernel_count(),
+		Err(Error::InvalidCoinbaseKernelCount { found: 2 })
+	);
+	assert_eq!(
+		b.validate(&BlindingFactor::zero(), verifier_cache()),
+		Err(Error::InvalidCoinbaseKernelCount { found: 2 })
+	);
+}
+
+#[test]
+// test that a block without any coinbase kernel is rejected
+fn no_coinbase_kernel() {
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = ProofBuilder::new(&keychain);
+	let prev = BlockHeader::default();
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let mut b = new_block(vec![], &keychain, &builder, &prev, &key_id);
+
+	b.kernels_mut().clear();
+
+	assert_eq!(
+		b.verify_coinbase_kernel_count(),
+		Err(Error::InvalidCoinbaseKernelCount { found: 0 })
+	);
+	assert_eq!(
+		b.validate(&BlindingFactor::zero(), verifier_cache()),
+		Err(Error::InvalidCoinbaseKernelCount { found: 0 })
+	);
+}
+
+#[test]
+// test that a block with a height locked coinbase kernel is rejected
+fn coinbase_kernel_lock_height() {
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = ProofBuilder::new(&keychain);
+	let prev = BlockHeader::default();
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let mut b = new_block(vec![], &keychain, &builder, &prev, &key_id);
+	b.validate(&BlindingFactor::zero(), verifier_cache())
+		.unwrap();
+
+	for k in b.kernels_mut().iter_mut().filter(|k| k.is_coinbase()) {
+		k.lock_height = 5;
+	}
+
+	assert_eq!(
+		b.verify_coinbase_lock_height(),
+		Err(Error::InvalidCoinbaseLockHeight(5))
+	);
+	assert_eq!(
+		b.validate(&BlindingFactor::zero(), verifier_cache()),
+		Err(Error::InvalidCoinbaseLockHeight(5))
+	);
+}
+
 #[test]
 fn serialize_deserialize_header_version() {
 	let mut vec1 = Vec::new();
@@ -690,3 +690,476 @@ fn hard_forks() {
 		));
 	}
 }
+
+#[test]
+fn difficulty_window_serde() {
+	global::set_mining_mode(global::ChainTypes::Mainnet);
+
+	// a short chain, the window gets padded up to DIFFICULTY_ADJUST_WINDOW + 1
+	let mut hi = HeaderInfo::from_diff_scaling(Difficulty::from_num(1000), 7);
+	hi.is_secondary = true;
+	let chain = repeat(60, hi, 10, Some(10_000));
+
+	let window = dump_difficulty_window(chain.clone());
+	assert_eq!(window.len(), DIFFICULTY_ADJUST_WINDOW as usize + 1);
+	assert_eq!(window, global::difficulty_data_to_vector(chain));
+
+	let serialized = serde_json::to_string(&window).unwrap();
+	let deserialized: Vec<HeaderInfo> = serde_json::from_str(&serialized).unwrap();
+	assert_eq!(deserialized, window);
+
+	let last = deserialized.last().unwrap();
+	assert_eq!(last.timestamp, 10_000 + 9 * 60);
+	assert_eq!(last.difficulty, Difficulty::from_num(1000));
+	assert_eq!(last.secondary_scaling, 7);
+	assert!(last.is_secondary);
+}
+
+#[test]
+fn next_difficulty_empty_cursor() {
+	global::set_mining_mode(global::ChainTypes::Mainnet);
+
+	// no header at all, the window is padded at the initial difficulty
+	let window = global::difficulty_data_to_vector(Vec::<HeaderInfo>::new());
+	assert_eq!(window.len(), DIFFICULTY_ADJUST_WINDOW as usize + 1);
+	assert!(window
+		.iter()
+		.all(|x| x.difficulty == Difficulty::from_num(INITIAL_DIFFICULTY)));
+
+	let next = next_difficulty(1, Vec::<HeaderInfo>::new());
+	assert_eq!(next.difficulty, Difficulty::from_num(INITIAL_DIFFICULTY));
+}
+
+#[test]
+fn next_difficulty_diagnostics() {
+	global::set_mining_mode(global::ChainTypes::Mainnet);
+
+	// full window, blocks twice as slow as the target, no secondary blocks
+	let hi = HeaderInfo::new(1, Difficulty::from_num(1000), 100, false);
+	let window = repeat(120, hi, DIFFICULTY_ADJUST_WINDOW + 1, Some(0));
+
+	let (next, diag) = next_difficulty_verbose(1, window.clone());
+	assert_eq!(next, next_difficulty(1, window));
+
+	// 60 intervals of 120s
+	assert_eq!(diag.ts_delta, 7200);
+	// 60 blocks at difficulty 1000 (first header is only the time bound)
+	assert_eq!(diag.diff_sum, 60_000);
+	// (7200 + 2 * 3600) / 3
+	assert_eq!(diag.damped_ts, 4800);
+	// within [3600 / 2, 3600 * 2]
+	assert_eq!(diag.adj_ts, 4800);
+	assert_eq!(diag.ar_count, 0);
+	// 60 * 100 * 90 / ((0 + 12 * 5400) / 13)
+	assert_eq!(diag.sec_pow_scaling, 108);
+
+	// 60_000 * 60 / 4800
+	assert_eq!(next.difficulty, Difficulty::from_num(750));
+	assert_eq!(next.secondary_scaling, 108);
+}
+
+#[test]
+fn approx_time_since_genesis() {
+	assert_eq!(approx_years_since_genesis(0), 0.0);
+	assert!((approx_years_since_genesis(YEAR_HEIGHT) - 1.0).abs() < 1e-9);
+	assert!((approx_years_since_genesis(YEAR_HEIGHT / 2) - 0.5).abs() < 1e-9);
+
+	assert_eq!(approx_days_since_genesis(0), 0.0);
+	assert!((approx_days_since_genesis(DAY_HEIGHT) - 1.0).abs() < 1e-9);
+	assert!((approx_days_since_genesis(WEEK_HEIGHT) - 7.0).abs() < 1e-9);
+}
+
+#[test]
+fn network_hashrate_estimation() {
+	global::set_mining_mode(global::ChainTypes::Mainnet);
+	let primary_weight = graph_weight(1, DEFAULT_MIN_EDGE_BITS);
+	let secondary_weight = graph_weight(1, SECOND_POW_EDGE_BITS);
+
+	// uniform window mined exactly on time, 42 * 10 graphs per second
+	let hi = HeaderInfo::new(
+		1,
+		Difficulty::from_num(10 * BLOCK_TIME_SEC * primary_weight),
+		1,
+		false,
+	);
+	let mut window = repeat(BLOCK_TIME_SEC, hi, DIFFICULTY_ADJUST_WINDOW + 1, Some(0));
+	// oldest first, as for next_difficulty
+	window.reverse();
+	assert_eq!(estimate_network_hashrate(1, &window), 420.0);
+	assert_eq!(estimate_network_hashrate_split(1, &window), (420.0, 0.0));
+
+	// every other block is secondary, the secondary graphs being lighter
+	let window: Vec<HeaderInfo> = window
+		.into_iter()
+		.enumerate()
+		.map(|(i, mut hi)| {
+			hi.is_secondary = i % 2 == 1;
+			hi
+		})
+		.collect();
+	let (primary, secondary) = estimate_network_hashrate_split(1, &window);
+	assert_eq!(primary, 210.0);
+	let expected = 210.0 * primary_weight as f64 / secondary_weight as f64;
+	assert!((secondary - expected).abs() < 1e-6);
+	assert_eq!(estimate_network_hashrate(1, &window), 210.0);
+
+	// zero timespan
+	let hi = HeaderInfo::from_diff_scaling(Difficulty::from_num(1000), 1);
+	let window = repeat(0, hi, 10, Some(0));
+	assert_eq!(estimate_network_hashrate(1, &window), 0.0);
+	assert_eq!(estimate_network_hashrate(1, &[]), 0.0);
+}
+
+#[test]
+fn header_version_schedule() {
+	// Tests for mainnet chain type.
+	{
+		global::set_mining_mode(global::ChainTypes::Mainnet);
+		assert_eq!(header_version_at(0), HeaderVersion::new(1));
+		assert_eq!(header_version_at(HARD_FORK_INTERVAL - 1), HeaderVersion::new(1));
+		assert_eq!(header_version_at(HARD_FORK_INTERVAL), HeaderVersion::new(2));
+		assert_eq!(
+			header_version_at(2 * HARD_FORK_INTERVAL - 1),
+			HeaderVersion::new(2)
+		);
+
+		assert_eq!(next_fork_height(0), Some(HARD_FORK_INTERVAL));
+		assert_eq!(
+			next_fork_height(HARD_FORK_INTERVAL - 1),
+			Some(HARD_FORK_INTERVAL)
+		);
+		assert_eq!(
+			next_fork_height(HARD_FORK_INTERVAL),
+			Some(2 * HARD_FORK_INTERVAL)
+		);
+		assert_eq!(next_fork_height(2 * HARD_FORK_INTERVAL), None);
+
+		assert!(valid_header_version(
+			HARD_FORK_INTERVAL - 1,
+			HeaderVersion::new(1)
+		));
+		assert!(valid_header_version(
+			2 * HARD_FORK_INTERVAL - 1,
+			HeaderVersion::new(2)
+		));
+		assert!(!valid_header_version(
+			2 * HARD_FORK_INTERVAL,
+			header_version_at(2 * HARD_FORK_INTERVAL)
+		));
+	}
+	// Tests for floonet chain type.
+	{
+		global::set_mining_mode(global::ChainTypes::Floonet);
+		assert_eq!(header_version_at(0), HeaderVersion::new(1));
+		assert_eq!(
+			header_version_at(FLOONET_FIRST_HARD_FORK - 1),
+			HeaderVersion::new(1)
+		);
+		assert_eq!(
+			header_version_at(FLOONET_FIRST_HARD_FORK),
+			HeaderVersion::new(2)
+		);
+
+		assert_eq!(next_fork_height(0), Some(FLOONET_FIRST_HARD_FORK));
+		assert_eq!(
+			next_fork_height(FLOONET_FIRST_HARD_FORK),
+			Some(2 * HARD_FORK_INTERVAL)
+		);
+		assert_eq!(next_fork_height(2 * HARD_FORK_INTERVAL), None);
+
+		assert!(valid_header_version(
+			2 * HARD_FORK_INTERVAL - 1,
+			HeaderVersion::new(2)
+		));
+		assert!(!valid_header_version(
+			2 * HARD_FORK_INTERVAL,
+			HeaderVersion::new(2)
+		));
+	}
+}
+
+#[test]
+fn regtest_block_time() {
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+	assert_eq!(global::block_time_sec(), BLOCK_TIME_SEC);
+	assert_eq!(block_time_window(), BLOCK_TIME_WINDOW);
+
+	// window of 1 second blocks at difficulty 1000
+	let hi = HeaderInfo::from_diff_scaling(Difficulty::from_num(1000), 1);
+	let window = repeat(1, hi, DIFFICULTY_ADJUST_WINDOW + 1, Some(0));
+
+	// on mainnet timing, these blocks are way too fast
+	global::set_mining_mode(global::ChainTypes::Mainnet);
+	let next = next_difficulty(1, window.clone());
+	assert!(next.difficulty > Difficulty::from_num(1000));
+
+	// on regtest they are right on time, difficulty stays the same
+	global::set_mining_mode(global::ChainTypes::Regtest);
+	assert_eq!(global::block_time_sec(), global::REGTEST_BLOCK_TIME_SEC);
+	assert_eq!(
+		block_time_window(),
+		DIFFICULTY_ADJUST_WINDOW * global::REGTEST_BLOCK_TIME_SEC
+	);
+	let next = next_difficulty(1, window);
+	assert_eq!(next.difficulty, Difficulty::from_num(1000));
+
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+}
+
+#[test]
+fn timewarp_detection() {
+	let now = Utc::now().timestamp() as u64;
+
+	// monotonic windows, regular or not, are fine
+	assert!(!detect_timewarp(&repeat_offs(now, 60, 10, 60)));
+	assert!(!detect_timewarp(&repeat_offs(now, 1, 10, 60)));
+	assert!(!detect_timewarp(&repeat_offs(now, 3600, 10, 60)));
+	assert!(!detect_timewarp(&[]));
+
+	// a bit of clock disagreement between miners is tolerated
+	let mut window = repeat_offs(now, 60, 10, 60);
+	window[10].timestamp -= 120;
+	window[30].timestamp -= timewarp_tolerance();
+	assert!(!detect_timewarp(&window));
+
+	// steadily decreasing timestamps are flagged
+	let mut decreasing = repeat_offs(now, 60, 10, 60);
+	decreasing.reverse();
+	assert!(detect_timewarp(&decreasing));
+
+	// as is a single header backdated beyond the tolerance
+	let mut window = repeat_offs(now, 60, 10, 60);
+	window[20].timestamp -= timewarp_tolerance() + 61;
+	assert!(detect_timewarp(&window));
+}
+
+#[test]
+fn reward_components_schedule() {
+	global::set_mining_mode(global::ChainTypes::Mainnet);
+
+	// genesis, the subsidy is the whole premine
+	let genesis = reward_components(0, 0);
+	assert_eq!(genesis.subsidy, GENESIS_BLOCK_REWARD);
+	assert_eq!(genesis.fees, 0);
+	assert_eq!(genesis.total, GENESIS_BLOCK_REWARD);
+	assert_eq!(reward(0, 0), genesis.total);
+
+	// first and mid schedule groups, fees on top of the subsidy
+	let fees = 7_000_000;
+	for height in &[1, 2_100_000, 2_100_001, 2_100_000 * 5 + 200] {
+		let components = reward_components(*height, fees);
+		assert_eq!(components.subsidy, calc_mwc_block_reward(*height));
+		assert_eq!(components.fees, fees);
+		assert_eq!(components.total, components.subsidy + fees);
+		assert_eq!(reward(fees, *height), components.total);
+	}
+	assert_eq!(reward_components(1, 0).subsidy, MWC_FIRST_GROUP_REWARD);
+	assert_eq!(
+		reward_components(2_100_001, 0).subsidy,
+		MWC_FIRST_GROUP_REWARD / 2
+	);
+
+	// past the cap, only the fees are left
+	let capped = reward_components(2_100_000 * 32 + 1, fees);
+	assert_eq!(capped.subsidy, 0);
+	assert_eq!(capped.total, fees);
+	assert_eq!(reward(fees, 2_100_000 * 32 + 1), fees);
+
+	// the total never overflows
+	assert_eq!(
+		reward_components(1, u64::max_value()).total,
+		u64::max_value()
+	);
+
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+}
+
+#[test]
+fn next_difficulty_long_gap() {
+	global::set_mining_mode(global::ChainTypes::Mainnet);
+	let window = DIFFICULTY_ADJUST_WINDOW;
+
+	// a window at minimum difficulty, then no block for a very long time
+	// (close enough to the end of time to saturate the dampening, leaving just
+	// enough room for a window of blocks afterward)
+	let mut chain = repeat_offs(1, 60, MIN_DIFFICULTY, window + 1);
+	chain[0].timestamp = u64::max_value() - 61 * window - 60;
+
+	// no overflow, difficulty bottoms out at the minimum
+	let diag = next_difficulty_verbose(1, chain.clone()).1;
+	assert_eq!(diag.adj_ts, window * 60 * CLAMP_FACTOR);
+	assert_eq!(
+		next_difficulty(1, chain.clone()).difficulty,
+		Difficulty::from_num(MIN_DIFFICULTY)
+	);
+
+	// blocks resume at the normal spacing, the gap is still in the window
+	let resume = chain[0].timestamp;
+	let mut resumed = repeat_offs(resume + 60, 60, 1000, window / 2);
+	resumed.extend(chain.iter().cloned());
+	assert!(next_difficulty(1, resumed).difficulty >= Difficulty::from_num(MIN_DIFFICULTY));
+
+	// once the gap left the window, the retarget follows the mined difficulty
+	let resumed = repeat_offs(resume + 60, 60, 1000, window + 1);
+	assert_eq!(
+		next_difficulty(1, resumed).difficulty,
+		Difficulty::from_num(1000)
+	);
+
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+}
+
+#[test]
+fn reward_components_mid_group() {
+	global::set_mining_mode(global::ChainTypes::Mainnet);
+
+	// mid group, the fees come on top of the group subsidy
+	let height = 2_100_000 * 2 + 1_050_000;
+	let breakdown = reward_components(height, 12_345);
+	assert_eq!(breakdown.subsidy, MWC_FIRST_GROUP_REWARD / 4);
+	assert_eq!(breakdown.fees, 12_345);
+	assert_eq!(breakdown.subsidy + breakdown.fees, breakdown.total);
+	assert_eq!(breakdown.total, reward(12_345, height));
+
+	// genesis subsidy is the genesis reward
+	let genesis = reward_components(0, 0);
+	assert_eq!(genesis.subsidy, GENESIS_BLOCK_REWARD);
+	assert_eq!(genesis.total, GENESIS_BLOCK_REWARD);
+
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+}
+
+#[test]
+fn emission_steps_schedule() {
+	global::set_mining_mode(global::ChainTypes::Mainnet);
+	let steps: Vec<(u64, u64)> = emission_steps().collect();
+	let group_size = 2_100_000;
+
+	// 32 groups and the terminal zero reward step
+	assert_eq!(steps.len(), 33);
+	assert_eq!(steps[0], (1, MWC_FIRST_GROUP_REWARD));
+	assert_eq!(steps[32], (32 * group_size + 1, 0));
+
+	for (i, (start, reward)) in steps.iter().enumerate() {
+		assert_eq!(calc_mwc_block_reward(*start), *reward);
+		if i > 0 {
+			let (prev_start, prev_reward) = steps[i - 1];
+			assert_eq!(*start, prev_start + group_size);
+			assert_eq!(calc_mwc_block_reward(*start - 1), prev_reward);
+			assert!(is_halving_block(*start));
+			assert!(!is_halving_block(*start - 1));
+			assert!(!is_halving_block(*start + 1));
+			if i < 32 {
+				assert_eq!(*reward, prev_reward / 2);
+			}
+		}
+	}
+	assert!(!is_halving_block(0));
+	assert!(!is_halving_block(1));
+	assert!(!is_halving_block(33 * group_size + 1));
+
+	// the whole emission on top of the genesis reward
+	let emitted: u64 = steps.iter().map(|(_, reward)| reward * group_size).sum();
+	assert_eq!(emitted, 20_000_000 * 1_000_000_000 - GENESIS_BLOCK_REWARD);
+
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+}
+
+#[test]
+fn coinbase_maturity_schedule() {
+	for height in &[0, 1, DAY_HEIGHT, YEAR_HEIGHT, 2_100_000 * 32 + 1] {
+		assert_eq!(coinbase_maturity(*height), DAY_HEIGHT);
+	}
+}
+
+#[test]
+fn reward_rounding_audit() {
+	global::set_mining_mode(global::ChainTypes::Mainnet);
+	let group_size = 2_100_000;
+	let audit = audit_reward_rounding();
+	assert_eq!(audit.len(), 32);
+
+	// exact halvings first, 2_380_952_380 / 8 is the first truncated one
+	assert_eq!(audit[0], (0, MWC_FIRST_GROUP_REWARD, 0));
+	assert_eq!(audit[1], (1, 1_190_476_190, 0));
+	assert_eq!(audit[2], (2, 595_238_095, 0));
+	assert_eq!(audit[3], (3, 297_619_047, group_size / 2));
+
+	for (group, reward, _) in &audit {
+		assert_eq!(calc_mwc_block_reward(group * group_size + 1), *reward);
+	}
+
+	// the truncated rewards emit exactly 20M with the genesis reward
+	let emitted: u64 = audit.iter().map(|(_, reward, _)| group_size * reward).sum();
+	assert_eq!(emitted + GENESIS_BLOCK_REWARD, 20_000_000 * GRIN_BASE);
+
+	// and the losses make up the difference with the exact halvings
+	let losses: u64 = audit.iter().map(|(_, _, loss)| loss).sum();
+	let exact: u64 = (0..32)
+		.map(|group| (group_size * MWC_FIRST_GROUP_REWARD) >> group)
+		.sum();
+	assert_eq!(emitted + losses, exact);
+	assert!(losses > 0);
+
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+}
+
+#[test]
+fn simulate_difficulty_on_target() {
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+	let initial = Difficulty::from_num(10_000);
+	let intervals = vec![global::block_time_sec(); 3 * DIFFICULTY_ADJUST_WINDOW as usize];
+	let series = simulate_difficulty(initial, &intervals);
+	assert_eq!(series.len(), intervals.len());
+
+	let low = Difficulty::from_num(initial.to_num() / CLAMP_FACTOR);
+	let high = Difficulty::from_num(initial.to_num() * CLAMP_FACTOR);
+	for diff in &series {
+		assert!(*diff >= low && *diff <= high);
+	}
+	// perfectly timed blocks don't move the difficulty at all
+	assert!(series.iter().all(|diff| *diff == initial));
+
+	// slower blocks bring it down, still within the clamp band
+	let slow = simulate_difficulty(initial, &vec![4 * global::block_time_sec(); 10]);
+	assert!(slow[9] < initial && slow[9] >= low);
+}
+
+#[test]
+fn difficulty_unit_is_secondary_graph_weight() {
+	// on production chains the unit is the weight of a secondary PoW graph
+	for chain_type in vec![global::ChainTypes::Mainnet, global::ChainTypes::Floonet] {
+		global::set_mining_mode(chain_type);
+		assert_eq!(
+			Difficulty::unit().to_num(),
+			graph_weight(1, SECOND_POW_EDGE_BITS)
+		);
+		assert_eq!(Difficulty::unit().to_num(), UNIT_DIFFICULTY);
+	}
+
+	// testing chains use a unit of 1 instead, to keep mining cheap
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+	assert_eq!(
+		Difficulty::unit().to_num(),
+		global::TESTING_INITIAL_GRAPH_WEIGHT as u64
+	);
+	assert_ne!(
+		Difficulty::unit().to_num(),
+		graph_weight(1, SECOND_POW_EDGE_BITS)
+	);
+}
+
+#[test]
+fn coinbase_block_weight_capacity() {
+	// one output and one kernel
+	assert_eq!(coinbase_block_weight(), 24);
+	assert_eq!(
+		coinbase_block_weight(),
+		BLOCK_OUTPUT_WEIGHT + BLOCK_KERNEL_WEIGHT
+	);
+
+	// the rest of the block is available to the txs
+	let capacity = MAX_BLOCK_WEIGHT - coinbase_block_weight();
+	assert_eq!(capacity, 39_976);
+	assert_eq!(capacity + coinbase_block_weight(), MAX_BLOCK_WEIGHT);
+}
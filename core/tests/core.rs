@@ -459,11 +459,10 @@ fn reward_empty_block() {
 
 	let previous_header = BlockHeader::default();
 
-	let b = new_block(vec![], &keychain, &builder, &previous_header, &key_id);
+	let mut b = new_block(vec![], &keychain, &builder, &previous_header, &key_id);
 
-	b.cut_through()
-		.unwrap()
-		.validate(&BlindingFactor::zero(), verifier_cache())
+	b.cut_through().unwrap();
+	b.validate(&BlindingFactor::zero(), verifier_cache())
 		.unwrap();
 }
 
@@ -480,18 +479,15 @@ fn reward_with_tx_block() {
 
 	let previous_header = BlockHeader::default();
 
-	let block = new_block(
+	let mut block = new_block(
 		vec![&mut tx1],
 		&keychain,
 		&builder,
 		&previous_header,
 		&key_id,
 	);
-	block
-		.cut_through()
-		.unwrap()
-		.validate(&BlindingFactor::zero(), vc.clone())
-		.unwrap();
+	block.cut_through().unwrap();
+	block.validate(&BlindingFactor::zero(), vc.clone()).unwrap();
 }
 
 #[test]
@@ -16,12 +16,27 @@
 
 pub mod common;
 
-use self::core::core::{Output, OutputFeatures};
-use self::core::libtx::proof;
+use self::core::consensus;
+use self::core::core::hash::Hashed;
+use self::core::core::transaction::sum_commitments;
+use self::core::core::{Output, OutputFeatures, OutputIdentifier};
+use self::core::global::{self, ChainTypes};
+use self::core::libtx::{proof, reward};
 use self::core::ser;
-use self::keychain::{ExtKeychain, Keychain};
+use self::keychain::{ExtKeychain, Keychain, SwitchCommitmentType};
+use self::util::secp::key::SecretKey;
+use self::util::secp::pedersen::Commitment;
+use self::util::Mutex;
 use grin_core as core;
 use grin_keychain as keychain;
+use grin_util as util;
+use lazy_static::lazy_static;
+
+lazy_static! {
+	/// Deterministic signing is a process wide flag, tests toggling it hold
+	/// this lock so they don't run concurrently.
+	static ref SIGNING_MODE: Mutex<()> = Mutex::new(());
+}
 
 #[test]
 fn test_output_ser_deser() {
@@ -46,3 +61,288 @@ fn test_output_ser_deser() {
 	assert_eq!(dout.commit, out.commit);
 	assert_eq!(dout.proof, out.proof);
 }
+
+#[test]
+fn test_output_identifier_bytes() {
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let switch = &keychain::SwitchCommitmentType::Regular;
+	let commit = keychain.commit(5, &key_id, switch).unwrap();
+
+	for features in &[OutputFeatures::Plain, OutputFeatures::Coinbase] {
+		let out_id = OutputIdentifier::new(*features, &commit);
+		let bytes = out_id.to_bytes();
+		assert_eq!(bytes.len(), 34);
+		assert_eq!(bytes, ser::ser_vec(&out_id).unwrap());
+		assert_eq!(OutputIdentifier::from_bytes(&bytes).unwrap(), out_id);
+	}
+
+	// truncated, trailing data or unknown features are all rejected
+	let bytes = OutputIdentifier::new(OutputFeatures::Plain, &commit).to_bytes();
+	assert!(OutputIdentifier::from_bytes(&bytes[..33]).is_err());
+	let mut longer = bytes.clone();
+	longer.push(0);
+	assert!(OutputIdentifier::from_bytes(&longer).is_err());
+	let mut bad_features = bytes.clone();
+	bad_features[0] = 7;
+	assert!(OutputIdentifier::from_bytes(&bad_features).is_err());
+}
+
+#[test]
+fn test_output_identifier_encoding_vector() {
+	// pinned encoding and hash, changing either breaks the output indexes
+	let mut commit_bytes = vec![0x08];
+	commit_bytes.extend(1..=32u8);
+	let commit = Commitment::from_vec(commit_bytes.clone());
+	let out_id = OutputIdentifier::new(OutputFeatures::Coinbase, &commit);
+
+	assert_eq!(
+		util::to_hex(out_id.to_bytes()),
+		"01080102030405060708090a0b0c0d0e0f101112131415161718191a1b1c1d1e1f20"
+	);
+	assert_eq!(
+		out_id.hash().to_hex(),
+		"9b3d63d8cef4f91baf69885d0454626d01f524f14145ae1af501d9c6f59bb662"
+	);
+
+	let decoded = OutputIdentifier::from_bytes(&out_id.to_bytes()).unwrap();
+	assert_eq!(decoded.features, OutputFeatures::Coinbase);
+	assert_eq!(decoded.commit.0.to_vec(), commit_bytes);
+}
+
+#[test]
+fn test_reward_output_reuse_nonce() {
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = proof::ProofBuilder::new(&keychain);
+	let secp = keychain.secp();
+	let rewind_nonce = SecretKey::from_slice(secp, &[2; 32]).unwrap();
+	let key_id1 = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let key_id2 = ExtKeychain::derive_key_id(1, 2, 0, 0, 0);
+
+	let (out1, kern1) =
+		reward::output_reuse_nonce(&keychain, &builder, &key_id1, 0, false, 1, &rewind_nonce)
+			.unwrap();
+	let (out2, kern2) =
+		reward::output_reuse_nonce(&keychain, &builder, &key_id2, 0, false, 1, &rewind_nonce)
+			.unwrap();
+
+	// same value and rewind nonce but different keys, each verifies on its own
+	assert_eq!(out1.features, OutputFeatures::Coinbase);
+	assert_ne!(out1.commit, out2.commit);
+	assert_ne!(out1.proof, out2.proof);
+	out1.verify_proof().unwrap();
+	out2.verify_proof().unwrap();
+	kern1.verify().unwrap();
+	kern2.verify().unwrap();
+
+	// a proof built for one key does not verify against the other commitment
+	let swapped = Output {
+		features: OutputFeatures::Coinbase,
+		commit: out2.commit,
+		proof: out1.proof,
+	};
+	assert!(swapped.verify_proof().is_err());
+
+	// same key and rewind nonce give the same proof
+	let (out1_again, _) =
+		reward::output_reuse_nonce(&keychain, &builder, &key_id1, 0, false, 1, &rewind_nonce)
+			.unwrap();
+	assert_eq!(out1_again.commit, out1.commit);
+	assert_eq!(out1_again.proof, out1.proof);
+}
+
+#[test]
+fn test_reward_output_deterministic_signing() {
+	let _lock = SIGNING_MODE.lock();
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = proof::ProofBuilder::new(&keychain);
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+
+	let reward_bytes = || {
+		let (out, kern) = reward::output(&keychain, &builder, &key_id, 0, false, 1).unwrap();
+		kern.verify().unwrap();
+		let mut vec = vec![];
+		ser::serialize(&mut vec, &out).expect("serialized failed");
+		ser::serialize(&mut vec, &kern).expect("serialized failed");
+		vec
+	};
+
+	// random nonces, the kernel signatures differ
+	assert_ne!(reward_bytes(), reward_bytes());
+
+	global::set_deterministic_signing(true);
+	assert_eq!(reward_bytes(), reward_bytes());
+	global::set_deterministic_signing(false);
+}
+
+#[test]
+fn test_reward_output_with_switch() {
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = proof::ProofBuilder::new(&keychain);
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let value = consensus::reward(0, 1);
+
+	let mut commits = vec![];
+	for switch in vec![SwitchCommitmentType::Regular, SwitchCommitmentType::None] {
+		let (out, kern) =
+			reward::output_with_switch(&keychain, &builder, &key_id, 0, None, 1, switch).unwrap();
+		assert_eq!(
+			out.commit,
+			keychain.commit(value, &key_id, &switch).unwrap()
+		);
+		out.verify_proof().unwrap();
+		kern.verify().unwrap();
+		commits.push(out.commit);
+	}
+	assert_ne!(commits[0], commits[1]);
+
+	// the default reward output uses regular switch commitments
+	let (out, _) = reward::output(&keychain, &builder, &key_id, 0, false, 1).unwrap();
+	assert_eq!(out.commit, commits[0]);
+}
+
+#[test]
+fn test_reward_verify_round_trip() {
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = proof::ProofBuilder::new(&keychain);
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+
+	for (height, fees) in vec![(1, 0), (2, 10_000), (2_100_000, 0), (3_200_000, 500)] {
+		let (out, kern) =
+			reward::output(&keychain, &builder, &key_id, fees, false, height).unwrap();
+		reward::verify(&out, &kern, height, fees).unwrap();
+
+		// the reward depends on both the fees and the height
+		assert!(reward::verify(&out, &kern, height, fees + 1).is_err());
+		if consensus::reward(0, height) != consensus::reward(0, height + 2_100_000) {
+			assert!(reward::verify(&out, &kern, height + 2_100_000, fees).is_err());
+		}
+	}
+
+	let (out, kern) = reward::output(&keychain, &builder, &key_id, 0, false, 1).unwrap();
+
+	// not a coinbase output
+	let mut plain_out = out.clone();
+	plain_out.features = OutputFeatures::Plain;
+	assert!(reward::verify(&plain_out, &kern, 1, 0).is_err());
+
+	// a signature with another key
+	let other_key_id = ExtKeychain::derive_key_id(1, 2, 0, 0, 0);
+	let (_, other_kern) = reward::output(&keychain, &builder, &other_key_id, 0, false, 1).unwrap();
+	let mut bad_kern = kern.clone();
+	bad_kern.excess_sig = other_kern.excess_sig;
+	assert!(reward::verify(&out, &bad_kern, 1, 0).is_err());
+}
+
+#[test]
+fn test_reward_outputs_batch() {
+	let _lock = SIGNING_MODE.lock();
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = proof::ProofBuilder::new(&keychain);
+	let requests = vec![
+		(ExtKeychain::derive_key_id(1, 1, 0, 0, 0), 0, 1),
+		(ExtKeychain::derive_key_id(1, 2, 0, 0, 0), 10_000, 2_100_000),
+		(ExtKeychain::derive_key_id(1, 3, 0, 0, 0), 500, 5_000_000),
+	];
+
+	// the kernel signatures are only reproducible with deterministic nonces
+	global::set_deterministic_signing(true);
+	let batch = reward::outputs_batch(&keychain, &builder, &requests).unwrap();
+	let single: Vec<_> = requests
+		.iter()
+		.map(|(key_id, fees, height)| {
+			reward::output(&keychain, &builder, key_id, *fees, false, *height).unwrap()
+		})
+		.collect();
+	global::set_deterministic_signing(false);
+
+	// byte for byte, the range proofs and signatures included
+	assert_eq!(batch.len(), 3);
+	assert_eq!(batch, single);
+	for ((out, kern), (single_out, single_kern)) in batch.iter().zip(single.iter()) {
+		assert_eq!(out.proof, single_out.proof);
+		assert_eq!(
+			ser::ser_vec(kern).unwrap(),
+			ser::ser_vec(single_kern).unwrap()
+		);
+	}
+	for ((out, kern), (_, fees, height)) in batch.iter().zip(requests.iter()) {
+		reward::verify(out, kern, *height, *fees).unwrap();
+	}
+}
+
+#[test]
+fn test_reward_output_with_nonce() {
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = proof::ProofBuilder::new(&keychain);
+	let secp = keychain.secp();
+	let nonce1 = SecretKey::from_slice(secp, &[4; 32]).unwrap();
+	let nonce2 = SecretKey::from_slice(secp, &[5; 32]).unwrap();
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+
+	let build = |nonce: &SecretKey| {
+		let (out, kern) =
+			reward::output_with_nonce(&keychain, &builder, &key_id, 0, Some(nonce.clone()), 1)
+				.unwrap();
+		kern.verify().unwrap();
+		(out, kern)
+	};
+
+	// same explicit nonce, same signature
+	let (out1, kern1) = build(&nonce1);
+	let (out1_again, kern1_again) = build(&nonce1);
+	assert_eq!(out1.commit, out1_again.commit);
+	assert_eq!(kern1.excess_sig, kern1_again.excess_sig);
+
+	// a different nonce signs differently
+	let (_, kern2) = build(&nonce2);
+	assert_eq!(kern1.excess, kern2.excess);
+	assert_ne!(kern1.excess_sig, kern2.excess_sig);
+
+	// the test mode shim is the [1; 32] nonce
+	let test_nonce = SecretKey::from_slice(secp, &[1; 32]).unwrap();
+	let (_, kern_test) = reward::output(&keychain, &builder, &key_id, 0, true, 1).unwrap();
+	assert_eq!(kern_test.excess_sig, build(&test_nonce).1.excess_sig);
+}
+
+#[test]
+fn test_reward_rewind_coinbase() {
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = proof::ProofBuilder::new(&keychain);
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let height = 12;
+
+	let (out, _) = reward::output(&keychain, &builder, &key_id, 0, false, height).unwrap();
+	assert_eq!(
+		reward::rewind_coinbase(&keychain, &builder, &out, height).unwrap(),
+		Some(consensus::reward(0, height))
+	);
+
+	// someone else's keychain can't rewind it
+	let foreign = ExtKeychain::from_random_seed(false).unwrap();
+	let foreign_builder = proof::ProofBuilder::new(&foreign);
+	assert_eq!(
+		reward::rewind_coinbase(&foreign, &foreign_builder, &out, height).unwrap(),
+		None
+	);
+}
+
+#[test]
+fn test_sum_commitments_reward_excess() {
+	let keychain = ExtKeychain::from_random_seed(false).unwrap();
+	let builder = proof::ProofBuilder::new(&keychain);
+	let key_id = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+	let height = 1;
+
+	let (out, kern) = reward::output(&keychain, &builder, &key_id, 0, false, height).unwrap();
+	let over_commit = keychain
+		.secp()
+		.commit_value(consensus::reward(0, height))
+		.unwrap();
+
+	let excess = sum_commitments(&[out.commitment()], &[over_commit]).unwrap();
+	assert_eq!(excess, kern.excess);
+	assert_eq!(sum_commitments(&[kern.excess], &[]).unwrap(), kern.excess);
+}
@@ -37,5 +37,6 @@ pub mod types;
 pub use crate::pool::Pool;
 pub use crate::transaction_pool::TransactionPool;
 pub use crate::types::{
-	BlockChain, DandelionConfig, PoolAdapter, PoolConfig, PoolEntry, PoolError, TxSource,
+	BlockChain, DandelionConfig, PoolAdapter, PoolConfig, PoolEntry, PoolError, RemovalReason,
+	TxSource, LOCAL_SOURCE_IDENTIFIER,
 };
@@ -15,6 +15,7 @@
 //! Transaction pool implementation.
 //! Used for both the txpool and stempool layers in the pool.
 
+use self::core::consensus;
 use self::core::core::hash::{Hash, Hashed};
 use self::core::core::id::{ShortId, ShortIdentifiable};
 use self::core::core::transaction;
@@ -22,11 +23,13 @@ use self::core::core::verifier_cache::VerifierCache;
 use self::core::core::{
 	Block, BlockHeader, BlockSums, Committed, Transaction, TxKernel, Weighting,
 };
+use self::core::global;
+use self::util::secp::pedersen::Commitment;
 use self::util::RwLock;
 use crate::types::{BlockChain, PoolEntry, PoolError};
 use grin_core as core;
 use grin_util as util;
-use std::cmp::Reverse;
+use std::cmp::{min, Reverse};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 
@@ -65,6 +68,15 @@ impl Pool {
 			.map(|x| x.tx.clone())
 	}
 
+	/// Query the tx pool for an individual tx with a kernel matching the
+	/// given kernel excess.
+	pub fn get_by_kernel_excess(&self, excess: &Commitment) -> Option<Transaction> {
+		self.entries
+			.iter()
+			.find(|x| x.tx.kernels().iter().any(|k| k.excess() == *excess))
+			.map(|x| x.tx.clone())
+	}
+
 	/// Query the tx pool for an individual tx matching the given kernel hash.
 	pub fn retrieve_tx_by_kernel_hash(&self, hash: Hash) -> Option<Transaction> {
 		for x in &self.entries {
@@ -77,6 +89,20 @@ impl Pool {
 		None
 	}
 
+	/// Kernel hashes of the pool txs the provided tx spends outputs from.
+	pub fn dependencies_of(&self, tx: &Transaction) -> Vec<Hash> {
+		let inputs: HashSet<_> = tx.inputs().iter().map(|x| x.commitment()).collect();
+		self.entries
+			.iter()
+			.filter(|x| {
+				x.tx.outputs()
+					.iter()
+					.any(|out| inputs.contains(&out.commitment()))
+			})
+			.flat_map(|x| x.tx.kernels().iter().map(|k| k.hash()))
+			.collect()
+	}
+
 	/// Query the tx pool for all known txs based on kernel short_ids
 	/// from the provided compact_block.
 	/// Note: does not validate that we return the full set of required txs.
@@ -131,19 +157,96 @@ impl Pool {
 		//   * maximize overall fees
 		let txs = self.bucket_transactions(weighting);
 
+		// Weight available to the txs, the coinbase output and kernel take the rest.
+		let budget = min(global::max_block_weight(), max_weight)
+			.saturating_sub(consensus::coinbase_block_weight());
+
 		// Iteratively apply the txs to the current chain state,
 		// rejecting any that do not result in a valid state.
-		// Verify these txs produce an aggregated tx below max_weight.
+		// Verify these txs produce an aggregated tx within the weight budget.
 		// Return a vec of all the valid txs.
 		let header = self.blockchain.chain_head()?;
-		let valid_txs = self.validate_raw_txs(&txs, None, &header, weighting)?;
+		let mut valid_txs = vec![];
+		let mut weight = 0;
+		for tx in txs {
+			// Every tx adds at least a kernel, stop once the budget is exhausted.
+			if weight + consensus::BLOCK_KERNEL_WEIGHT > budget {
+				break;
+			}
+
+			let mut candidate_txs = valid_txs.clone();
+			candidate_txs.push(tx.clone());
+			let agg_tx = transaction::aggregate(candidate_txs)?;
+			if agg_tx.tx_weight_as_block() > budget {
+				continue;
+			}
+
+			if self.validate_raw_tx(&agg_tx, &header, weighting).is_ok() {
+				weight = agg_tx.tx_weight_as_block();
+				valid_txs.push(tx);
+			}
+		}
 		Ok(valid_txs)
 	}
 
+	/// Txs in the pool spending any of the inputs of the provided tx, along
+	/// with the pool txs depending on them.
+	pub fn conflicting_transactions(&self, tx: &Transaction) -> Vec<Transaction> {
+		let mut spent: HashSet<_> = tx.inputs().iter().map(|x| x.commitment()).collect();
+		let mut conflicts = vec![];
+
+		// Entries are in insertion order, parents before children.
+		for entry in &self.entries {
+			if entry
+				.tx
+				.inputs()
+				.iter()
+				.any(|x| spent.contains(&x.commitment()))
+			{
+				// Any tx spending an output of a conflicting tx conflicts too.
+				spent.extend(entry.tx.outputs().iter().map(|x| x.commitment()));
+				conflicts.push(entry.tx.clone());
+			}
+		}
+		conflicts
+	}
+
+	/// Remove the tx from the pool along with the pool txs depending on it
+	/// (spending its outputs, directly or not). Returns the removed txs.
+	pub fn remove_with_dependents(&mut self, tx: &Transaction) -> Vec<Transaction> {
+		let mut outputs: HashSet<_> = HashSet::new();
+		let mut removed = vec![];
+
+		// Entries are in insertion order, parents before children.
+		for entry in &self.entries {
+			if entry.tx == *tx
+				|| entry
+					.tx
+					.inputs()
+					.iter()
+					.any(|x| outputs.contains(&x.commitment()))
+			{
+				outputs.extend(entry.tx.outputs().iter().map(|x| x.commitment()));
+				removed.push(entry.tx.clone());
+			}
+		}
+
+		self.entries.retain(|x| !removed.contains(&x.tx));
+		removed
+	}
+
 	pub fn all_transactions(&self) -> Vec<Transaction> {
 		self.entries.iter().map(|x| x.tx.clone()).collect()
 	}
 
+	/// Kernel excess commitments of all txs in the pool.
+	pub fn all_kernels(&self) -> Vec<Commitment> {
+		self.entries
+			.iter()
+			.flat_map(|x| x.tx.kernels().iter().map(|k| k.excess()))
+			.collect()
+	}
+
 	/// Return a single aggregate tx representing all txs in the txpool.
 	/// Returns None if the txpool is empty.
 	pub fn all_transactions_aggregate(&self) -> Result<Option<Transaction>, PoolError> {
@@ -179,6 +282,13 @@ impl Pool {
 
 		txs.extend(extra_txs);
 
+		// Reject the tx if it spends (directly or via its ancestors) the output
+		// of a tx that in turn depends on it. Aggregating such a set would cut
+		// it through entirely and break the dependency ordering.
+		if has_dependency_cycle(&entry.tx, &txs) {
+			return Err(PoolError::DependencyCycle);
+		}
+
 		let agg_tx = if txs.is_empty() {
 			// If we have nothing to aggregate then simply return the tx itself.
 			entry.tx.clone()
@@ -315,7 +425,10 @@ impl Pool {
 		let mut output_commits = HashMap::new();
 		let mut rejected = HashSet::new();
 
-		for entry in &self.entries {
+		// Parents first, so a child always finds the bucket of its parent.
+		for idx in dependency_order(&self.entries) {
+			let entry = &self.entries[idx];
+
 			// check the commits index to find parents and their position
 			// if single parent then we are good, we can bucket it with its parent
 			// if multiple parents then we need to combine buckets, but for now simply reject it (rare case)
@@ -375,7 +488,11 @@ impl Pool {
 							// Otherwise put it in its own bucket at the end.
 							// Note: This bucket will have a lower fee_to_weight
 							// than the bucket it depends on.
-							tx_buckets.push(Bucket::new(entry.tx.clone()));
+							// Any txs spending its outputs go into this new bucket.
+							insert_pos = Some(tx_buckets.len());
+							let mut child_bucket = Bucket::new(entry.tx.clone());
+							child_bucket.depends_on = Some(pos);
+							tx_buckets.push(child_bucket);
 						}
 					} else {
 						// Aggregation failed so discard this new tx.
@@ -397,15 +514,44 @@ impl Pool {
 			}
 		}
 
-		// Sort them by fee_to_weight (descending).
+		// Sort them by fee_to_weight (descending), preserving insertion order on ties.
 		// Txs with no dependencies will be toward the start of the vec.
 		// Txs with a big chain of dependencies will be toward the end of the vec.
-		tx_buckets.sort_unstable_by_key(|x| Reverse(x.fee_to_weight));
+		let mut sorted: Vec<usize> = (0..tx_buckets.len()).collect();
+		sorted.sort_by_key(|&i| Reverse(tx_buckets[i].fee_to_weight));
+
+		// A bucket may pay a better fee_to_weight than the bucket it depends on
+		// (a high fee child of a low fee tx). Hold it back until its parent has
+		// been taken so a child never precedes its parent.
+		let mut taken = vec![false; tx_buckets.len()];
+		let mut deferred: Vec<usize> = vec![];
+		let mut order = Vec::with_capacity(tx_buckets.len());
+		for i in sorted {
+			if tx_buckets[i]
+				.depends_on
+				.map_or(false, |parent| !taken[parent])
+			{
+				deferred.push(i);
+				continue;
+			}
+			taken[i] = true;
+			order.push(i);
+
+			// Taking this bucket may release deferred buckets (and their children).
+			while let Some(pos) = deferred.iter().position(|&j| {
+				tx_buckets[j]
+					.depends_on
+					.map_or(true, |parent| taken[parent])
+			}) {
+				let j = deferred.remove(pos);
+				taken[j] = true;
+				order.push(j);
+			}
+		}
 
-		tx_buckets
+		order
 			.into_iter()
-			.map(|x| x.raw_txs)
-			.flatten()
+			.flat_map(|i| tx_buckets[i].raw_txs.clone())
 			.collect()
 	}
 
@@ -445,22 +591,104 @@ impl Pool {
 		self.entries.len()
 	}
 
+	/// Total weight of the txs in the pool, using block weighing.
+	pub fn weight(&self) -> usize {
+		self.entries.iter().map(|x| x.tx.tx_weight_as_block()).sum()
+	}
+
 	/// Is the pool empty?
 	pub fn is_empty(&self) -> bool {
 		self.entries.is_empty()
 	}
 }
 
+/// Walks the ancestors of the new tx in the provided txs (the txs whose outputs
+/// it spends, their own parents and so on) looking for one spending an output
+/// of the new tx.
+fn has_dependency_cycle(tx: &Transaction, txs: &[Transaction]) -> bool {
+	let mut output_index = HashMap::new();
+	for (idx, x) in txs.iter().enumerate() {
+		for out in x.outputs() {
+			output_index.insert(out.commitment(), idx);
+		}
+	}
+
+	let new_outputs: HashSet<Commitment> = tx.outputs().iter().map(|x| x.commitment()).collect();
+	let mut visited = HashSet::new();
+	let mut to_visit: Vec<usize> = tx
+		.inputs()
+		.iter()
+		.filter_map(|x| output_index.get(&x.commitment()).cloned())
+		.collect();
+
+	while let Some(idx) = to_visit.pop() {
+		if !visited.insert(idx) {
+			continue;
+		}
+		for input in txs[idx].inputs() {
+			if new_outputs.contains(&input.commitment()) {
+				return true;
+			}
+			if let Some(parent) = output_index.get(&input.commitment()) {
+				to_visit.push(*parent);
+			}
+		}
+	}
+	false
+}
+
+/// Indexes of the entries ordered so every tx comes after the pool txs it
+/// spends from, otherwise preserving insertion order.
+fn dependency_order(entries: &[PoolEntry]) -> Vec<usize> {
+	let mut output_index = HashMap::new();
+	for (idx, x) in entries.iter().enumerate() {
+		for out in x.tx.outputs() {
+			output_index.insert(out.commitment(), idx);
+		}
+	}
+
+	let mut visited = vec![false; entries.len()];
+	let mut order = Vec::with_capacity(entries.len());
+	for start in 0..entries.len() {
+		// Depth first, a tx is emitted once all its parents have been.
+		let mut stack = vec![(start, false)];
+		while let Some((idx, parents_done)) = stack.pop() {
+			if parents_done {
+				order.push(idx);
+				continue;
+			}
+			if visited[idx] {
+				continue;
+			}
+			visited[idx] = true;
+			stack.push((idx, true));
+			for input in entries[idx].tx.inputs().iter().rev() {
+				if let Some(&parent) = output_index.get(&input.commitment()) {
+					if !visited[parent] {
+						stack.push((parent, false));
+					}
+				}
+			}
+		}
+	}
+	order
+}
+
 struct Bucket {
 	raw_txs: Vec<Transaction>,
+	/// Fee/weight ratio based on block weight, what a miner is maximizing.
 	fee_to_weight: u64,
+	/// The bucket containing the parent tx if this bucket could not be
+	/// aggregated with it.
+	depends_on: Option<usize>,
 }
 
 impl Bucket {
 	fn new(tx: Transaction) -> Bucket {
 		Bucket {
-			fee_to_weight: tx.fee_to_weight(),
+			fee_to_weight: tx.fee_to_weight_as_block(),
 			raw_txs: vec![tx.clone()],
+			depends_on: None,
 		}
 	}
 
@@ -475,8 +703,9 @@ impl Bucket {
 		let agg_tx = transaction::aggregate(raw_txs.clone())?;
 		agg_tx.validate(weighting, verifier_cache)?;
 		Ok(Bucket {
-			fee_to_weight: agg_tx.fee_to_weight(),
+			fee_to_weight: agg_tx.fee_to_weight_as_block(),
 			raw_txs: raw_txs,
+			depends_on: self.depends_on,
 		})
 	}
 }
@@ -21,13 +21,24 @@ use self::core::core::hash::{Hash, Hashed};
 use self::core::core::id::ShortId;
 use self::core::core::verifier_cache::VerifierCache;
 use self::core::core::{transaction, Block, BlockHeader, Transaction, Weighting};
+use self::core::ser;
+use self::core::{consensus, global};
+use self::util::secp::pedersen::Commitment;
 use self::util::RwLock;
 use crate::pool::Pool;
-use crate::types::{BlockChain, PoolAdapter, PoolConfig, PoolEntry, PoolError, TxSource};
+use crate::types::{
+	BlockChain, PoolAdapter, PoolConfig, PoolEntry, PoolError, RemovalReason, TxSource,
+	LOCAL_SOURCE_IDENTIFIER,
+};
 use chrono::prelude::*;
+use chrono::Duration;
 use grin_core as core;
 use grin_util as util;
-use std::collections::VecDeque;
+use std::cmp::{max, Reverse};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::Path;
 use std::sync::Arc;
 
 /// Transaction pool implementation.
@@ -40,6 +51,12 @@ pub struct TransactionPool {
 	pub stempool: Pool,
 	/// Cache of previous txs in case of a re-org.
 	pub reorg_cache: Arc<RwLock<VecDeque<PoolEntry>>>,
+	/// Txs with a lock height slightly ahead of the chain, held until the
+	/// chain reaches it (see accept_future_lock_window in the pool config).
+	pub pending: Vec<PoolEntry>,
+	/// Time and weight of the txs recently received from each peer, by peer
+	/// identifier, for rate limiting.
+	source_usage: HashMap<String, VecDeque<(DateTime<Utc>, usize)>>,
 	/// The blockchain
 	pub blockchain: Arc<dyn BlockChain>,
 	pub verifier_cache: Arc<RwLock<dyn VerifierCache>>,
@@ -64,6 +81,8 @@ impl TransactionPool {
 				"stempool".to_string(),
 			),
 			reorg_cache: Arc::new(RwLock::new(VecDeque::new())),
+			pending: vec![],
+			source_usage: HashMap::new(),
 			blockchain: chain,
 			verifier_cache,
 			adapter,
@@ -116,34 +135,62 @@ impl TransactionPool {
 		// We now need to reconcile the stempool based on the new state of the txpool.
 		// Some stempool txs may no longer be valid and we need to evict them.
 		{
+			let prev_entries = self.stempool.entries.clone();
 			let txpool_tx = self.txpool.all_transactions_aggregate()?;
 			self.stempool.reconcile(txpool_tx, header)?;
+
+			// The stem txs now in the txpool were fluffed, not removed.
+			for tx in removed_txs(prev_entries, &self.stempool) {
+				if !self.txpool.contains_tx(tx.hash()) {
+					self.adapter.tx_removed(&tx, RemovalReason::Conflict);
+				}
+			}
 		}
 		Ok(())
 	}
 
 	/// Add the given tx to the pool, directing it to either the stempool or
 	/// txpool based on stem flag provided.
+	/// Returns the hashes of the txpool txs replaced by this tx, see
+	/// replace_by_fee in the pool config.
 	pub fn add_to_pool(
 		&mut self,
 		src: TxSource,
 		tx: Transaction,
 		stem: bool,
 		header: &BlockHeader,
-	) -> Result<(), PoolError> {
+	) -> Result<Vec<Hash>, PoolError> {
+		self.add_to_pool_inner(src, tx, stem, header, true)
+	}
+
+	// Add the given tx to the pool, only counting it against the rate limit of
+	// its source if asked to (not when it was counted already, on arrival).
+	fn add_to_pool_inner(
+		&mut self,
+		src: TxSource,
+		tx: Transaction,
+		stem: bool,
+		header: &BlockHeader,
+		rate_limit: bool,
+	) -> Result<Vec<Hash>, PoolError> {
 		// Quick check to deal with common case of seeing the *same* tx
 		// broadcast from multiple peers simultaneously.
 		if !stem && self.txpool.contains_tx(tx.hash()) {
 			return Err(PoolError::DuplicateTx);
 		}
 
+		// Is the peer sending us too many txs?
+		if rate_limit {
+			self.check_source_rate(&src, &tx, Utc::now())?;
+		}
+
 		// Do we have the capacity to accept this transaction?
 		let acceptability = self.is_acceptable(&tx, stem);
 		let mut evict = false;
 		if !stem && acceptability.as_ref().err() == Some(&PoolError::OverCapacity) {
 			evict = true;
-		} else if acceptability.is_err() {
-			return acceptability;
+		} else if let Err(e) = acceptability {
+			return Err(e);
 		}
 
 		// Make sure the transaction is valid before anything else.
@@ -151,11 +198,47 @@ impl TransactionPool {
 		tx.validate(Weighting::AsTransaction, self.verifier_cache.clone())
 			.map_err(PoolError::InvalidTx)?;
 
+		// Hold the tx if its lock height is within the accepted window ahead of
+		// the next block, it gets added to the pool once the chain reaches it.
+		let next_height = header.height + 1;
+		if !stem
+			&& tx.lock_height() > next_height
+			&& tx.lock_height() <= next_height + self.config.accept_future_lock_window
+		{
+			return self.add_to_pending(src, tx).map(|_| vec![]);
+		}
+
 		// Check the tx lock_time is valid based on current chain state.
 		self.blockchain.verify_tx_lock_height(&tx)?;
 
-		// Check coinbase maturity before we go any further.
-		self.blockchain.verify_coinbase_maturity(&tx)?;
+		// Check coinbase maturity (for the block after the provided header)
+		// before we go any further.
+		let needed_height = self.blockchain.coinbase_mature_height(&tx)?;
+		if needed_height > header.height + 1 {
+			return Err(PoolError::ImmatureCoinbase { needed_height });
+		}
+
+		// Replace-by-fee, evict the txpool txs spending the same inputs (and
+		// their descendants) if this tx pays enough more than they do.
+		// Only for fluff txs, the stempool keeps rejecting conflicts.
+		let mut replaced = vec![];
+		let mut replaced_txs = vec![];
+		let mut prev_entries = None;
+		if !stem && self.config.replace_by_fee {
+			let conflicts = self.txpool.conflicting_transactions(&tx);
+			if !conflicts.is_empty() {
+				let conflicts_fee: u64 = conflicts.iter().map(|x| x.fee()).sum();
+				let required_fee =
+					conflicts_fee.saturating_add(max(1, self.config.replace_by_fee_increment));
+				if tx.fee() < required_fee {
+					return Err(PoolError::ReplacementFeeTooLow(required_fee));
+				}
+				prev_entries = Some(self.txpool.entries.clone());
+				self.txpool.entries.retain(|x| !conflicts.contains(&x.tx));
+				replaced = conflicts.iter().map(|x| x.hash()).collect();
+				replaced_txs = conflicts;
+			}
+		}
 
 		let entry = PoolEntry {
 			src,
@@ -172,7 +255,38 @@ impl TransactionPool {
 				.and_then(|_| self.adapter.stem_tx_accepted(&entry.tx))
 				.is_err()
 		{
-			self.add_to_txpool(entry.clone(), header)?;
+			// Keep a copy of the txpool if we may have to evict txs to make room.
+			let weight = self.txpool.weight() + entry.tx.tx_weight_as_block();
+			if weight > self.config.max_pool_weight && prev_entries.is_none() {
+				prev_entries = Some(self.txpool.entries.clone());
+			}
+
+			let res = self
+				.add_to_txpool(entry.clone(), header)
+				.and_then(|_| self.evict_over_weight(&entry.tx));
+			let evicted = match res {
+				Ok(evicted) => evicted,
+				Err(e) => {
+					// Put back any tx we replaced or evicted.
+					if let Some(entries) = prev_entries {
+						self.txpool.entries = entries;
+					}
+					return Err(e);
+				}
+			};
+			for tx in &replaced_txs {
+				self.adapter.tx_removed(tx, RemovalReason::Replaced);
+			}
+			for tx in &evicted {
+				self.adapter.tx_removed(tx, RemovalReason::Evicted);
+			}
+			if !replaced.is_empty() {
+				debug!(
+					"add_to_pool: tx {} replaced {:?}",
+					entry.tx.hash(),
+					replaced
+				);
+			}
 			self.add_to_reorg_cache(entry.clone());
 			self.adapter.tx_accepted(&entry.tx);
 		}
@@ -182,9 +296,92 @@ impl TransactionPool {
 			self.evict_from_txpool();
 		}
 
+		Ok(replaced)
+	}
+
+	// Record the tx against its source, rejecting it if the source went over
+	// its count or weight limit within the rate limit window.
+	// Local txs are not limited.
+	fn check_source_rate(
+		&mut self,
+		src: &TxSource,
+		tx: &Transaction,
+		now: DateTime<Utc>,
+	) -> Result<(), PoolError> {
+		if src.is_local() {
+			return Ok(());
+		}
+
+		let cutoff = now - Duration::seconds(self.config.source_rate_window_secs as i64);
+		let usage = self
+			.source_usage
+			.entry(src.identifier.clone())
+			.or_insert_with(VecDeque::new);
+		while usage.front().map(|x| x.0 < cutoff).unwrap_or(false) {
+			let _ = usage.pop_front();
+		}
+
+		let weight = tx.tx_weight_as_block();
+		let total_weight: usize = usage.iter().map(|x| x.1).sum();
+		if usage.len() >= self.config.source_max_txs
+			|| total_weight.saturating_add(weight) > self.config.source_max_weight
+		{
+			debug!(
+				"add_to_pool: rate limited tx {} from {}",
+				tx.hash(),
+				src.identifier
+			);
+			return Err(PoolError::SourceRateLimited);
+		}
+		usage.push_back((now, weight));
+
+		// Forget about the sources with no recent tx.
+		let source_usage = &mut self.source_usage;
+		if source_usage.len() > self.config.max_pool_size {
+			source_usage.retain(|_, x| x.back().map(|y| y.0 >= cutoff).unwrap_or(false));
+		}
+		Ok(())
+	}
+
+	fn add_to_pending(&mut self, src: TxSource, tx: Transaction) -> Result<(), PoolError> {
+		if self.pending.iter().any(|x| x.tx == tx) {
+			return Err(PoolError::DuplicateTx);
+		}
+		if self.pending.len() >= self.config.max_pool_size {
+			return Err(PoolError::OverCapacity);
+		}
+		debug!(
+			"add_to_pool: tx {} held until lock height {}",
+			tx.hash(),
+			tx.lock_height()
+		);
+		self.pending.push(PoolEntry {
+			src,
+			tx_at: Utc::now(),
+			tx,
+		});
 		Ok(())
 	}
 
+	// Move the pending txs whose lock height is reached by the block after
+	// the provided header to the pool. The ones failing validation are dropped.
+	// They were counted against the rate limit of their source on arrival.
+	fn promote_pending(&mut self, header: &BlockHeader) {
+		let next_height = header.height + 1;
+		let (ready, pending): (Vec<_>, Vec<_>) = self
+			.pending
+			.drain(..)
+			.partition(|x| x.tx.lock_height() <= next_height);
+		self.pending = pending;
+
+		for entry in ready {
+			let txhash = entry.tx.hash();
+			if let Err(e) = self.add_to_pool_inner(entry.src, entry.tx, false, header, false) {
+				debug!("promote_pending: dropping tx {}, {:?}", txhash, e);
+			}
+		}
+	}
+
 	// Remove the last transaction from the flattened bucket transactions.
 	// No other tx depends on it, it has low fee_to_weight and is unlikely to participate in any cut-through.
 	pub fn evict_from_txpool(&mut self) {
@@ -202,11 +399,170 @@ impl TransactionPool {
 					.filter(|x| x.tx != *evictable_transaction)
 					.map(|x| x.clone())
 					.collect::<Vec<_>>();
+				self.adapter
+					.tx_removed(evictable_transaction, RemovalReason::Evicted);
 			}
 			None => (),
 		}
 	}
 
+	// Evict the lowest fee_to_weight txs (along with the txs depending on them)
+	// until the txpool fits in max_pool_weight again.
+	// The new tx is rejected if it is below this eviction floor itself.
+	// Returns the evicted txs.
+	fn evict_over_weight(&mut self, tx: &Transaction) -> Result<Vec<Transaction>, PoolError> {
+		let mut all_evicted = vec![];
+		while self.txpool.weight() > self.config.max_pool_weight {
+			let bucket_transactions = self.txpool.bucket_transactions(Weighting::NoLimit);
+			let evictable_transaction = match bucket_transactions.last() {
+				Some(x) => x.clone(),
+				None => break,
+			};
+			let evicted = self.txpool.remove_with_dependents(&evictable_transaction);
+			debug!(
+				"evict_over_weight: evicted {} txs, txpool weight {}",
+				evicted.len(),
+				self.txpool.weight()
+			);
+			if evicted.contains(tx) {
+				return Err(PoolError::OverCapacity);
+			}
+			all_evicted.extend(evicted);
+		}
+		Ok(all_evicted)
+	}
+
+	/// Fluff the stempool txs whose embargo timer expired by now, moving them
+	/// to the txpool where they get broadcast and become mineable.
+	/// Until then stem txs stay private to the stempool.
+	/// Returns the number of txs successfully fluffed.
+	pub fn fluff_all_expired(
+		&mut self,
+		now: DateTime<Utc>,
+		embargo_secs: u16,
+	) -> Result<usize, PoolError> {
+		let cutoff = now.timestamp() - embargo_secs as i64;
+		let expired_entries: Vec<_> = self
+			.stempool
+			.entries
+			.iter()
+			.filter(|x| x.tx_at.timestamp() < cutoff)
+			.cloned()
+			.collect();
+
+		if expired_entries.is_empty() {
+			return Ok(0);
+		}
+
+		debug!(
+			"fluff_all_expired: Found {} expired txs.",
+			expired_entries.len()
+		);
+
+		let header = self.chain_head()?;
+
+		let src = TxSource {
+			debug_name: "embargo_expired".to_string(),
+			identifier: LOCAL_SOURCE_IDENTIFIER.to_string(),
+		};
+
+		let mut fluffed = 0;
+		for entry in expired_entries {
+			let txhash = entry.tx.hash();
+			match self.add_to_pool(src.clone(), entry.tx, false, &header) {
+				Ok(_) => {
+					info!(
+						"fluff_all_expired: embargo expired for {}, fluffed successfully.",
+						txhash
+					);
+					fluffed += 1;
+				}
+				Err(e) => warn!(
+					"fluff_all_expired: failed to fluff expired tx {}, {:?}",
+					txhash, e
+				),
+			};
+		}
+		Ok(fluffed)
+	}
+
+	/// Remove the txs that have been sitting in the pool for longer than the ttl
+	/// by now, along with the txs depending on them. Catches the txs that will
+	/// never get mined, like the ones conflicting with a tx confirmed on a fork.
+	/// Returns the number of txs removed.
+	pub fn remove_expired(&mut self, now: DateTime<Utc>, ttl: Duration) -> usize {
+		let cutoff = now - ttl;
+		let mut removed = vec![];
+		for pool in vec![&mut self.txpool, &mut self.stempool] {
+			let expired: Vec<_> = pool
+				.entries
+				.iter()
+				.filter(|x| x.tx_at < cutoff)
+				.map(|x| x.tx.clone())
+				.collect();
+			for tx in expired {
+				removed.extend(pool.remove_with_dependents(&tx));
+			}
+		}
+		if !removed.is_empty() {
+			debug!("remove_expired: removed {} expired txs", removed.len());
+		}
+		for tx in &removed {
+			self.adapter.tx_removed(tx, RemovalReason::Expired);
+		}
+		removed.len()
+	}
+
+	/// Save the txpool txs to the file at path, so they can be restored with
+	/// load_from_disk after a restart.
+	pub fn save_to_disk<P: AsRef<Path>>(&self, path: P) -> Result<(), PoolError> {
+		let txs = self.txpool.all_transactions();
+		if let Some(parent) = path.as_ref().parent() {
+			fs::create_dir_all(parent)
+				.map_err(|e| PoolError::Other(format!("save_to_disk: {:?}", e)))?;
+		}
+		let file =
+			File::create(path).map_err(|e| PoolError::Other(format!("save_to_disk: {:?}", e)))?;
+		let mut writer = BufWriter::new(file);
+		ser::serialize(&mut writer, &txs)
+			.map_err(|e| PoolError::Other(format!("save_to_disk: {:?}", e)))?;
+		writer
+			.flush()
+			.map_err(|e| PoolError::Other(format!("save_to_disk: {:?}", e)))?;
+		debug!("save_to_disk: saved {} txs", txs.len());
+		Ok(())
+	}
+
+	/// Load the txs saved with save_to_disk back in the txpool, validating them
+	/// against the provided header. The ones no longer valid (spent, immature,
+	/// ...) are discarded. Returns the number of txs restored.
+	pub fn load_from_disk<P: AsRef<Path>>(
+		&mut self,
+		path: P,
+		header: &BlockHeader,
+	) -> Result<usize, PoolError> {
+		let file =
+			File::open(path).map_err(|e| PoolError::Other(format!("load_from_disk: {:?}", e)))?;
+		let txs: Vec<Transaction> = ser::deserialize(&mut BufReader::new(file))
+			.map_err(|e| PoolError::Other(format!("load_from_disk: {:?}", e)))?;
+
+		let src = TxSource {
+			debug_name: "restored".to_string(),
+			identifier: LOCAL_SOURCE_IDENTIFIER.to_string(),
+		};
+
+		let mut restored = 0;
+		for tx in txs {
+			let txhash = tx.hash();
+			match self.add_to_pool(src.clone(), tx, false, header) {
+				Ok(_) => restored += 1,
+				Err(e) => debug!("load_from_disk: discarding tx {}, {:?}", txhash, e),
+			}
+		}
+		debug!("load_from_disk: restored {} txs", restored);
+		Ok(restored)
+	}
+
 	// Old txs will "age out" after 30 mins.
 	pub fn truncate_reorg_cache(&mut self, cutoff: DateTime<Utc>) {
 		let mut cache = self.reorg_cache.write();
@@ -236,8 +592,13 @@ impl TransactionPool {
 	}
 
 	/// Reconcile the transaction pool (both txpool and stempool) against the
-	/// provided block.
-	pub fn reconcile_block(&mut self, block: &Block) -> Result<(), PoolError> {
+	/// provided block. Returns the txs that got orphaned, removed because they
+	/// spend an output of a pool tx that was removed without being mined, so a
+	/// relay can drop them or request their new parents.
+	pub fn reconcile_block(&mut self, block: &Block) -> Result<Vec<Transaction>, PoolError> {
+		let prev_txpool = self.txpool.entries.clone();
+		let prev_stempool = self.stempool.entries.clone();
+
 		// First reconcile the txpool.
 		self.txpool.reconcile_block(block);
 		self.txpool.reconcile(None, &block.header)?;
@@ -249,7 +610,40 @@ impl TransactionPool {
 			self.stempool.reconcile(txpool_tx, &block.header)?;
 		}
 
-		Ok(())
+		// Txs with all their kernels in the block were mined, the others
+		// removed are now invalid, either conflicting with the block or
+		// spending the outputs of another such tx (orphaned).
+		let mut removed = removed_txs(prev_txpool, &self.txpool);
+		removed.extend(removed_txs(prev_stempool, &self.stempool));
+		let is_mined = |tx: &Transaction| tx.kernels().iter().all(|k| block.kernels().contains(k));
+		let lost_outputs: HashSet<Commitment> = removed
+			.iter()
+			.filter(|tx| !is_mined(tx))
+			.flat_map(|tx| tx.outputs().iter().map(|out| out.commitment()))
+			.collect();
+		let mut orphaned = vec![];
+		for tx in removed {
+			let reason = if is_mined(&tx) {
+				RemovalReason::Mined
+			} else if tx
+				.inputs()
+				.iter()
+				.any(|input| lost_outputs.contains(&input.commitment()))
+			{
+				RemovalReason::Orphaned
+			} else {
+				RemovalReason::Conflict
+			};
+			self.adapter.tx_removed(&tx, reason);
+			if reason == RemovalReason::Orphaned {
+				orphaned.push(tx);
+			}
+		}
+
+		// The chain may have reached the lock height of pending txs.
+		self.promote_pending(&block.header);
+
+		Ok(orphaned)
 	}
 
 	/// Retrieve individual transaction for the given kernel hash.
@@ -257,6 +651,55 @@ impl TransactionPool {
 		self.txpool.retrieve_tx_by_kernel_hash(hash)
 	}
 
+	/// Retrieve the pool tx (txpool or stempool) with a kernel matching the
+	/// given kernel excess.
+	pub fn get_by_kernel_excess(&self, excess: &Commitment) -> Option<Transaction> {
+		self.txpool
+			.get_by_kernel_excess(excess)
+			.or_else(|| self.stempool.get_by_kernel_excess(excess))
+	}
+
+	/// Whether a pool tx (txpool or stempool) has a kernel matching the given
+	/// kernel excess.
+	pub fn contains_kernel(&self, excess: &Commitment) -> bool {
+		self.get_by_kernel_excess(excess).is_some()
+	}
+
+	/// Kernel hashes of the txpool txs the provided tx spends outputs from.
+	pub fn dependencies_of(&self, tx: &Transaction) -> Vec<Hash> {
+		self.txpool.dependencies_of(tx)
+	}
+
+	/// Aggregate the pool txs (txpool or stempool) identified by the provided
+	/// kernel hashes into a single tx, cutting through the outputs created
+	/// and spent by these txs. The resulting tx is validated (kernel sums
+	/// included) before being returned.
+	pub fn aggregate_transactions(&self, txs: &[Hash]) -> Result<Transaction, PoolError> {
+		let mut found: Vec<Transaction> = vec![];
+		for hash in txs {
+			let tx = self
+				.txpool
+				.retrieve_tx_by_kernel_hash(*hash)
+				.or_else(|| self.stempool.retrieve_tx_by_kernel_hash(*hash))
+				.ok_or_else(|| PoolError::Other(format!("tx with kernel {} not in pool", hash)))?;
+			if !found.contains(&tx) {
+				found.push(tx);
+			}
+		}
+
+		let tx = transaction::aggregate(found)?;
+		tx.validate(Weighting::NoLimit, self.verifier_cache.clone())?;
+		Ok(tx)
+	}
+
+	/// Kernel excess commitments of every tx in the pool (txpool and stempool).
+	/// Used to announce our kernel inventory to peers.
+	pub fn all_kernels(&self) -> Vec<Commitment> {
+		let mut kernels = self.txpool.all_kernels();
+		kernels.extend(self.stempool.all_kernels());
+		kernels
+	}
+
 	/// Retrieve all transactions matching the provided "compact block"
 	/// based on the kernel set.
 	/// Note: we only look in the txpool for this (stempool is under embargo).
@@ -304,7 +747,80 @@ impl TransactionPool {
 	/// Returns a vector of transactions from the txpool so we can build a
 	/// block from them.
 	pub fn prepare_mineable_transactions(&self) -> Result<Vec<Transaction>, PoolError> {
-		self.txpool
-			.prepare_mineable_transactions(self.config.mineable_max_weight)
+		self.prepare_mineable_transactions_with_limit(self.config.mineable_max_weight)
+	}
+
+	/// Returns a vector of transactions from the txpool fitting in a block of
+	/// at most max_weight (capped by the max block weight), coinbase included.
+	/// Allows miners to reserve some of the block weight.
+	pub fn prepare_mineable_transactions_with_limit(
+		&self,
+		max_weight: usize,
+	) -> Result<Vec<Transaction>, PoolError> {
+		self.txpool.prepare_mineable_transactions(max_weight)
+	}
+
+	/// Position (0 based) of the tx with the provided kernel excess in the
+	/// mineable selection for the next block, as `prepare_mineable_transactions`
+	/// builds it. None if the tx wouldn't make it into the block (or isn't in
+	/// the txpool).
+	pub fn inclusion_rank(&self, kernel: &Commitment) -> Option<usize> {
+		self.prepare_mineable_transactions()
+			.ok()?
+			.iter()
+			.position(|tx| tx.kernels().iter().any(|k| k.excess() == *kernel))
 	}
+
+	/// Estimate the fee per weight unit (the transaction weight, like
+	/// accept_fee_base) a tx should pay to likely get mined within the next
+	/// target_blocks blocks. The txpool txs are packed by decreasing fee rate
+	/// into blocks of max block weight (the block weight only measuring the
+	/// room a tx takes), if they overflow the target blocks the estimate is the
+	/// median fee rate of the last target block. Otherwise, and for an empty
+	/// pool, any accepted tx should make it and the pool minimum fee rate is
+	/// returned.
+	pub fn estimate_fee_per_weight(&self, target_blocks: u32) -> u64 {
+		let floor = max(self.config.accept_fee_base, 1);
+		let target_blocks = max(target_blocks, 1) as usize;
+
+		let mut txs = self.txpool.all_transactions();
+		// Same fee rate as below, with more precision.
+		txs.sort_by_key(|x| Reverse(x.fee_to_weight()));
+
+		// Weight available to the txs in a block, the coinbase takes the rest.
+		let capacity =
+			global::max_block_weight().saturating_sub(consensus::coinbase_block_weight());
+
+		let mut block = 1;
+		let mut block_weight = 0;
+		let mut last_block_rates = vec![];
+		for tx in txs {
+			let weight = tx.tx_weight_as_block();
+			if block_weight > 0 && block_weight + weight > capacity {
+				block += 1;
+				block_weight = 0;
+			}
+			if block > target_blocks {
+				// The pool overflows the target blocks, compete with the txs
+				// making it in the last one.
+				last_block_rates.sort();
+				let median = last_block_rates[last_block_rates.len() / 2];
+				return max(median, floor);
+			}
+			block_weight += weight;
+			if block == target_blocks {
+				last_block_rates.push(tx.fee() / tx.tx_weight() as u64);
+			}
+		}
+		floor
+	}
+}
+
+// The txs of the provided (previous) entries no longer in the pool.
+fn removed_txs(prev_entries: Vec<PoolEntry>, pool: &Pool) -> Vec<Transaction> {
+	prev_entries
+		.into_iter()
+		.map(|x| x.tx)
+		.filter(|x| !pool.contains_tx(x.hash()))
+		.collect()
 }
@@ -102,11 +102,48 @@ pub struct PoolConfig {
 	#[serde = "default_max_stempool_size"]
 	pub max_stempool_size: usize,
 
+	/// Maximum total weight of the transactions in the pool (using block
+	/// weighing). The lowest fee transactions are evicted beyond it.
+	#[serde(default = "default_max_pool_weight")]
+	pub max_pool_weight: usize,
+
 	/// Maximum total weight of transactions that can get selected to build a
 	/// block from. Allows miners to restrict the maximum weight of their
 	/// blocks.
 	#[serde = "default_mineable_max_weight"]
 	pub mineable_max_weight: usize,
+
+	/// Allow a transaction to replace the txpool transactions spending the
+	/// same inputs if it pays a higher fee (replace-by-fee).
+	#[serde(default = "default_replace_by_fee")]
+	pub replace_by_fee: bool,
+
+	/// Minimum fee increment over the fees of the replaced transactions for
+	/// a replace-by-fee transaction to be accepted.
+	#[serde(default = "default_replace_by_fee_increment")]
+	pub replace_by_fee_increment: u64,
+
+	/// Number of blocks the lock height of a transaction can be ahead of the
+	/// next block height for it to be held as pending instead of rejected.
+	/// Pending transactions are moved to the txpool once the chain reaches
+	/// their lock height. 0 disables it.
+	#[serde(default = "default_accept_future_lock_window")]
+	pub accept_future_lock_window: u64,
+
+	/// Time window (in seconds) over which the transactions received from a
+	/// single peer are rate limited.
+	#[serde(default = "default_source_rate_window_secs")]
+	pub source_rate_window_secs: u64,
+
+	/// Maximum number of transactions accepted from a single peer within the
+	/// rate limit window.
+	#[serde(default = "default_source_max_txs")]
+	pub source_max_txs: usize,
+
+	/// Maximum total weight (using block weighing) of the transactions
+	/// accepted from a single peer within the rate limit window.
+	#[serde(default = "default_source_max_weight")]
+	pub source_max_weight: usize,
 }
 
 impl Default for PoolConfig {
@@ -115,7 +152,14 @@ impl Default for PoolConfig {
 			accept_fee_base: default_accept_fee_base(),
 			max_pool_size: default_max_pool_size(),
 			max_stempool_size: default_max_stempool_size(),
+			max_pool_weight: default_max_pool_weight(),
 			mineable_max_weight: default_mineable_max_weight(),
+			replace_by_fee: default_replace_by_fee(),
+			replace_by_fee_increment: default_replace_by_fee_increment(),
+			accept_future_lock_window: default_accept_future_lock_window(),
+			source_rate_window_secs: default_source_rate_window_secs(),
+			source_max_txs: default_source_max_txs(),
+			source_max_weight: default_source_max_weight(),
 		}
 	}
 }
@@ -129,9 +173,30 @@ fn default_max_pool_size() -> usize {
 fn default_max_stempool_size() -> usize {
 	50_000
 }
+fn default_max_pool_weight() -> usize {
+	100 * global::max_block_weight()
+}
 fn default_mineable_max_weight() -> usize {
 	global::max_block_weight()
 }
+fn default_replace_by_fee() -> bool {
+	false
+}
+fn default_replace_by_fee_increment() -> u64 {
+	consensus::MILLI_GRIN
+}
+fn default_accept_future_lock_window() -> u64 {
+	0
+}
+fn default_source_rate_window_secs() -> u64 {
+	60
+}
+fn default_source_max_txs() -> usize {
+	1_000
+}
+fn default_source_max_weight() -> usize {
+	10 * global::max_block_weight()
+}
 
 /// Represents a single entry in the pool.
 /// A single (possibly aggregated) transaction.
@@ -145,6 +210,10 @@ pub struct PoolEntry {
 	pub tx: Transaction,
 }
 
+/// Identifier of a tx source that is not a peer: the node itself or a local
+/// wallet pushing through the api.
+pub const LOCAL_SOURCE_IDENTIFIER: &str = "?.?.?.?";
+
 /// Placeholder: the data representing where we heard about a tx from.
 ///
 /// Used to make decisions based on transaction acceptance priority from
@@ -161,6 +230,14 @@ pub struct TxSource {
 	pub identifier: String,
 }
 
+impl TxSource {
+	/// Is this tx submitted locally (or by the node itself) rather than
+	/// received from a peer? Local sources have no peer identifier.
+	pub fn is_local(&self) -> bool {
+		self.identifier == LOCAL_SOURCE_IDENTIFIER
+	}
+}
+
 /// Possible errors when interacting with the transaction pool.
 #[derive(Debug, Fail, PartialEq)]
 pub enum PoolError {
@@ -180,9 +257,13 @@ pub enum PoolError {
 	/// greater than height of current block
 	#[fail(display = "Immature transaction")]
 	ImmatureTransaction,
-	/// Attempt to spend a coinbase output before it has sufficiently matured.
-	#[fail(display = "Immature coinbase")]
-	ImmatureCoinbase,
+	/// Attempt to spend a coinbase output before it has sufficiently matured,
+	/// with the height of the first block that can include the tx.
+	#[fail(display = "Immature coinbase, mature at height {}", needed_height)]
+	ImmatureCoinbase {
+		/// First block height the coinbase outputs can be spent at
+		needed_height: u64,
+	},
 	/// Problem propagating a stem tx to the next Dandelion relay node.
 	#[fail(display = "Dandelion error")]
 	DandelionError,
@@ -198,6 +279,17 @@ pub enum PoolError {
 	/// Attempt to add a duplicate tx to the pool.
 	#[fail(display = "Duplicate tx")]
 	DuplicateTx,
+	/// Replace-by-fee tx not paying enough to replace the conflicting txs,
+	/// with the minimum fee required.
+	#[fail(display = "Replacement fee too low, {} required", _0)]
+	ReplacementFeeTooLow(u64),
+	/// The source of the tx sent more txs than allowed within the rate limit
+	/// window.
+	#[fail(display = "Source rate limited")]
+	SourceRateLimited,
+	/// Attempt to add a tx that would form a dependency cycle with pool txs.
+	#[fail(display = "Dependency cycle")]
+	DependencyCycle,
 	/// Other kinds of error (not yet pulled out into meaningful errors).
 	#[fail(display = "General pool error {}", _0)]
 	Other(String),
@@ -229,9 +321,10 @@ impl From<committed::Error> for PoolError {
 
 /// Interface that the pool requires from a blockchain implementation.
 pub trait BlockChain: Sync + Send {
-	/// Verify any coinbase outputs being spent
-	/// have matured sufficiently.
-	fn verify_coinbase_maturity(&self, tx: &transaction::Transaction) -> Result<(), PoolError>;
+	/// Height of the first block the tx can be included in as far as the
+	/// maturity of the coinbase outputs it spends is concerned, 0 if it spends
+	/// no coinbase output.
+	fn coinbase_mature_height(&self, tx: &transaction::Transaction) -> Result<u64, PoolError>;
 
 	/// Verify any coinbase outputs being spent
 	/// have matured sufficiently.
@@ -245,6 +338,25 @@ pub trait BlockChain: Sync + Send {
 	fn get_block_sums(&self, hash: &Hash) -> Result<BlockSums, PoolError>;
 }
 
+/// Why a tx was removed from the pool (txpool or stempool).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum RemovalReason {
+	/// The tx was included in a block.
+	Mined,
+	/// The tx is not valid anymore against the chain or the txpool, like when
+	/// one of its inputs got spent by another tx.
+	Conflict,
+	/// The tx spends an output of a pool tx removed without being mined, the
+	/// output it depends on doesn't exist anymore.
+	Orphaned,
+	/// The tx sat in the pool for longer than the ttl.
+	Expired,
+	/// The tx was replaced by a tx paying a higher fee (replace-by-fee).
+	Replaced,
+	/// The tx was evicted to make room in a full pool.
+	Evicted,
+}
+
 /// Bridge between the transaction pool and the rest of the system. Handles
 /// downstream processing of valid transactions by the rest of the system, most
 /// importantly the broadcasting of transactions to our peers.
@@ -254,6 +366,9 @@ pub trait PoolAdapter: Send + Sync {
 
 	/// The stem transaction pool has accepted this transactions as valid.
 	fn stem_tx_accepted(&self, tx: &transaction::Transaction) -> Result<(), PoolError>;
+
+	/// The transaction pool (txpool or stempool) dropped this transaction.
+	fn tx_removed(&self, _tx: &transaction::Transaction, _reason: RemovalReason) {}
 }
 
 /// Dummy adapter used as a placeholder for real implementations
@@ -20,10 +20,13 @@ use self::core::core::{Block, BlockHeader, Transaction};
 use self::core::libtx;
 use self::core::pow::Difficulty;
 use self::keychain::{ExtKeychain, Keychain};
+use self::pool::PoolEntry;
 use self::util::RwLock;
 use crate::common::*;
+use chrono::Utc;
 use grin_core as core;
 use grin_keychain as keychain;
+use grin_pool as pool;
 use grin_util as util;
 use std::sync::Arc;
 
@@ -110,6 +113,34 @@ fn test_transaction_pool_block_building() {
 				.unwrap();
 
 			assert_eq!(write_pool.total_size(), 5);
+
+			// All five kernels are announced.
+			let kernels = write_pool.all_kernels();
+			assert_eq!(kernels.len(), 5);
+			for tx in &[&root_tx_1, &root_tx_2, &root_tx_3, &child_tx_1, &child_tx_2] {
+				assert!(kernels.contains(&tx.kernels()[0].excess()));
+			}
+
+			// Aggregating a tx with its child cuts through the intermediate output.
+			let agg_tx = write_pool
+				.aggregate_transactions(&[
+					root_tx_1.kernels()[0].hash(),
+					child_tx_1.kernels()[0].hash(),
+				])
+				.unwrap();
+			assert_eq!(agg_tx.inputs(), root_tx_1.inputs());
+			assert_eq!(agg_tx.outputs(), child_tx_1.outputs());
+			assert!(agg_tx
+				.outputs()
+				.iter()
+				.all(|x| x.commitment() != root_tx_1.outputs()[0].commitment()));
+			assert_eq!(agg_tx.kernels().len(), 2);
+			assert!(agg_tx.kernels().contains(&root_tx_1.kernels()[0]));
+			assert!(agg_tx.kernels().contains(&child_tx_1.kernels()[0]));
+			assert_eq!(agg_tx.fee(), root_tx_1.fee() + child_tx_1.fee());
+
+			// The pool itself is left untouched.
+			assert_eq!(write_pool.total_size(), 5);
 		}
 
 		let txs = pool.read().prepare_mineable_transactions().unwrap();
@@ -139,3 +170,81 @@ fn test_transaction_pool_block_building() {
 	// Cleanup db directory
 	clean_output_dir(db_root.clone());
 }
+
+/// Test the mineable txs are in dependency order (parents first) even when a
+/// child ends up in the pool ahead of its parent.
+#[test]
+fn test_block_building_child_inserted_first() {
+	util::init_test_logger();
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_block_building_child_first".to_string();
+	clean_output_dir(db_root.clone());
+
+	{
+		let mut chain = ChainAdapter::init(db_root.clone()).unwrap();
+
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+		let add_block =
+			|prev_header: BlockHeader, txs: Vec<Transaction>, chain: &mut ChainAdapter| {
+				let height = prev_header.height + 1;
+				let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+				let fee = txs.iter().map(|x| x.fee()).sum();
+				let reward = libtx::reward::output(
+					&keychain,
+					&libtx::ProofBuilder::new(&keychain),
+					&key_id,
+					fee,
+					false,
+					height,
+				)
+				.unwrap();
+				let mut block = Block::new(&prev_header, txs, Difficulty::min(), reward).unwrap();
+				block.header.prev_root = prev_header.hash();
+				chain.update_db_for_block(&block);
+				block
+			};
+
+		let block = add_block(BlockHeader::default(), vec![], &mut chain);
+		let header = block.header;
+
+		let initial_tx = test_transaction_spending_coinbase(&keychain, &header, vec![10, 20]);
+		let block = add_block(header, vec![initial_tx], &mut chain);
+		let header = block.header;
+
+		let mut pool = test_setup(Arc::new(chain.clone()), verifier_cache);
+
+		let parent_tx = test_transaction(&keychain, vec![10], vec![9]);
+		let child_tx = test_transaction(&keychain, vec![9], vec![1]);
+		let other_tx = test_transaction(&keychain, vec![20], vec![18]);
+
+		// The child is pushed ahead of its parent, bypassing add_to_pool.
+		for tx in vec![&child_tx, &other_tx, &parent_tx] {
+			pool.txpool.entries.push(PoolEntry {
+				src: test_source(),
+				tx_at: Utc::now(),
+				tx: tx.clone(),
+			});
+		}
+
+		assert_eq!(
+			pool.dependencies_of(&child_tx),
+			vec![parent_tx.kernels()[0].hash()]
+		);
+		assert!(pool.dependencies_of(&parent_tx).is_empty());
+		assert!(pool.dependencies_of(&other_tx).is_empty());
+
+		let txs = pool.prepare_mineable_transactions().unwrap();
+		assert_eq!(txs.len(), 3);
+		let parent_pos = txs.iter().position(|x| *x == parent_tx).unwrap();
+		let child_pos = txs.iter().position(|x| *x == child_tx).unwrap();
+		assert!(parent_pos < child_pos);
+
+		// The resulting block is valid.
+		let block = add_block(header, txs, &mut chain);
+		assert_eq!(block.kernels().len(), 4);
+	}
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
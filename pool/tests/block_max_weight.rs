@@ -18,11 +18,12 @@ pub mod common;
 
 use self::core::core::hash::Hashed;
 use self::core::core::verifier_cache::LruVerifierCache;
-use self::core::core::{Block, BlockHeader, Transaction};
+use self::core::core::{Block, BlockHeader, Transaction, TransactionBody};
 use self::core::global;
 use self::core::libtx;
 use self::core::pow::Difficulty;
 use self::keychain::{ExtKeychain, Keychain};
+use self::util::secp::pedersen::Commitment;
 use self::util::RwLock;
 use crate::common::*;
 use grin_core as core;
@@ -110,6 +111,8 @@ fn test_block_building_max_weight() {
 			[1125, 1000, 250, 875, 750]
 		);
 
+		let kernels: Vec<Commitment> = txs.iter().map(|x| x.kernels()[0].excess()).collect();
+
 		// Populate our txpool with the txs.
 		{
 			let mut write_pool = pool.write();
@@ -139,6 +142,16 @@ fn test_block_building_max_weight() {
 			[1125, 1000, 875]
 		);
 
+		// Position of each of our txs in the next block, the highest fee rate
+		// first, None for the ones that don't fit.
+		assert_eq!(
+			kernels
+				.iter()
+				.map(|k| pool.read().inclusion_rank(k))
+				.collect::<Vec<_>>(),
+			[Some(0), Some(1), None, Some(2), None]
+		);
+
 		let block = add_block(header, txs, &mut chain);
 
 		// Check contents of the block itself (including coinbase reward).
@@ -161,3 +174,212 @@ fn test_block_building_max_weight() {
 	// Cleanup db directory
 	clean_output_dir(db_root.clone());
 }
+
+#[test]
+fn test_block_building_fee_rate_ordering() {
+	util::init_test_logger();
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_block_building_fee_rate_ordering".to_string();
+	clean_output_dir(db_root.clone());
+
+	{
+		let mut chain = ChainAdapter::init(db_root.clone()).unwrap();
+
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+		let add_block =
+			|prev_header: BlockHeader, txs: Vec<Transaction>, chain: &mut ChainAdapter| {
+				let height = prev_header.height + 1;
+				let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+				let fee = txs.iter().map(|x| x.fee()).sum();
+				let reward = libtx::reward::output(
+					&keychain,
+					&libtx::ProofBuilder::new(&keychain),
+					&key_id,
+					fee,
+					false,
+					height,
+				)
+				.unwrap();
+				let mut block = Block::new(&prev_header, txs, Difficulty::min(), reward).unwrap();
+				block.header.prev_root = prev_header.hash();
+				chain.update_db_for_block(&block);
+				block
+			};
+
+		let block = add_block(BlockHeader::default(), vec![], &mut chain);
+		let header = block.header;
+
+		let initial_tx = test_transaction_spending_coinbase(
+			&keychain,
+			&header,
+			vec![100, 200, 300, 400, 500, 600],
+		);
+		let block = add_block(header, vec![initial_tx], &mut chain);
+		let header = block.header;
+
+		let pool = RwLock::new(test_setup(Arc::new(chain.clone()), verifier_cache));
+
+		let low_1 = test_transaction(&keychain, vec![100], vec![99]);
+		let low_2 = test_transaction(&keychain, vec![200], vec![199]);
+		let parent = test_transaction(&keychain, vec![300], vec![280]);
+		// Too low a fee to be aggregated with its parent.
+		let child = test_transaction(&keychain, vec![280], vec![279]);
+		// Pays for its parents (child pays for parent).
+		let grandchild = test_transaction(&keychain, vec![279], vec![249]);
+		let high_1 = test_transaction(&keychain, vec![400], vec![370]);
+		let high_2 = test_transaction(&keychain, vec![500], vec![470]);
+		let high_3 = test_transaction(&keychain, vec![600], vec![570]);
+
+		// Each tx takes 25 of the block weight, the txs do not all fit in a block.
+		let txs = vec![
+			low_1.clone(),
+			low_2.clone(),
+			parent.clone(),
+			child.clone(),
+			grandchild.clone(),
+			high_1.clone(),
+			high_2.clone(),
+			high_3.clone(),
+		];
+		assert_eq!(
+			txs.iter().map(|x| x.tx_weight_as_block()).sum::<usize>(),
+			8 * 25
+		);
+		assert!(8 * 25 > global::max_block_weight());
+		assert_eq!(
+			txs.iter()
+				.map(|x| x.fee_to_weight_as_block())
+				.collect::<Vec<_>>(),
+			[40, 40, 800, 40, 1200, 1200, 1200, 1200]
+		);
+
+		{
+			let mut write_pool = pool.write();
+			for tx in txs {
+				write_pool
+					.add_to_pool(test_source(), tx, false, &header)
+					.unwrap();
+			}
+		}
+		assert_eq!(pool.read().total_size(), 8);
+
+		let txs = pool.read().prepare_mineable_transactions().unwrap();
+
+		// High fee txs first, the low fee child and its parent are pulled in by
+		// the grandchild, always ordered parent first.
+		assert_eq!(txs, [high_1, high_2, high_3, parent, child, grandchild]);
+		let block = add_block(header, txs, &mut chain);
+
+		// Only the low fee txs are left in the pool.
+		{
+			let mut write_pool = pool.write();
+			write_pool.reconcile_block(&block).unwrap();
+			assert_eq!(write_pool.total_size(), 2);
+			let remaining = write_pool.txpool.all_transactions();
+			assert!(remaining.contains(&low_1));
+			assert!(remaining.contains(&low_2));
+		}
+	}
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
+
+#[test]
+fn test_block_building_weight_limit() {
+	util::init_test_logger();
+	global::set_mining_mode(global::ChainTypes::AutomatedTesting);
+
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_block_building_weight_limit".to_string();
+	clean_output_dir(db_root.clone());
+
+	{
+		let mut chain = ChainAdapter::init(db_root.clone()).unwrap();
+
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+		let add_block =
+			|prev_header: BlockHeader, txs: Vec<Transaction>, chain: &mut ChainAdapter| {
+				let height = prev_header.height + 1;
+				let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+				let fee = txs.iter().map(|x| x.fee()).sum();
+				let reward = libtx::reward::output(
+					&keychain,
+					&libtx::ProofBuilder::new(&keychain),
+					&key_id,
+					fee,
+					false,
+					height,
+				)
+				.unwrap();
+				let mut block = Block::new(&prev_header, txs, Difficulty::min(), reward).unwrap();
+				block.header.prev_root = prev_header.hash();
+				chain.update_db_for_block(&block);
+				block
+			};
+
+		let block = add_block(BlockHeader::default(), vec![], &mut chain);
+		let header = block.header;
+
+		let initial_tx = test_transaction_spending_coinbase(
+			&keychain,
+			&header,
+			vec![100, 200, 300, 400, 500, 600, 700],
+		);
+		let block = add_block(header, vec![initial_tx], &mut chain);
+		let header = block.header;
+
+		let pool = RwLock::new(test_setup(Arc::new(chain.clone()), verifier_cache));
+
+		// Independent txs, each taking 25 of the block weight.
+		// More than a block can hold.
+		{
+			let mut write_pool = pool.write();
+			for value in vec![100, 200, 300, 400, 500, 600, 700] {
+				let tx = test_transaction(&keychain, vec![value], vec![value - 10]);
+				write_pool
+					.add_to_pool(test_source(), tx, false, &header)
+					.unwrap();
+			}
+		}
+		assert_eq!(pool.read().total_size(), 7);
+		assert!(7 * 25 > global::max_block_weight());
+
+		// Without a limit we fill the whole block.
+		let txs = pool
+			.read()
+			.prepare_mineable_transactions_with_limit(usize::max_value())
+			.unwrap();
+		assert_eq!(txs.len(), 5);
+
+		// Reserve some of the block weight.
+		let max_weight = 100;
+		let txs = pool
+			.read()
+			.prepare_mineable_transactions_with_limit(max_weight)
+			.unwrap();
+		assert_eq!(txs.len(), 3);
+
+		let block = add_block(header, txs, &mut chain);
+		let block_weight = TransactionBody::weight_as_block(
+			block.inputs().len(),
+			block.outputs().len(),
+			block.kernels().len(),
+		);
+		assert!(block_weight <= max_weight);
+
+		// The txs that did not fit are left in the pool.
+		{
+			let mut write_pool = pool.write();
+			write_pool.reconcile_block(&block).unwrap();
+			assert_eq!(write_pool.total_size(), 4);
+		}
+	}
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
@@ -17,6 +17,7 @@ pub mod common;
 use self::core::core::hash::Hash;
 use self::core::core::verifier_cache::LruVerifierCache;
 use self::core::core::{BlockHeader, BlockSums, Transaction};
+use self::core::global;
 use self::keychain::{ExtKeychain, Keychain};
 use self::pool::types::{BlockChain, PoolError};
 use self::util::RwLock;
@@ -53,9 +54,9 @@ impl BlockChain for CoinbaseMaturityErrorChainAdapter {
 		unimplemented!();
 	}
 
-	// Returns an ImmatureCoinbase for every tx we pass in.
-	fn verify_coinbase_maturity(&self, _tx: &Transaction) -> Result<(), PoolError> {
-		Err(PoolError::ImmatureCoinbase)
+	// Every tx we pass in spends a coinbase freshly mined at height 1.
+	fn coinbase_mature_height(&self, _tx: &Transaction) -> Result<u64, PoolError> {
+		Ok(1 + global::coinbase_maturity())
 	}
 
 	// Mocking this out for these tests.
@@ -79,8 +80,19 @@ fn test_coinbase_maturity() {
 		let mut write_pool = pool.write();
 		let tx = test_transaction(&keychain, vec![50], vec![49]);
 		match write_pool.add_to_pool(test_source(), tx.clone(), true, &BlockHeader::default()) {
-			Err(PoolError::ImmatureCoinbase) => {}
+			Err(PoolError::ImmatureCoinbase { .. }) => {}
 			_ => panic!("Expected an immature coinbase error here."),
 		}
+
+		// Still immature in the block just before maturity, the error tells
+		// when it can be spent.
+		let mut header = BlockHeader::default();
+		header.height = global::coinbase_maturity() - 1;
+		assert_eq!(
+			write_pool.add_to_pool(test_source(), tx.clone(), false, &header),
+			Err(PoolError::ImmatureCoinbase {
+				needed_height: 1 + global::coinbase_maturity()
+			})
+		);
 	}
 }
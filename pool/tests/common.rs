@@ -138,8 +138,8 @@ impl BlockChain for ChainAdapter {
 
 	// Mocking this check out for these tests.
 	// We will test the Merkle proof verification logic elsewhere.
-	fn verify_coinbase_maturity(&self, _tx: &Transaction) -> Result<(), PoolError> {
-		Ok(())
+	fn coinbase_mature_height(&self, _tx: &Transaction) -> Result<u64, PoolError> {
+		Ok(0)
 	}
 
 	// Mocking this out for these tests.
@@ -157,7 +157,14 @@ pub fn test_setup(
 			accept_fee_base: 0,
 			max_pool_size: 50,
 			max_stempool_size: 50,
+			max_pool_weight: usize::max_value(),
 			mineable_max_weight: 10_000,
+			replace_by_fee: false,
+			replace_by_fee_increment: 0,
+			accept_future_lock_window: 0,
+			source_rate_window_secs: 60,
+			source_max_txs: usize::max_value(),
+			source_max_weight: usize::max_value(),
 		},
 		chain.clone(),
 		verifier_cache.clone(),
@@ -0,0 +1,167 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::hash::Hashed;
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::core::{Block, BlockHeader, Transaction};
+use self::core::global::{self, ChainTypes};
+use self::core::libtx;
+use self::core::pow::Difficulty;
+use self::keychain::{ExtKeychain, Keychain};
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_util as util;
+use std::sync::Arc;
+
+/// Test the fee estimate against a pool holding more than a block worth of
+/// txs with various fee rates.
+#[test]
+fn test_estimate_fee_per_weight() {
+	// Blocks of 150 weight, room for 5 txs of weight 25 next to the coinbase.
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_fee_estimate".to_string();
+	clean_output_dir(db_root.clone());
+
+	{
+		let mut chain = ChainAdapter::init(db_root.clone()).unwrap();
+
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+		let add_block =
+			|prev_header: BlockHeader, txs: Vec<Transaction>, chain: &mut ChainAdapter| {
+				let height = prev_header.height + 1;
+				let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+				let fee = txs.iter().map(|x| x.fee()).sum();
+				let reward = libtx::reward::output(
+					&keychain,
+					&libtx::ProofBuilder::new(&keychain),
+					&key_id,
+					fee,
+					false,
+					height,
+				)
+				.unwrap();
+				let mut block = Block::new(&prev_header, txs, Difficulty::min(), reward).unwrap();
+				block.header.prev_root = prev_header.hash();
+				chain.update_db_for_block(&block);
+				block
+			};
+
+		let block = add_block(BlockHeader::default(), vec![], &mut chain);
+		let header = block.header;
+
+		let values: Vec<u64> = (1..=8).map(|x| x * 1000).collect();
+		let initial_tx = test_transaction_spending_coinbase(&keychain, &header, values.clone());
+		let block = add_block(header, vec![initial_tx], &mut chain);
+		let header = block.header;
+
+		let mut pool = test_setup(Arc::new(chain.clone()), verifier_cache);
+
+		// Nothing to compete with, the floor.
+		assert_eq!(pool.estimate_fee_per_weight(1), 1);
+
+		// 8 txs of weight 4 paying 10 to 80 per weight unit.
+		for (i, value) in values.iter().enumerate() {
+			let fee = 40 * (i as u64 + 1);
+			let tx = test_transaction(&keychain, vec![*value], vec![value - fee]);
+			pool.add_to_pool(test_source(), tx, false, &header).unwrap();
+		}
+		assert_eq!(pool.total_size(), 8);
+
+		// The top block holds the 5 best paying txs, 40 to 80 per weight unit.
+		let mut top_block_rates = vec![80, 70, 60, 50, 40];
+		top_block_rates.sort();
+		let median = top_block_rates[top_block_rates.len() / 2];
+		let estimate = pool.estimate_fee_per_weight(1);
+		assert!(estimate >= median);
+
+		// Everything fits in 2 blocks.
+		assert!(pool.estimate_fee_per_weight(2) < estimate);
+		assert_eq!(pool.estimate_fee_per_weight(2), 1);
+
+		// A pool minimum fee rate is the floor.
+		pool.config.accept_fee_base = 5;
+		assert_eq!(pool.estimate_fee_per_weight(2), 5);
+	}
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
+
+/// Test the fee estimate of a congested pool is in the same unit as the pool
+/// minimum fee rate, the estimate being the floor when every tx pays it.
+#[test]
+fn test_estimate_fee_per_weight_floor_unit() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_fee_estimate_floor".to_string();
+	clean_output_dir(db_root.clone());
+
+	{
+		let mut chain = ChainAdapter::init(db_root.clone()).unwrap();
+
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+		let add_block =
+			|prev_header: BlockHeader, txs: Vec<Transaction>, chain: &mut ChainAdapter| {
+				let height = prev_header.height + 1;
+				let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+				let fee = txs.iter().map(|x| x.fee()).sum();
+				let reward = libtx::reward::output(
+					&keychain,
+					&libtx::ProofBuilder::new(&keychain),
+					&key_id,
+					fee,
+					false,
+					height,
+				)
+				.unwrap();
+				let mut block = Block::new(&prev_header, txs, Difficulty::min(), reward).unwrap();
+				block.header.prev_root = prev_header.hash();
+				chain.update_db_for_block(&block);
+				block
+			};
+
+		let block = add_block(BlockHeader::default(), vec![], &mut chain);
+		let header = block.header;
+
+		let values: Vec<u64> = (1..=8).map(|x| x * 1000).collect();
+		let initial_tx = test_transaction_spending_coinbase(&keychain, &header, values.clone());
+		let block = add_block(header, vec![initial_tx], &mut chain);
+		let header = block.header;
+
+		let mut pool = test_setup(Arc::new(chain.clone()), verifier_cache);
+		pool.config.accept_fee_base = 10;
+		assert_eq!(pool.estimate_fee_per_weight(1), 10);
+
+		// 8 txs of weight 4 paying exactly the minimum, more than a block worth.
+		for value in &values {
+			let tx = test_transaction(&keychain, vec![*value], vec![value - 40]);
+			pool.add_to_pool(test_source(), tx, false, &header).unwrap();
+		}
+		assert_eq!(pool.total_size(), 8);
+
+		// Congested or not, the estimate is the minimum fee rate.
+		assert_eq!(pool.estimate_fee_per_weight(1), 10);
+		assert_eq!(pool.estimate_fee_per_weight(2), 10);
+	}
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
@@ -0,0 +1,114 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::hash::Hashed;
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::core::{Block, BlockHeader, Transaction};
+use self::core::libtx::{self, build};
+use self::core::pow::Difficulty;
+use self::keychain::{ExtKeychain, Keychain};
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_util as util;
+use std::sync::Arc;
+
+/// Test a tx locked a couple of blocks ahead of the chain is held in the
+/// pending area and becomes mineable once the chain reaches its lock height.
+#[test]
+fn test_future_lock_height_pending() {
+	util::init_test_logger();
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_future_lock_height".to_string();
+	clean_output_dir(db_root.clone());
+
+	{
+		let mut chain = ChainAdapter::init(db_root.clone()).unwrap();
+
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+		let add_block =
+			|prev_header: BlockHeader, txs: Vec<Transaction>, chain: &mut ChainAdapter| {
+				let height = prev_header.height + 1;
+				let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+				let fee = txs.iter().map(|x| x.fee()).sum();
+				let reward = libtx::reward::output(
+					&keychain,
+					&libtx::ProofBuilder::new(&keychain),
+					&key_id,
+					fee,
+					false,
+					height,
+				)
+				.unwrap();
+				let mut block = Block::new(&prev_header, txs, Difficulty::min(), reward).unwrap();
+				block.header.prev_root = prev_header.hash();
+				chain.update_db_for_block(&block);
+				block
+			};
+
+		let block = add_block(BlockHeader::default(), vec![], &mut chain);
+		let header = block.header;
+
+		let initial_tx = test_transaction_spending_coinbase(&keychain, &header, vec![100]);
+		let block = add_block(header, vec![initial_tx], &mut chain);
+		let header = block.header;
+
+		let mut pool = test_setup(Arc::new(chain.clone()), verifier_cache);
+		pool.config.accept_future_lock_window = 2;
+		// A single tx from the source, counted on arrival and not on promotion.
+		pool.config.source_max_txs = 1;
+
+		// Locked 2 blocks after the next one.
+		let lock_height = header.height + 1 + 2;
+		let tx = build::transaction(
+			vec![
+				build::input(100, ExtKeychain::derive_key_id(1, 100, 0, 0, 0)),
+				build::output(90, ExtKeychain::derive_key_id(1, 90, 0, 0, 0)),
+				build::with_fee(10),
+				build::with_lock_height(lock_height),
+			],
+			&keychain,
+			&libtx::ProofBuilder::new(&keychain),
+		)
+		.unwrap();
+
+		pool.add_to_pool(test_source(), tx.clone(), false, &header)
+			.unwrap();
+		assert_eq!(pool.total_size(), 0);
+		assert_eq!(pool.pending.len(), 1);
+		assert!(pool.prepare_mineable_transactions().unwrap().is_empty());
+
+		// One block later the tx is still locked.
+		let block = add_block(header, vec![], &mut chain);
+		pool.reconcile_block(&block).unwrap();
+		let header = block.header;
+		assert_eq!(pool.total_size(), 0);
+		assert_eq!(pool.pending.len(), 1);
+
+		// The next block can include it.
+		let block = add_block(header, vec![], &mut chain);
+		pool.reconcile_block(&block).unwrap();
+		let header = block.header;
+		assert_eq!(header.height + 1, lock_height);
+		assert!(pool.pending.is_empty());
+		assert_eq!(pool.prepare_mineable_transactions().unwrap(), vec![tx]);
+	}
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
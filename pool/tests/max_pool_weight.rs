@@ -0,0 +1,130 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Test coverage for the eviction of low fee txs when the pool is full.
+
+pub mod common;
+
+use self::core::core::hash::Hashed;
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::core::{Block, BlockHeader, Transaction};
+use self::core::libtx;
+use self::core::pow::Difficulty;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::PoolError;
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+#[test]
+fn test_max_pool_weight_eviction() {
+	util::init_test_logger();
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_max_pool_weight".to_string();
+	clean_output_dir(db_root.clone());
+
+	{
+		let mut chain = ChainAdapter::init(db_root.clone()).unwrap();
+
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+		let add_block =
+			|prev_header: BlockHeader, txs: Vec<Transaction>, chain: &mut ChainAdapter| {
+				let height = prev_header.height + 1;
+				let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+				let fee = txs.iter().map(|x| x.fee()).sum();
+				let reward = libtx::reward::output(
+					&keychain,
+					&libtx::ProofBuilder::new(&keychain),
+					&key_id,
+					fee,
+					false,
+					height,
+				)
+				.unwrap();
+				let mut block = Block::new(&prev_header, txs, Difficulty::min(), reward).unwrap();
+				block.header.prev_root = prev_header.hash();
+				chain.update_db_for_block(&block);
+				block
+			};
+
+		let block = add_block(BlockHeader::default(), vec![], &mut chain);
+		let header = block.header;
+
+		let initial_tx = test_transaction_spending_coinbase(
+			&keychain,
+			&header,
+			vec![100, 200, 300, 400, 500, 600, 700],
+		);
+		let block = add_block(header, vec![initial_tx], &mut chain);
+		let header = block.header;
+
+		// Room for 4 txs of 1 input, 1 output and 1 kernel (weight 25 each).
+		let mut pool = test_setup(Arc::new(chain.clone()), verifier_cache);
+		pool.config.max_pool_weight = 100;
+
+		let low_1 = test_transaction(&keychain, vec![100], vec![99]);
+		let low_2 = test_transaction(&keychain, vec![200], vec![198]);
+		let low_3 = test_transaction(&keychain, vec![300], vec![297]);
+		let parent = test_transaction(&keychain, vec![400], vec![396]);
+		let child = test_transaction(&keychain, vec![396], vec![386]);
+		let mid = test_transaction(&keychain, vec![500], vec![495]);
+		let lowest = test_transaction(&keychain, vec![600], vec![599]);
+		let high = test_transaction(&keychain, vec![700], vec![680]);
+
+		for tx in vec![&low_1, &low_2, &low_3, &parent] {
+			pool.add_to_pool(test_source(), tx.clone(), false, &header)
+				.unwrap();
+		}
+		assert_eq!(pool.total_size(), 4);
+		assert_eq!(pool.txpool.weight(), 100);
+
+		// Each new tx evicts the lowest fee rate tx in the pool.
+		// The child pays for its parent, both are kept.
+		pool.add_to_pool(test_source(), child.clone(), false, &header)
+			.unwrap();
+		pool.add_to_pool(test_source(), mid.clone(), false, &header)
+			.unwrap();
+		assert_eq!(pool.total_size(), 4);
+
+		// A tx below the eviction floor is rejected, nothing is evicted.
+		assert_eq!(
+			pool.add_to_pool(test_source(), lowest.clone(), false, &header),
+			Err(PoolError::OverCapacity)
+		);
+		assert_eq!(pool.total_size(), 4);
+		assert!(pool.txpool.all_transactions().contains(&low_3));
+
+		pool.add_to_pool(test_source(), high.clone(), false, &header)
+			.unwrap();
+
+		// Only the highest fee rate txs survive, the child along with its parent.
+		assert_eq!(pool.total_size(), 4);
+		assert!(pool.txpool.weight() <= 100);
+		let txs = pool.txpool.all_transactions();
+		for tx in &[&parent, &child, &mid, &high] {
+			assert!(txs.contains(*tx));
+		}
+		for tx in &[&low_1, &low_2, &low_3, &lowest] {
+			assert!(!txs.contains(*tx));
+		}
+	}
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
@@ -0,0 +1,104 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::hash::Hashed;
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::core::{Block, BlockHeader, Transaction};
+use self::core::libtx;
+use self::core::pow::Difficulty;
+use self::keychain::{ExtKeychain, Keychain};
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_util as util;
+use std::path::Path;
+use std::sync::Arc;
+
+/// Test the txpool txs can be saved and restored in a fresh pool, the ones
+/// made invalid by a new block in the meantime being discarded.
+#[test]
+fn test_pool_save_and_load() {
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_pool_persistence".to_string();
+	clean_output_dir(db_root.clone());
+
+	{
+		let mut chain = ChainAdapter::init(db_root.clone()).unwrap();
+
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+		let add_block =
+			|prev_header: BlockHeader, txs: Vec<Transaction>, chain: &mut ChainAdapter| {
+				let height = prev_header.height + 1;
+				let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+				let fee = txs.iter().map(|x| x.fee()).sum();
+				let reward = libtx::reward::output(
+					&keychain,
+					&libtx::ProofBuilder::new(&keychain),
+					&key_id,
+					fee,
+					false,
+					height,
+				)
+				.unwrap();
+				let mut block = Block::new(&prev_header, txs, Difficulty::min(), reward).unwrap();
+				block.header.prev_root = prev_header.hash();
+				chain.update_db_for_block(&block);
+				block
+			};
+
+		let block = add_block(BlockHeader::default(), vec![], &mut chain);
+		let header = block.header;
+
+		let initial_tx = test_transaction_spending_coinbase(&keychain, &header, vec![100, 200]);
+		let tx1 = test_transaction(&keychain, vec![100], vec![90]);
+		let tx2 = test_transaction(&keychain, vec![200], vec![190]);
+
+		let pool_file = Path::new(&db_root).join("txpool.bin");
+		{
+			let mut pool = test_setup(Arc::new(chain.clone()), verifier_cache.clone());
+			for tx in vec![&initial_tx, &tx1, &tx2] {
+				pool.add_to_pool(test_source(), tx.clone(), false, &header)
+					.unwrap();
+			}
+			assert_eq!(pool.total_size(), 3);
+			pool.save_to_disk(&pool_file).unwrap();
+		}
+
+		// A fresh pool restores all the saved txs.
+		{
+			let mut pool = test_setup(Arc::new(chain.clone()), verifier_cache.clone());
+			assert_eq!(pool.load_from_disk(&pool_file, &header).unwrap(), 3);
+			assert_eq!(pool.total_size(), 3);
+		}
+
+		// Now a block confirms the initial tx along with a tx conflicting with tx1.
+		let conflicting_tx = test_transaction(&keychain, vec![100], vec![80]);
+		let block = add_block(header, vec![initial_tx.clone(), conflicting_tx], &mut chain);
+		let header = block.header;
+
+		// Only tx2 is still valid, the other ones are discarded.
+		{
+			let mut pool = test_setup(Arc::new(chain.clone()), verifier_cache.clone());
+			assert_eq!(pool.load_from_disk(&pool_file, &header).unwrap(), 1);
+			assert_eq!(pool.txpool.all_transactions(), vec![tx2.clone()]);
+		}
+	}
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
@@ -0,0 +1,129 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::core::{Block, BlockHeader, Transaction};
+use self::core::libtx;
+use self::core::pow::Difficulty;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::Pool;
+use self::util::RwLock;
+use crate::common::*;
+use chrono::{Duration, Utc};
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+/// Test the txs older than the ttl are swept from the pool along with their
+/// dependents, while the fresh ones remain.
+#[test]
+fn test_remove_expired() {
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_remove_expired".to_string();
+	clean_output_dir(db_root.clone());
+
+	let chain = Arc::new(ChainAdapter::init(db_root.clone()).unwrap());
+
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+	// Initialize a new pool with our chain adapter.
+	let pool = RwLock::new(test_setup(chain.clone(), verifier_cache.clone()));
+
+	let header = {
+		let height = 1;
+		let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+		let reward = libtx::reward::output(
+			&keychain,
+			&libtx::ProofBuilder::new(&keychain),
+			&key_id,
+			0,
+			false,
+			height,
+		)
+		.unwrap();
+		let block = Block::new(&BlockHeader::default(), vec![], Difficulty::min(), reward).unwrap();
+
+		chain.update_db_for_block(&block);
+
+		block.header
+	};
+
+	let initial_tx = test_transaction_spending_coinbase(&keychain, &header, vec![100, 200, 300]);
+	let old_tx = test_transaction(&keychain, vec![100], vec![90]);
+	let child_tx = test_transaction(&keychain, vec![90], vec![85]);
+	let fresh_tx = test_transaction(&keychain, vec![200], vec![190]);
+	let old_stem_tx = test_transaction(&keychain, vec![300], vec![290]);
+
+	// Mock clock, every tx is inserted at the same time and the old ones are
+	// then pushed back by 2 hours.
+	let now = Utc::now();
+	let ttl = Duration::hours(1);
+	let backdate = |pool: &mut Pool, tx: &Transaction| {
+		for entry in pool.entries.iter_mut().filter(|x| x.tx == *tx) {
+			entry.tx_at = now - Duration::hours(2);
+		}
+	};
+
+	{
+		let mut write_pool = pool.write();
+		for tx in vec![&initial_tx, &old_tx, &child_tx, &fresh_tx] {
+			write_pool
+				.add_to_pool(test_source(), tx.clone(), false, &header)
+				.unwrap();
+		}
+		write_pool
+			.add_to_pool(test_source(), old_stem_tx.clone(), true, &header)
+			.unwrap();
+		assert_eq!(write_pool.total_size(), 4);
+		assert_eq!(write_pool.stempool.size(), 1);
+
+		// Nothing is expired yet.
+		assert_eq!(write_pool.remove_expired(now, ttl), 0);
+
+		backdate(&mut write_pool.txpool, &old_tx);
+		backdate(&mut write_pool.stempool, &old_stem_tx);
+	}
+
+	// Advance the clock just short of the ttl of the old txs.
+	{
+		let mut write_pool = pool.write();
+		assert_eq!(
+			write_pool.remove_expired(now - Duration::minutes(61), ttl),
+			0
+		);
+		assert_eq!(write_pool.total_size(), 4);
+	}
+
+	// The old txs are swept, the child of the old tx along with it.
+	{
+		let mut write_pool = pool.write();
+		assert_eq!(write_pool.remove_expired(now, ttl), 3);
+		assert_eq!(write_pool.total_size(), 2);
+		assert_eq!(write_pool.stempool.size(), 0);
+
+		let txs = write_pool.txpool.all_transactions();
+		assert!(txs.contains(&initial_tx));
+		assert!(txs.contains(&fresh_tx));
+		assert!(!txs.contains(&old_tx));
+		assert!(!txs.contains(&child_tx));
+	}
+
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
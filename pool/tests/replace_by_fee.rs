@@ -0,0 +1,130 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::hash::Hashed;
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::core::{Block, BlockHeader};
+use self::core::libtx;
+use self::core::pow::Difficulty;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::PoolError;
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+/// Test a tx paying a higher fee replaces the txpool txs spending the same
+/// inputs, only when replace-by-fee is enabled.
+#[test]
+fn test_replace_by_fee() {
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_replace_by_fee".to_string();
+	clean_output_dir(db_root.clone());
+
+	let chain = Arc::new(ChainAdapter::init(db_root.clone()).unwrap());
+
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+	// Initialize a new pool with our chain adapter.
+	let pool = RwLock::new(test_setup(chain.clone(), verifier_cache.clone()));
+
+	let header = {
+		let height = 1;
+		let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+		let reward = libtx::reward::output(
+			&keychain,
+			&libtx::ProofBuilder::new(&keychain),
+			&key_id,
+			0,
+			false,
+			height,
+		)
+		.unwrap();
+		let block = Block::new(&BlockHeader::default(), vec![], Difficulty::min(), reward).unwrap();
+
+		chain.update_db_for_block(&block);
+
+		block.header
+	};
+
+	// Now create tx to spend a coinbase, giving us some useful outputs for testing
+	// with.
+	let initial_tx = test_transaction_spending_coinbase(&keychain, &header, vec![100, 200]);
+
+	let tx1 = test_transaction(&keychain, vec![100], vec![90]);
+	let child_tx1 = test_transaction(&keychain, vec![90], vec![85]);
+	let other_tx = test_transaction(&keychain, vec![200], vec![190]);
+
+	{
+		let mut write_pool = pool.write();
+		for tx in vec![initial_tx, tx1.clone(), child_tx1.clone(), other_tx.clone()] {
+			let replaced = write_pool
+				.add_to_pool(test_source(), tx, false, &header)
+				.unwrap();
+			assert!(replaced.is_empty());
+		}
+		assert_eq!(write_pool.total_size(), 4);
+	}
+
+	// A conflicting tx is rejected as a double spend by default.
+	let tx2 = test_transaction(&keychain, vec![100], vec![75]);
+	{
+		let mut write_pool = pool.write();
+		assert!(write_pool
+			.add_to_pool(test_source(), tx2.clone(), false, &header)
+			.is_err());
+		assert_eq!(write_pool.total_size(), 4);
+	}
+
+	{
+		let mut write_pool = pool.write();
+		write_pool.config.replace_by_fee = true;
+		write_pool.config.replace_by_fee_increment = 10;
+
+		// The replacement has to pay for the tx and its child, plus the increment.
+		let low_fee_tx = test_transaction(&keychain, vec![100], vec![86]);
+		assert_eq!(
+			write_pool.add_to_pool(test_source(), low_fee_tx, false, &header),
+			Err(PoolError::ReplacementFeeTooLow(25))
+		);
+		assert_eq!(write_pool.total_size(), 4);
+
+		// Stem txs are never replacing anything.
+		assert!(write_pool
+			.add_to_pool(test_source(), tx2.clone(), true, &header)
+			.is_err());
+		assert_eq!(write_pool.total_size(), 4);
+
+		let replaced = write_pool
+			.add_to_pool(test_source(), tx2.clone(), false, &header)
+			.unwrap();
+		assert_eq!(replaced, vec![tx1.hash(), child_tx1.hash()]);
+
+		assert_eq!(write_pool.total_size(), 3);
+		let txs = write_pool.txpool.all_transactions();
+		assert!(txs.contains(&tx2));
+		assert!(txs.contains(&other_tx));
+		assert!(!txs.contains(&tx1));
+		assert!(!txs.contains(&child_tx1));
+	}
+
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
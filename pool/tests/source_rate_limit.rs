@@ -0,0 +1,113 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::core::{Block, BlockHeader};
+use self::core::libtx;
+use self::core::pow::Difficulty;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::{PoolError, TxSource, LOCAL_SOURCE_IDENTIFIER};
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+fn source(identifier: &str) -> TxSource {
+	TxSource {
+		debug_name: "test".to_string(),
+		identifier: identifier.to_string(),
+	}
+}
+
+/// Test a peer flooding us with txs gets rate limited while the other peers
+/// and the local txs are still accepted.
+#[test]
+fn test_source_rate_limit() {
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_source_rate_limit".to_string();
+	clean_output_dir(db_root.clone());
+
+	{
+		let chain = Arc::new(ChainAdapter::init(db_root.clone()).unwrap());
+
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+		let mut pool = test_setup(chain.clone(), verifier_cache.clone());
+		pool.config.source_max_txs = 3;
+
+		let header = {
+			let height = 1;
+			let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+			let reward = libtx::reward::output(
+				&keychain,
+				&libtx::ProofBuilder::new(&keychain),
+				&key_id,
+				0,
+				false,
+				height,
+			)
+			.unwrap();
+			let block =
+				Block::new(&BlockHeader::default(), vec![], Difficulty::min(), reward).unwrap();
+
+			chain.update_db_for_block(&block);
+
+			block.header
+		};
+
+		let initial_tx =
+			test_transaction_spending_coinbase(&keychain, &header, vec![100, 200, 300, 400, 500]);
+		pool.add_to_pool(source(LOCAL_SOURCE_IDENTIFIER), initial_tx, false, &header)
+			.unwrap();
+
+		let txs: Vec<_> = vec![100, 200, 300, 400, 500]
+			.into_iter()
+			.map(|x| test_transaction(&keychain, vec![x], vec![x - 10]))
+			.collect();
+
+		// The first 3 txs from the peer are accepted, the next one is not.
+		for tx in &txs[..3] {
+			pool.add_to_pool(source("1.2.3.4"), tx.clone(), false, &header)
+				.unwrap();
+		}
+		assert_eq!(
+			pool.add_to_pool(source("1.2.3.4"), txs[3].clone(), false, &header),
+			Err(PoolError::SourceRateLimited)
+		);
+		assert_eq!(pool.total_size(), 4);
+
+		// Another peer is not affected.
+		pool.add_to_pool(source("5.6.7.8"), txs[3].clone(), false, &header)
+			.unwrap();
+
+		// Neither are the local txs.
+		pool.add_to_pool(
+			source(LOCAL_SOURCE_IDENTIFIER),
+			txs[4].clone(),
+			false,
+			&header,
+		)
+		.unwrap();
+		assert_eq!(pool.total_size(), 6);
+	}
+
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
@@ -19,10 +19,13 @@ use self::core::core::{transaction, Block, BlockHeader, Weighting};
 use self::core::libtx;
 use self::core::pow::Difficulty;
 use self::keychain::{ExtKeychain, Keychain};
+use self::pool::{PoolEntry, PoolError};
 use self::util::RwLock;
 use crate::common::*;
+use chrono::{Duration, Utc};
 use grin_core as core;
 use grin_keychain as keychain;
+use grin_pool as pool;
 use grin_util as util;
 use std::sync::Arc;
 
@@ -474,3 +477,195 @@ fn test_the_transaction_pool() {
 	// Cleanup db directory
 	clean_output_dir(db_root.clone());
 }
+
+/// Test a tx forming a dependency cycle with a pool tx is rejected.
+#[test]
+fn test_pool_rejects_dependency_cycle() {
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_transaction_pool_cycle".to_string();
+	clean_output_dir(db_root.clone());
+
+	let chain = Arc::new(ChainAdapter::init(db_root.clone()).unwrap());
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+	let mut pool = test_setup(chain.clone(), verifier_cache.clone());
+
+	let header = {
+		let height = 1;
+		let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+		let reward = libtx::reward::output(
+			&keychain,
+			&libtx::ProofBuilder::new(&keychain),
+			&key_id,
+			0,
+			false,
+			height,
+		)
+		.unwrap();
+		let block = Block::new(&BlockHeader::default(), vec![], Difficulty::min(), reward).unwrap();
+		chain.update_db_for_block(&block);
+		block.header
+	};
+
+	// Two txs each spending the output of the other.
+	let key_id1 = ExtKeychain::derive_key_id(1, 11, 0, 0, 0);
+	let key_id2 = ExtKeychain::derive_key_id(1, 12, 0, 0, 0);
+	let builder = libtx::ProofBuilder::new(&keychain);
+	let tx1 = libtx::build::transaction(
+		vec![
+			libtx::build::input(1_000, key_id2.clone()),
+			libtx::build::output(1_000, key_id1.clone()),
+			libtx::build::with_fee(0),
+		],
+		&keychain,
+		&builder,
+	)
+	.unwrap();
+	let tx2 = libtx::build::transaction(
+		vec![
+			libtx::build::input(1_000, key_id1.clone()),
+			libtx::build::output(1_000, key_id2.clone()),
+			libtx::build::with_fee(0),
+		],
+		&keychain,
+		&builder,
+	)
+	.unwrap();
+
+	// Neither tx can get in on its own, so force the first one into the txpool.
+	pool.txpool.entries.push(PoolEntry {
+		src: test_source(),
+		tx_at: Utc::now(),
+		tx: tx1,
+	});
+
+	// Aggregated together the pair cuts through to nothing, reject it instead.
+	assert_eq!(
+		pool.add_to_pool(test_source(), tx2.clone(), false, &header),
+		Err(PoolError::DependencyCycle)
+	);
+	assert_eq!(
+		pool.add_to_pool(test_source(), tx2, true, &header),
+		Err(PoolError::DependencyCycle)
+	);
+	assert_eq!(pool.total_size(), 1);
+	assert_eq!(pool.stempool.size(), 0);
+
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
+
+/// Test stem txs stay private to the stempool, they are not mineable until
+/// their embargo expires and they get fluffed.
+#[test]
+fn test_stem_tx_not_mineable_until_fluffed() {
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_transaction_pool_fluff".to_string();
+	clean_output_dir(db_root.clone());
+
+	let chain = Arc::new(ChainAdapter::init(db_root.clone()).unwrap());
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+	let mut pool = test_setup(chain.clone(), verifier_cache.clone());
+
+	let header = {
+		let height = 1;
+		let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+		let reward = libtx::reward::output(
+			&keychain,
+			&libtx::ProofBuilder::new(&keychain),
+			&key_id,
+			0,
+			false,
+			height,
+		)
+		.unwrap();
+		let block = Block::new(&BlockHeader::default(), vec![], Difficulty::min(), reward).unwrap();
+		chain.update_db_for_block(&block);
+		block.header
+	};
+
+	let initial_tx = test_transaction_spending_coinbase(&keychain, &header, vec![100, 200]);
+	pool.add_to_pool(test_source(), initial_tx.clone(), false, &header)
+		.unwrap();
+
+	let stem_tx = test_transaction(&keychain, vec![100], vec![90]);
+	pool.add_to_pool(test_source(), stem_tx.clone(), true, &header)
+		.unwrap();
+	assert_eq!(pool.total_size(), 1);
+	assert_eq!(pool.stempool.size(), 1);
+
+	// Only the fluffed tx is mineable.
+	let txs = pool.prepare_mineable_transactions().unwrap();
+	assert_eq!(txs, vec![initial_tx.clone()]);
+
+	// Nothing to fluff before the embargo expires.
+	let embargo_secs = 180;
+	assert_eq!(pool.fluff_all_expired(Utc::now(), embargo_secs), Ok(0));
+	assert_eq!(pool.stempool.size(), 1);
+
+	let later = Utc::now() + Duration::seconds(embargo_secs as i64 + 1);
+	assert_eq!(pool.fluff_all_expired(later, embargo_secs), Ok(1));
+	assert_eq!(pool.total_size(), 2);
+	assert!(pool.stempool.is_empty());
+
+	let txs = pool.prepare_mineable_transactions().unwrap();
+	assert_eq!(txs.len(), 2);
+	assert!(txs.contains(&stem_tx));
+
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
+
+/// Test looking up a pool tx (txpool or stempool) by its kernel excess.
+#[test]
+fn test_get_by_kernel_excess() {
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_transaction_pool_kernel_excess".to_string();
+	clean_output_dir(db_root.clone());
+
+	let chain = Arc::new(ChainAdapter::init(db_root.clone()).unwrap());
+	let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+	let mut pool = test_setup(chain.clone(), verifier_cache.clone());
+
+	let header = {
+		let height = 1;
+		let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+		let reward = libtx::reward::output(
+			&keychain,
+			&libtx::ProofBuilder::new(&keychain),
+			&key_id,
+			0,
+			false,
+			height,
+		)
+		.unwrap();
+		let block = Block::new(&BlockHeader::default(), vec![], Difficulty::min(), reward).unwrap();
+		chain.update_db_for_block(&block);
+		block.header
+	};
+
+	let initial_tx = test_transaction_spending_coinbase(&keychain, &header, vec![100, 200]);
+	pool.add_to_pool(test_source(), initial_tx.clone(), false, &header)
+		.unwrap();
+	let stem_tx = test_transaction(&keychain, vec![100], vec![90]);
+	pool.add_to_pool(test_source(), stem_tx.clone(), true, &header)
+		.unwrap();
+	assert_eq!(pool.stempool.size(), 1);
+
+	for tx in &[&initial_tx, &stem_tx] {
+		let excess = tx.kernels()[0].excess;
+		assert_eq!(pool.get_by_kernel_excess(&excess), Some((*tx).clone()));
+		assert!(pool.contains_kernel(&excess));
+	}
+
+	// A tx never added to the pool.
+	let unknown_tx = test_transaction(&keychain, vec![200], vec![190]);
+	let excess = unknown_tx.kernels()[0].excess;
+	assert_eq!(pool.get_by_kernel_excess(&excess), None);
+	assert!(!pool.contains_kernel(&excess));
+
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
@@ -0,0 +1,199 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::core::core::hash::{Hash, Hashed};
+use self::core::core::verifier_cache::LruVerifierCache;
+use self::core::core::{Block, BlockHeader, Transaction};
+use self::core::libtx;
+use self::core::pow::Difficulty;
+use self::keychain::{ExtKeychain, Keychain};
+use self::pool::{PoolAdapter, PoolError, RemovalReason};
+use self::util::RwLock;
+use crate::common::*;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_pool as pool;
+use grin_util as util;
+use std::sync::Arc;
+
+/// Adapter recording the txs removed from the pool.
+#[derive(Default)]
+struct RemovedAdapter {
+	removed: RwLock<Vec<(Hash, RemovalReason)>>,
+}
+
+impl PoolAdapter for RemovedAdapter {
+	fn tx_accepted(&self, _tx: &Transaction) {}
+	fn stem_tx_accepted(&self, _tx: &Transaction) -> Result<(), PoolError> {
+		Ok(())
+	}
+	fn tx_removed(&self, tx: &Transaction, reason: RemovalReason) {
+		self.removed.write().push((tx.hash(), reason));
+	}
+}
+
+/// Test the adapter is told about every tx reconcile_block clears from the
+/// pool once mined.
+#[test]
+fn test_tx_removed_mined() {
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_tx_removed".to_string();
+	clean_output_dir(db_root.clone());
+
+	{
+		let mut chain = ChainAdapter::init(db_root.clone()).unwrap();
+
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+		let add_block =
+			|prev_header: BlockHeader, txs: Vec<Transaction>, chain: &mut ChainAdapter| {
+				let height = prev_header.height + 1;
+				let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+				let fee = txs.iter().map(|x| x.fee()).sum();
+				let reward = libtx::reward::output(
+					&keychain,
+					&libtx::ProofBuilder::new(&keychain),
+					&key_id,
+					fee,
+					false,
+					height,
+				)
+				.unwrap();
+				let mut block = Block::new(&prev_header, txs, Difficulty::min(), reward).unwrap();
+				block.header.prev_root = prev_header.hash();
+				chain.update_db_for_block(&block);
+				block
+			};
+
+		let block = add_block(BlockHeader::default(), vec![], &mut chain);
+		let header = block.header;
+
+		let initial_tx = test_transaction_spending_coinbase(&keychain, &header, vec![10, 20, 30]);
+		let block = add_block(header, vec![initial_tx], &mut chain);
+		let header = block.header;
+
+		let adapter = Arc::new(RemovedAdapter::default());
+		let mut pool = test_setup(Arc::new(chain.clone()), verifier_cache);
+		pool.adapter = adapter.clone();
+
+		let root_tx_1 = test_transaction(&keychain, vec![10, 20], vec![24]);
+		let root_tx_2 = test_transaction(&keychain, vec![30], vec![28]);
+		let child_tx = test_transaction(&keychain, vec![24], vec![22]);
+		for tx in &[&root_tx_1, &root_tx_2, &child_tx] {
+			pool.add_to_pool(test_source(), (*tx).clone(), false, &header)
+				.unwrap();
+		}
+		assert_eq!(pool.total_size(), 3);
+		assert!(adapter.removed.read().is_empty());
+
+		let txs = pool.prepare_mineable_transactions().unwrap();
+		let block = add_block(header, txs, &mut chain);
+
+		pool.reconcile_block(&block).unwrap();
+		assert_eq!(pool.total_size(), 0);
+
+		let removed = adapter.removed.read();
+		assert_eq!(removed.len(), 3);
+		for tx in &[&root_tx_1, &root_tx_2, &child_tx] {
+			assert_eq!(
+				removed.iter().filter(|x| x.0 == tx.hash()).count(),
+				1,
+				"tx {} removed once",
+				tx.hash()
+			);
+		}
+		assert!(removed.iter().all(|x| x.1 == RemovalReason::Mined));
+	}
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
+
+/// Test reconcile_block reports the child txs orphaned by a block
+/// conflicting with their parent.
+#[test]
+fn test_tx_removed_orphaned() {
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+
+	let db_root = ".mwc_tx_removed_orphaned".to_string();
+	clean_output_dir(db_root.clone());
+
+	{
+		let mut chain = ChainAdapter::init(db_root.clone()).unwrap();
+
+		let verifier_cache = Arc::new(RwLock::new(LruVerifierCache::new()));
+
+		let add_block =
+			|prev_header: BlockHeader, txs: Vec<Transaction>, chain: &mut ChainAdapter| {
+				let height = prev_header.height + 1;
+				let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+				let fee = txs.iter().map(|x| x.fee()).sum();
+				let reward = libtx::reward::output(
+					&keychain,
+					&libtx::ProofBuilder::new(&keychain),
+					&key_id,
+					fee,
+					false,
+					height,
+				)
+				.unwrap();
+				let mut block = Block::new(&prev_header, txs, Difficulty::min(), reward).unwrap();
+				block.header.prev_root = prev_header.hash();
+				chain.update_db_for_block(&block);
+				block
+			};
+
+		let block = add_block(BlockHeader::default(), vec![], &mut chain);
+		let header = block.header;
+
+		let initial_tx = test_transaction_spending_coinbase(&keychain, &header, vec![10, 20, 30]);
+		let block = add_block(header, vec![initial_tx], &mut chain);
+		let header = block.header;
+
+		let adapter = Arc::new(RemovedAdapter::default());
+		let mut pool = test_setup(Arc::new(chain.clone()), verifier_cache);
+		pool.adapter = adapter.clone();
+
+		let parent_tx = test_transaction(&keychain, vec![10, 20], vec![24]);
+		let child_tx = test_transaction(&keychain, vec![24], vec![22]);
+		let other_tx = test_transaction(&keychain, vec![30], vec![28]);
+		for tx in &[&parent_tx, &child_tx, &other_tx] {
+			pool.add_to_pool(test_source(), (*tx).clone(), false, &header)
+				.unwrap();
+		}
+		assert_eq!(pool.total_size(), 3);
+
+		// the block spends an input of the parent in another tx
+		let double_spend_tx = test_transaction(&keychain, vec![10], vec![9]);
+		let block = add_block(header, vec![double_spend_tx], &mut chain);
+
+		let orphaned = pool.reconcile_block(&block).unwrap();
+		assert_eq!(orphaned.len(), 1);
+		assert_eq!(orphaned[0].hash(), child_tx.hash());
+		assert_eq!(pool.total_size(), 1);
+
+		let removed = adapter.removed.read();
+		assert_eq!(
+			*removed,
+			vec![
+				(parent_tx.hash(), RemovalReason::Conflict),
+				(child_tx.hash(), RemovalReason::Orphaned),
+			]
+		);
+	}
+	// Cleanup db directory
+	clean_output_dir(db_root.clone());
+}
@@ -0,0 +1,176 @@
+// Copyright 2018 The Grin Developers
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod common;
+
+use self::chain::types::NoopAdapter;
+use self::core::consensus;
+use self::core::core::hash::{Hash, Hashed};
+use self::core::core::verifier_cache::{LruVerifierCache, VerifierCache};
+use self::core::core::{Block, Output, Transaction, TxKernel};
+use self::core::global::{self, ChainTypes};
+use self::core::libtx::{self, build};
+use self::core::pow::{self, Difficulty};
+use self::keychain::{ExtKeychain, Keychain};
+use self::util::RwLock;
+use crate::common::*;
+use chrono::Duration;
+use grin_chain as chain;
+use grin_core as core;
+use grin_keychain as keychain;
+use grin_util as util;
+use std::fs;
+use std::sync::Arc;
+
+/// Verifier cache recording the kernels and outputs it had to let through for
+/// verification, i.e. the cache misses.
+struct RecordingVerifierCache {
+	cache: LruVerifierCache,
+	unverified_kernels: Vec<Hash>,
+	unverified_outputs: Vec<Hash>,
+}
+
+impl RecordingVerifierCache {
+	fn new() -> RecordingVerifierCache {
+		RecordingVerifierCache {
+			cache: LruVerifierCache::new(),
+			unverified_kernels: vec![],
+			unverified_outputs: vec![],
+		}
+	}
+
+	fn clear(&mut self) {
+		self.unverified_kernels.clear();
+		self.unverified_outputs.clear();
+	}
+}
+
+impl VerifierCache for RecordingVerifierCache {
+	fn filter_kernel_sig_unverified(&mut self, kernels: &[TxKernel]) -> Vec<TxKernel> {
+		let res = self.cache.filter_kernel_sig_unverified(kernels);
+		self.unverified_kernels.extend(res.iter().map(|x| x.hash()));
+		res
+	}
+
+	fn filter_rangeproof_unverified(&mut self, outputs: &[Output]) -> Vec<Output> {
+		let res = self.cache.filter_rangeproof_unverified(outputs);
+		self.unverified_outputs.extend(res.iter().map(|x| x.proof.hash()));
+		res
+	}
+
+	fn add_kernel_sig_verified(&mut self, kernels: Vec<TxKernel>) {
+		self.cache.add_kernel_sig_verified(kernels);
+	}
+
+	fn add_rangeproof_verified(&mut self, outputs: Vec<Output>) {
+		self.cache.add_rangeproof_verified(outputs);
+	}
+}
+
+/// Test the chain and the pool can share a single verifier cache, a tx verified
+/// by the pool is not verified again when the chain processes the block
+/// containing it.
+#[test]
+fn test_verifier_cache_shared_by_chain_and_pool() {
+	global::set_mining_mode(ChainTypes::AutomatedTesting);
+	let keychain: ExtKeychain = Keychain::from_random_seed(false).unwrap();
+	let builder = libtx::ProofBuilder::new(&keychain);
+
+	let db_root = ".mwc_shared_verifier_cache".to_string();
+	let chain_dir = ".mwc_shared_verifier_cache_chain";
+	clean_output_dir(db_root.clone());
+	let _ = fs::remove_dir_all(chain_dir);
+
+	let verifier_cache = Arc::new(RwLock::new(RecordingVerifierCache::new()));
+	{
+		let chain = chain::Chain::init(
+			chain_dir.to_string(),
+			Arc::new(NoopAdapter {}),
+			pow::mine_genesis_block().unwrap(),
+			pow::verify_size,
+			verifier_cache.clone(),
+			false,
+		)
+		.unwrap();
+		let pool_chain = Arc::new(ChainAdapter::init(db_root.clone()).unwrap());
+		let mut pool = test_setup(pool_chain.clone(), verifier_cache.clone());
+
+		// Build and process a block on the chain, mirroring it in the pool chain adapter.
+		let add_block = |txs: Vec<Transaction>| -> Block {
+			let prev = chain.head_header().unwrap();
+			let height = prev.height + 1;
+			let key_id = ExtKeychain::derive_key_id(1, height as u32, 0, 0, 0);
+			let fee = txs.iter().map(|x| x.fee()).sum();
+			let reward =
+				libtx::reward::output(&keychain, &builder, &key_id, fee, false, height).unwrap();
+			let next_header_info =
+				consensus::next_difficulty(height, chain.difficulty_iter().unwrap());
+			let mut block = Block::new(&prev, txs, Difficulty::min(), reward).unwrap();
+			block.header.timestamp = prev.timestamp + Duration::seconds(60);
+			block.header.pow.secondary_scaling = next_header_info.secondary_scaling;
+			chain.set_txhashset_roots(&mut block).unwrap();
+			chain
+				.process_block(block.clone(), chain::Options::SKIP_POW)
+				.unwrap();
+			pool_chain.update_db_for_block(&block);
+			block
+		};
+
+		// Mine enough blocks for the first coinbase to mature.
+		for _ in 0..global::coinbase_maturity() + 1 {
+			add_block(vec![]);
+		}
+
+		let key_id1 = ExtKeychain::derive_key_id(1, 1, 0, 0, 0);
+		let key_id2 = ExtKeychain::derive_key_id(1, 100, 0, 0, 0);
+		let amount = consensus::MWC_FIRST_GROUP_REWARD;
+		let tx = build::transaction(
+			vec![
+				build::coinbase_input(amount, key_id1),
+				build::output(amount - 20000, key_id2),
+				build::with_fee(20000),
+			],
+			&keychain,
+			&builder,
+		)
+		.unwrap();
+		let tx_kernel = tx.kernels()[0].hash();
+		let tx_output = tx.outputs()[0].proof.hash();
+
+		// The pool verifies the tx, populating the shared cache.
+		verifier_cache.write().clear();
+		let header = chain.head_header().unwrap();
+		pool.add_to_pool(test_source(), tx, false, &header).unwrap();
+		assert!(verifier_cache.read().unverified_kernels.contains(&tx_kernel));
+		assert!(verifier_cache.read().unverified_outputs.contains(&tx_output));
+
+		// Mine the tx, the chain only has to verify the new coinbase.
+		verifier_cache.write().clear();
+		let txs = pool.prepare_mineable_transactions().unwrap();
+		assert_eq!(txs.len(), 1);
+		let block = add_block(txs);
+		assert_eq!(block.kernels().len(), 2);
+		assert_eq!(chain.head_header().unwrap().hash(), block.hash());
+
+		let cache = verifier_cache.read();
+		assert!(!cache.unverified_kernels.contains(&tx_kernel));
+		assert!(!cache.unverified_outputs.contains(&tx_output));
+		assert_eq!(cache.unverified_kernels.len(), 1);
+		assert_eq!(cache.unverified_outputs.len(), 1);
+	}
+
+	// Cleanup db directories
+	clean_output_dir(db_root.clone());
+	let _ = fs::remove_dir_all(chain_dir);
+}
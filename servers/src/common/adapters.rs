@@ -91,6 +91,7 @@ impl p2p::ChainAdapter for NetToChainAdapter {
 		&self,
 		tx: core::Transaction,
 		stem: bool,
+		peer_info: &PeerInfo,
 	) -> Result<bool, chain::Error> {
 		// nothing much we can do with a new transaction while syncing
 		if self.sync_state.is_syncing() {
@@ -99,7 +100,7 @@ impl p2p::ChainAdapter for NetToChainAdapter {
 
 		let source = pool::TxSource {
 			debug_name: "p2p".to_string(),
-			identifier: "?.?.?.?".to_string(),
+			identifier: peer_info.addr.to_string(),
 		};
 
 		let header = self.chain().head_header()?;
@@ -722,7 +723,7 @@ impl ChainAdapter for ChainToPoolAndNetAdapter {
 		// Reconcile the txpool against the new block *after* we have broadcast it too our peers.
 		// This may be slow and we do not want to delay block propagation.
 		// We only want to reconcile the txpool against the new block *if* total work has increased.
-		let is_reorg = if let BlockStatus::Reorg(_) = status {
+		let is_reorg = if let BlockStatus::Reorg { .. } = status {
 			true
 		} else {
 			false
@@ -741,6 +742,16 @@ impl ChainAdapter for ChainToPoolAndNetAdapter {
 			let _ = self.tx_pool.write().reconcile_reorg_cache(&b.header);
 		}
 	}
+
+	fn block_orphaned(&self, b: &core::Block) {
+		// Missing parents are requested by the body sync, nothing to do here.
+		debug!(
+			"block_orphaned: {} at {}, parent {} missing",
+			b.hash(),
+			b.header.height,
+			b.header.prev_hash
+		);
+	}
 }
 
 impl ChainToPoolAndNetAdapter {
@@ -837,6 +848,10 @@ impl pool::PoolAdapter for PoolToNetAdapter {
 			Ok(())
 		}
 	}
+
+	fn tx_removed(&self, tx: &core::Transaction, reason: pool::RemovalReason) {
+		debug!("tx {} removed from the pool, {:?}", tx.hash(), reason);
+	}
 }
 
 impl PoolToNetAdapter {
@@ -915,10 +930,10 @@ impl pool::BlockChain for PoolToChainAdapter {
 			.map_err(|_| pool::PoolError::Other(format!("failed to validate tx")))
 	}
 
-	fn verify_coinbase_maturity(&self, tx: &Transaction) -> Result<(), pool::PoolError> {
+	fn coinbase_mature_height(&self, tx: &Transaction) -> Result<u64, pool::PoolError> {
 		self.chain()
-			.verify_coinbase_maturity(tx)
-			.map_err(|_| pool::PoolError::ImmatureCoinbase)
+			.coinbase_mature_height(tx)
+			.map_err(|_| pool::PoolError::Other(format!("failed to check coinbase maturity")))
 	}
 
 	fn verify_tx_lock_height(&self, tx: &Transaction) -> Result<(), pool::PoolError> {
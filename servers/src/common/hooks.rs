@@ -117,13 +117,19 @@ impl NetEvents for EventLogger {
 impl ChainEvents for EventLogger {
 	fn on_block_accepted(&self, block: &core::Block, status: &BlockStatus) {
 		match status {
-			BlockStatus::Reorg(depth) => {
+			BlockStatus::Reorg {
+				depth,
+				prev_head,
+				new_head,
+			} => {
 				warn!(
-					"block_accepted (REORG!): {:?} at {} (depth: {}, diff: {})",
+					"block_accepted (REORG!): {:?} at {} (depth: {}, diff: {}, prev head: {:?}, new head: {:?})",
 					block.hash(),
 					block.header.height,
 					depth,
 					block.header.total_difficulty(),
+					prev_head,
+					new_head,
 				);
 			}
 			BlockStatus::Fork => {
@@ -263,19 +269,26 @@ impl WebHook {
 impl ChainEvents for WebHook {
 	fn on_block_accepted(&self, block: &core::Block, status: &BlockStatus) {
 		let status_str = match status {
-			BlockStatus::Reorg(_) => "reorg",
+			BlockStatus::Reorg { .. } => "reorg",
 			BlockStatus::Fork => "fork",
 			BlockStatus::Next => "head",
 		};
 
-		// Add additional `depth` field to the JSON in case of reorg
-		let payload = if let BlockStatus::Reorg(depth) = status {
+		// Add additional `depth`, `prev_head` and `new_head` fields to the JSON in case of reorg
+		let payload = if let BlockStatus::Reorg {
+			depth,
+			prev_head,
+			new_head,
+		} = status
+		{
 			json!({
 				"hash": block.header.hash().to_hex(),
 				"status": status_str,
 				"data": block,
 
-				"depth": depth
+				"depth": depth,
+				"prev_head": prev_head.to_hex(),
+				"new_head": new_head.to_hex()
 			})
 		} else {
 			json!({
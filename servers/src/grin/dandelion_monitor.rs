@@ -19,10 +19,11 @@ use std::thread;
 use std::time::{Duration, Instant};
 
 use crate::common::adapters::DandelionAdapter;
-use crate::core::core::hash::Hashed;
 use crate::core::core::transaction;
 use crate::core::core::verifier_cache::VerifierCache;
-use crate::pool::{DandelionConfig, Pool, PoolEntry, PoolError, TransactionPool, TxSource};
+use crate::pool::{
+	DandelionConfig, Pool, PoolEntry, PoolError, TransactionPool, TxSource, LOCAL_SOURCE_IDENTIFIER,
+};
 use crate::util::{RwLock, StopState};
 
 /// A process to monitor transactions in the stempool.
@@ -151,7 +152,7 @@ fn process_fluff_phase(
 
 	let src = TxSource {
 		debug_name: "fluff".to_string(),
-		identifier: "?.?.?.?".to_string(),
+		identifier: LOCAL_SOURCE_IDENTIFIER.to_string(),
 	};
 
 	tx_pool.add_to_pool(src, agg_tx, false, &header)?;
@@ -169,30 +170,7 @@ fn process_expired_entries(
 		.embargo_secs
 		.expect("embargo_secs config missing")
 		+ thread_rng().gen_range(0, 31);
-	let expired_entries = select_txs_cutoff(&tx_pool.stempool, embargo_secs);
 
-	if expired_entries.is_empty() {
-		return Ok(());
-	}
-
-	debug!("dand_mon: Found {} expired txs.", expired_entries.len());
-
-	let header = tx_pool.chain_head()?;
-
-	let src = TxSource {
-		debug_name: "embargo_expired".to_string(),
-		identifier: "?.?.?.?".to_string(),
-	};
-
-	for entry in expired_entries {
-		let txhash = entry.tx.hash();
-		match tx_pool.add_to_pool(src.clone(), entry.tx, false, &header) {
-			Ok(_) => info!(
-				"dand_mon: embargo expired for {}, fluffed successfully.",
-				txhash
-			),
-			Err(e) => warn!("dand_mon: failed to fluff expired tx {}, {:?}", txhash, e),
-		};
-	}
+	tx_pool.fluff_all_expired(Utc::now(), embargo_secs)?;
 	Ok(())
 }
@@ -49,6 +49,9 @@ use crate::pool;
 use crate::util::file::get_first_line;
 use crate::util::{RwLock, StopState};
 
+/// File in the db root the txpool is saved to on shutdown.
+const TX_POOL_FILE: &str = "txpool.bin";
+
 /// Grin server holding internal structures.
 pub struct Server {
 	/// server config
@@ -173,6 +176,7 @@ impl Server {
 		let genesis = match config.chain_type {
 			global::ChainTypes::AutomatedTesting => genesis::genesis_dev(),
 			global::ChainTypes::UserTesting => genesis::genesis_dev(),
+			global::ChainTypes::Regtest => genesis::genesis_dev(),
 			global::ChainTypes::Floonet => genesis::genesis_floo(),
 			global::ChainTypes::Mainnet => genesis::genesis_main(),
 		};
@@ -213,6 +217,21 @@ impl Server {
 		pool_net_adapter.init(p2p_server.peers.clone());
 		net_adapter.init(p2p_server.peers.clone());
 
+		// Restore the txpool saved on the last shutdown, once the pool adapter
+		// can broadcast the restored txs. The file is removed afterwards so the
+		// same txs are not replayed on every restart.
+		let tx_pool_file = Path::new(&config.db_root).join(TX_POOL_FILE);
+		if tx_pool_file.exists() {
+			let header = shared_chain.head_header()?;
+			match tx_pool.write().load_from_disk(&tx_pool_file, &header) {
+				Ok(n) => info!("Restored {} txs in the txpool", n),
+				Err(e) => warn!("Failed to restore the txpool: {:?}", e),
+			}
+			if let Err(e) = fs::remove_file(&tx_pool_file) {
+				warn!("Failed to remove {:?}: {:?}", tx_pool_file, e);
+			}
+		}
+
 		let mut connect_thread = None;
 
 		if config.p2p_config.seeding_type != p2p::Seeding::Programmatic {
@@ -528,6 +547,12 @@ impl Server {
 				Ok(_) => info!("dandelion_monitor thread stopped"),
 			}
 		}
+		let tx_pool_file = Path::new(&self.config.db_root).join(TX_POOL_FILE);
+		match self.tx_pool.read().save_to_disk(&tx_pool_file) {
+			Ok(_) => info!("txpool saved"),
+			Err(e) => error!("failed to save the txpool: {:?}", e),
+		}
+
 		// this call is blocking and makes sure all peers stop, however
 		// we can't be sure that we stoped a listener blocked on accept, so we don't join the p2p thread
 		self.p2p.stop();
@@ -107,7 +107,10 @@ impl BodySync {
 		// also if the chain is already saturated with orphans, throttle
 		let block_count = cmp::min(
 			cmp::min(100, peers.len() * 10),
-			chain::MAX_ORPHAN_SIZE.saturating_sub(self.chain.orphans_len()) + 1,
+			self.chain
+				.max_orphans()
+				.saturating_sub(self.chain.orphans_len())
+				+ 1,
 		);
 
 		let hashes_to_get = hashes
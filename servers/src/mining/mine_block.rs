@@ -89,7 +89,8 @@ pub fn get_block(
 		let mut new_key_id = key_id.to_owned();
 		match e {
 			self::Error::Chain(c) => match c.kind() {
-				chain::ErrorKind::DuplicateCommitment(_) => {
+				chain::ErrorKind::DuplicateCommitment(_)
+				| chain::ErrorKind::DuplicateUtxo { .. } => {
 					debug!(
 						"Duplicate commit for potential coinbase detected. Trying next derivation."
 					);
@@ -149,7 +150,10 @@ fn build_block(
 
 	// Determine the difficulty our block should be at.
 	// Note: do not keep the difficulty_iter in scope (it has an active batch).
-	let difficulty = consensus::next_difficulty(head.height + 1, chain.difficulty_iter()?);
+	let difficulty = consensus::next_difficulty(
+		head.height + 1,
+		chain.difficulty_iter_bounded(consensus::DIFFICULTY_ADJUST_WINDOW as usize + 1)?,
+	);
 
 	// Extract current "mineable" transactions from the pool.
 	// If this fails for *any* reason then fallback to an empty vec of txs.
@@ -177,7 +181,8 @@ fn build_block(
 	};
 
 	let (output, kernel, block_fees) = get_coinbase(wallet_listener_url, block_fees)?;
-	let mut b = core::Block::from_reward(&head, txs, output, kernel, difficulty.difficulty)?;
+	let mut b =
+		core::Block::from_reward(&head, txs.clone(), output, kernel, difficulty.difficulty)?;
 
 	// making sure we're not spending time mining a useless block
 	b.validate(&head.total_kernel_offset, verifier_cache)?;
@@ -202,13 +207,17 @@ fn build_block(
 				// If this is a duplicate commitment then likely trying to use
 				// a key that hass already been derived but not in the wallet
 				// for some reason, allow caller to retry.
-				chain::ErrorKind::DuplicateCommitment(e) => Err(Error::Chain(
-					chain::ErrorKind::DuplicateCommitment(e).into(),
-				)),
+				chain::ErrorKind::DuplicateCommitment(_)
+				| chain::ErrorKind::DuplicateUtxo { .. } => Err(Error::Chain(e.kind().into())),
 
 				// Some other issue, possibly duplicate kernel
 				_ => {
 					error!("Error setting txhashset root to build a block: {:?}", e);
+					// Point back to the offending tx if one of them is at fault,
+					// so the caller can drop it and rebuild the block.
+					if let Err(tx_err) = chain.validate_block_txs(&txs) {
+						return Err(Error::Chain(tx_err));
+					}
 					Err(Error::Chain(
 						chain::ErrorKind::Other(format!("{:?}", e)).into(),
 					))
@@ -93,6 +93,8 @@ fn real_main() -> i32 {
 		global::ChainTypes::Floonet
 	} else if args.is_present("usernet") {
 		global::ChainTypes::UserTesting
+	} else if args.is_present("regtest") {
+		global::ChainTypes::Regtest
 	} else {
                 // disabling mainnet for now. Will reenable for launch.
 		//global::ChainTypes::Mainnet
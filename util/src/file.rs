@@ -43,6 +43,22 @@ pub fn copy_dir_to(src: &Path, dst: &Path) -> io::Result<u64> {
 	Ok(counter)
 }
 
+/// Total size of the files in a directory and its subdirectories, zero if
+/// the directory does not exist.
+pub fn dir_size(path: &Path) -> io::Result<u64> {
+	if !path.is_dir() {
+		return Ok(0);
+	}
+	let mut size = 0;
+	for entry in WalkDir::new(path) {
+		let entry = entry?;
+		if entry.file_type().is_file() {
+			size += entry.metadata()?.len();
+		}
+	}
+	Ok(size)
+}
+
 /// List directory
 pub fn list_files(path: String) -> Vec<String> {
 	let mut files_vec: Vec<String> = vec![];